@@ -0,0 +1,66 @@
+use clean_code_notes_exercises::exercises::exercise_04::*;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+fn end_of_day_batch(size: usize) -> Vec<Transaction> {
+    (0..size)
+        .map(|n| {
+            Transaction::new(
+                format!("t{}", n),
+                TransactionType::Payment,
+                TransactionStatus::Pending,
+                PaymentMethod::CreditCard,
+                Money::new(n as i64 % 10_000 + 1, Currency::USD),
+            )
+        })
+        .collect()
+}
+
+fn bench_process_transactions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("process_transactions");
+    for size in [1_000, 100_000] {
+        let batch = end_of_day_batch(size);
+
+        group.bench_with_input(BenchmarkId::new("sequential", size), &batch, |b, batch| {
+            b.iter_batched(
+                || batch.clone(),
+                |mut transactions| process_transactions(&mut transactions),
+                BatchSize::LargeInput,
+            )
+        });
+
+        group.bench_with_input(BenchmarkId::new("stream", size), &batch, |b, batch| {
+            b.iter_batched(
+                || batch.clone(),
+                |transactions| {
+                    let mut store = MemoryStore::new();
+                    let mut summary = ProcessingSummary::default();
+                    for processed in process_stream(transactions, &mut store, &FeeTable::default())
+                    {
+                        summary.add(&processed.unwrap().result).unwrap();
+                    }
+                    summary
+                },
+                BatchSize::LargeInput,
+            )
+        });
+
+        #[cfg(feature = "parallel")]
+        group.bench_with_input(BenchmarkId::new("parallel", size), &batch, |b, batch| {
+            b.iter_batched(
+                || batch.clone(),
+                |mut transactions| {
+                    process_transactions_parallel(
+                        &mut transactions,
+                        &mut MemoryStore::new(),
+                        &FeeTable::default(),
+                    )
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_process_transactions);
+criterion_main!(benches);
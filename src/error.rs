@@ -0,0 +1,118 @@
+use crate::exercises::exercise_02::StorageError;
+use crate::exercises::exercise_03::{AccountError, CreateUserError, ImportError, UsernameError};
+use crate::exercises::exercise_04::{
+    FormatError, InstallmentError, LedgerError, MoneyError, ProcessorError, StoreError,
+    TransactionError,
+};
+use crate::exercises::exercise_05::{PricingError, TrackingError, TrackingNumberError};
+use crate::exercises::exercise_06::auth::AuthError;
+use crate::exercises::exercise_06::ocp::PrintError;
+use crate::exercises::exercise_06::rbac::AccessError;
+
+/// ErrorCode trait, a stable identifier for every kind of error
+///
+/// Codes are `area.kind` in snake case. Messages may be reworded, codes are
+/// never changed once released, so callers can match on them.
+pub trait ErrorCode {
+    fn code(&self) -> &'static str;
+}
+
+macro_rules! wrap_errors {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $($variant:ident($error:ty)),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        pub enum $name {
+            $($variant($error),)*
+        }
+
+        impl $name {
+            fn inner(&self) -> &(dyn std::error::Error + 'static) {
+                match self {
+                    $($name::$variant(error) => error,)*
+                }
+            }
+        }
+
+        impl ErrorCode for $name {
+            fn code(&self) -> &'static str {
+                match self {
+                    $($name::$variant(error) => error.code(),)*
+                }
+            }
+        }
+
+        $(
+            impl From<$error> for $name {
+                fn from(error: $error) -> Self {
+                    $name::$variant(error)
+                }
+            }
+        )*
+    };
+}
+
+wrap_errors! {
+    /// Error enum, any error of the crate
+    ///
+    /// Every module error converts into it, so functions using several modules
+    /// can return `Result<T, Error>` and use `?` on all of them. It's transparent:
+    /// the message, code and source are the ones of the error it wraps.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use clean_code_notes_exercises::error::{Error, ErrorCode};
+    /// use clean_code_notes_exercises::exercises::exercise_04::*;
+    ///
+    /// fn total(amounts: &[&str]) -> Result<Money, Error> {
+    ///     let mut total = Money::zero(Currency::USD);
+    ///     for amount in amounts {
+    ///         total = total.checked_add(&amount.parse::<Money>()?)?;
+    ///     }
+    ///     Ok(total)
+    /// }
+    ///
+    /// assert_eq!(total(&["1.00 USD", "2.50 USD"]).unwrap(), Money::new(350, Currency::USD));
+    ///
+    /// let error = total(&["1.00 USD", "2.50 EUR"]).unwrap_err();
+    /// assert_eq!(error.code(), "money.currency_mismatch");
+    /// assert_eq!(format!("{}", error), "Currency mismatch: USD and EUR");
+    /// ```
+    #[derive(Debug)]
+    pub enum Error {
+        Storage(StorageError),
+        CreateUser(CreateUserError),
+        Username(UsernameError),
+        Account(AccountError),
+        Import(ImportError),
+        Transaction(TransactionError),
+        Money(MoneyError),
+        Processor(ProcessorError),
+        Store(StoreError),
+        Format(FormatError),
+        Ledger(LedgerError),
+        Installment(InstallmentError),
+        Pricing(PricingError),
+        Tracking(TrackingError),
+        TrackingNumber(TrackingNumberError),
+        Auth(AuthError),
+        Access(AccessError),
+        Print(PrintError),
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.inner())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.inner().source()
+    }
+}
@@ -10,7 +10,7 @@
 /// assert_eq!(point.x, 1.0);
 /// assert_eq!(point.y, 2.0);
 /// ```
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct Point {
     pub x: f64,
     pub y: f64,
@@ -44,6 +44,7 @@ impl Point {
 /// assert_eq!(bounds.bottom_left.x, 0.0);
 /// assert_eq!(bounds.bottom_left.y, -1.0);
 /// ```
+#[derive(Debug, Clone)]
 pub struct Bounds {
     pub top_left: Point,
     pub top_right: Point,
@@ -81,6 +82,7 @@ impl Bounds {
 /// assert_eq!(rectangle.get_area(), 6.0);
 /// assert_eq!(rectangle.get_perimeter(), 10.0);
 /// ```
+#[derive(Debug, Clone)]
 pub struct Rectangle {
     pub width: f64,
     pub height: f64,
@@ -1,5 +1,85 @@
 /// Eduardo Aire Torres - <eduardo.aire.torres@gmail>
+use crate::error::ErrorCode;
+use crate::retry::{Clock, Retry, RetryError};
 use std::io::Write;
+use std::path::PathBuf;
+
+/// StorageError enum, what went wrong on disk and the I/O error behind it
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::error::ErrorCode;
+/// use clean_code_notes_exercises::exercises::exercise_02::{DiskStorage, StorageError};
+/// use std::error::Error;
+///
+/// let storage = DiskStorage::new("missing_directory");
+///
+/// let error = storage.insert_file("test.txt", "Test").unwrap_err();
+///
+/// assert_eq!(error.code(), "storage.write_file");
+/// assert_eq!(format!("{}", error), "Cannot write file missing_directory/test.txt");
+/// assert_eq!(error.source().is_some(), true);
+/// ```
+#[derive(Debug)]
+pub enum StorageError {
+    CreateDirectory {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    WriteFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+impl StorageError {
+    pub fn io_error(&self) -> &std::io::Error {
+        match self {
+            StorageError::CreateDirectory { source, .. } => source,
+            StorageError::WriteFile { source, .. } => source,
+        }
+    }
+
+    /// Whether trying again may work, like after an interrupted write
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self.io_error().kind(),
+            std::io::ErrorKind::Interrupted
+                | std::io::ErrorKind::TimedOut
+                | std::io::ErrorKind::WouldBlock
+                | std::io::ErrorKind::ResourceBusy
+        )
+    }
+}
+
+impl std::fmt::Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            StorageError::CreateDirectory { path, .. } => {
+                write!(f, "Cannot create directory {}", path.display())
+            }
+            StorageError::WriteFile { path, .. } => {
+                write!(f, "Cannot write file {}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for StorageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.io_error())
+    }
+}
+
+impl ErrorCode for StorageError {
+    fn code(&self) -> &'static str {
+        match self {
+            StorageError::CreateDirectory { .. } => "storage.create_directory",
+            StorageError::WriteFile { .. } => "storage.write_file",
+        }
+    }
+}
 
 /// DiskStorage struct
 ///
@@ -29,9 +109,11 @@ impl DiskStorage {
         std::path::Path::new(&self.storage_directory).to_path_buf()
     }
 
-    pub fn create_directory(&self) -> std::io::Result<()> {
-        if !self.get_directory_path().exists() {
-            std::fs::create_dir_all(&self.storage_directory)?;
+    pub fn create_directory(&self) -> Result<(), StorageError> {
+        let path = self.get_directory_path();
+        if !path.exists() {
+            std::fs::create_dir_all(&path)
+                .map_err(|source| StorageError::CreateDirectory { path, source })?;
         }
         Ok(())
     }
@@ -39,11 +121,22 @@ impl DiskStorage {
     /// # Warning
     ///
     /// Directory must exist in advance
-    pub fn insert_file(&self, file_name: &str, content: &str) -> std::io::Result<()> {
-        let file_path = self.get_directory_path().join(file_name);
-        let mut file = std::fs::File::create(file_path)?;
-        file.write_all(content.as_bytes())?;
-        // TODO: Add error handling
-        Ok(())
+    pub fn insert_file(&self, file_name: &str, content: &str) -> Result<(), StorageError> {
+        let path = self.get_directory_path().join(file_name);
+        std::fs::File::create(&path)
+            .and_then(|mut file| file.write_all(content.as_bytes()))
+            .map_err(|source| StorageError::WriteFile { path, source })
+    }
+
+    /// Inserts a file, trying again while the errors are transient
+    pub fn insert_file_with_retry<C: Clock>(
+        &self,
+        file_name: &str,
+        content: &str,
+        retry: &mut Retry<C>,
+    ) -> Result<(), RetryError<StorageError>> {
+        retry.run(StorageError::is_transient, |_| {
+            self.insert_file(file_name, content)
+        })
     }
 }
@@ -1,3 +1,13 @@
+pub mod account;
+pub use account::*;
+pub mod import;
+pub use import::*;
+pub mod username;
+pub use username::*;
+
+use chrono::Utc;
+
+use crate::error::ErrorCode;
 use regex::Regex;
 
 /// User struct
@@ -5,12 +15,13 @@ use regex::Regex;
 /// # Examples
 ///
 /// ```
-/// use clean_code_notes_exercises::exercises::exercise_03::User;
+/// use clean_code_notes_exercises::exercises::exercise_03::{AccountStatus, User};
 ///
 /// let user = User::new("username".to_string(), "test@test.com".to_string(), "password".to_string());
 /// assert_eq!(user.username, "username");
 /// assert_eq!(user.email, "test@test.com");
 /// assert_eq!(user.password, "password");
+/// assert_eq!(user.status(), &AccountStatus::PendingVerification);
 /// user.save();
 /// ```
 #[derive(Debug)]
@@ -18,6 +29,10 @@ pub struct User {
     pub username: String,
     pub email: String,
     pub password: String,
+    /// Changed only through the account lifecycle methods
+    status: AccountStatus,
+    /// Issued and cleared only by the email verification methods
+    verification_token: Option<VerificationToken>,
 }
 
 impl User {
@@ -26,10 +41,21 @@ impl User {
             username,
             email,
             password,
+            status: AccountStatus::PendingVerification,
+            verification_token: None,
         }
     }
 
-    pub fn save(&self) -> () {
+    pub fn status(&self) -> &AccountStatus {
+        &self.status
+    }
+
+    /// The token waiting to verify the email, `None` once it's verified
+    pub fn verification_token(&self) -> Option<&VerificationToken> {
+        self.verification_token.as_ref()
+    }
+
+    pub fn save(&self) {
         println!("User saved: {:?}", self);
     }
 }
@@ -39,14 +65,16 @@ impl User {
 /// # Examples
 ///
 /// ```
+/// use clean_code_notes_exercises::error::ErrorCode;
 /// use clean_code_notes_exercises::exercises::exercise_03::{CreateUserError, create_user};
 ///
 /// let username = "username".to_string();
 /// let email = "test.com".to_string();
 /// let password = "password".to_string();
 ///
-/// let result = create_user(username, email, password);
-/// assert_eq!(result.unwrap_err(), CreateUserError::InvalidEmail);
+/// let error = create_user(username, email, password).unwrap_err();
+/// assert_eq!(error, CreateUserError::InvalidEmail);
+/// assert_eq!(error.code(), "user.invalid_email");
 /// ```
 #[derive(Debug, Clone, PartialEq)]
 pub enum CreateUserError {
@@ -54,6 +82,10 @@ pub enum CreateUserError {
     InvalidEmail,
     InvalidPassword,
     InvalidUserData,
+    DuplicateUsername,
+    DuplicateEmail,
+    /// The new account could not be set up, like when its token can't be issued
+    Account(AccountError),
 }
 
 impl std::fmt::Display for CreateUserError {
@@ -63,16 +95,49 @@ impl std::fmt::Display for CreateUserError {
             CreateUserError::InvalidEmail => write!(f, "Invalid email"),
             CreateUserError::InvalidPassword => write!(f, "Invalid password"),
             CreateUserError::InvalidUserData => write!(f, "Invalid user data"),
+            CreateUserError::DuplicateUsername => write!(f, "Duplicate username"),
+            CreateUserError::DuplicateEmail => write!(f, "Duplicate email"),
+            CreateUserError::Account(error) => write!(f, "Could not set up the account: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for CreateUserError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CreateUserError::Account(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl ErrorCode for CreateUserError {
+    fn code(&self) -> &'static str {
+        match self {
+            CreateUserError::InvalidUsername => "user.invalid_username",
+            CreateUserError::InvalidEmail => "user.invalid_email",
+            CreateUserError::InvalidPassword => "user.invalid_password",
+            CreateUserError::InvalidUserData => "user.invalid_user_data",
+            CreateUserError::DuplicateUsername => "user.duplicate_username",
+            CreateUserError::DuplicateEmail => "user.duplicate_email",
+            CreateUserError::Account(_) => "user.account",
         }
     }
 }
 
-/// Create user function
+impl From<AccountError> for CreateUserError {
+    fn from(error: AccountError) -> Self {
+        CreateUserError::Account(error)
+    }
+}
+
+/// Create user function, the new user is pending verification and has a
+/// verification token issued
 ///
 /// # Examples
 ///
 /// ```
-/// use clean_code_notes_exercises::exercises::exercise_03::create_user;
+/// use clean_code_notes_exercises::exercises::exercise_03::{create_user, AccountStatus};
 ///
 /// let username = "username".to_string();
 /// let email = "test@test.com".to_string();
@@ -81,15 +146,47 @@ impl std::fmt::Display for CreateUserError {
 /// let result = create_user(username, email, password);
 ///
 /// assert_eq!(result.is_ok(), true);
+///
+/// let user = result.unwrap();
+/// assert_eq!(user.status(), &AccountStatus::PendingVerification);
+/// assert_eq!(user.verification_token().is_some(), true);
 /// ```
 pub fn create_user(
     username: String,
     email: String,
     password: String,
-) -> Result<(), CreateUserError> {
-    Validate::user_data(&username, &email, &password)?;
-    let new_user = User::new(username, email, password);
-    Ok(new_user.save())
+) -> Result<User, CreateUserError> {
+    create_user_with_policy(username, email, password, &UsernamePolicy::default())
+}
+
+/// Create user function with a custom username policy, the username is saved
+/// in its normalized form
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_03::*;
+/// use unicode_script::Script;
+///
+/// let latin_only = UsernamePolicy::new(5, 32, ScriptPolicy::Allowed(vec![Script::Latin]));
+///
+/// let result = create_user_with_policy("José_Luis".to_string(), "test@test.com".to_string(), "password".to_string(), &latin_only);
+/// assert_eq!(result.is_ok(), true);
+///
+/// let result = create_user_with_policy("Дмитрий".to_string(), "test@test.com".to_string(), "password".to_string(), &latin_only);
+/// assert_eq!(result.unwrap_err(), CreateUserError::InvalidUsername);
+/// ```
+pub fn create_user_with_policy(
+    username: String,
+    email: String,
+    password: String,
+    policy: &UsernamePolicy,
+) -> Result<User, CreateUserError> {
+    Validate::user_data_with_policy(&username, &email, &password, policy)?;
+    let mut new_user = User::new(normalize_username(&username), email, password);
+    new_user.issue_verification_token(Utc::now())?;
+    new_user.save();
+    Ok(new_user)
 }
 
 /// This struct contains validation functions for different inputs
@@ -103,6 +200,8 @@ pub fn create_user(
 /// assert_eq!(Validate::password("pass"), false);
 /// assert_eq!(Validate::username("12345"), true);
 /// assert_eq!(Validate::username("123 43"), false);
+/// assert_eq!(Validate::username("Zoë_Müller"), true);
+/// assert_eq!(Validate::username("pаypal"), false);
 /// assert_eq!(Validate::email("test@test"), false);
 /// assert_eq!(Validate::email("test@test.com"), true);
 /// assert_eq!(Validate::user_data("username", "test@test.com", "password").is_ok(), true);
@@ -112,7 +211,11 @@ pub struct Validate;
 
 impl Validate {
     pub fn username(input: &str) -> bool {
-        Regex::new(r"^[A-Za-z\d_]{5,}$").unwrap().is_match(input)
+        Validate::username_with_policy(input, &UsernamePolicy::default())
+    }
+
+    pub fn username_with_policy(input: &str, policy: &UsernamePolicy) -> bool {
+        policy.validate(input).is_ok()
     }
 
     pub fn email(input: &str) -> bool {
@@ -126,9 +229,18 @@ impl Validate {
     }
 
     pub fn user_data(username: &str, email: &str, password: &str) -> Result<(), CreateUserError> {
+        Validate::user_data_with_policy(username, email, password, &UsernamePolicy::default())
+    }
+
+    pub fn user_data_with_policy(
+        username: &str,
+        email: &str,
+        password: &str,
+        policy: &UsernamePolicy,
+    ) -> Result<(), CreateUserError> {
         let mut errors = vec![];
 
-        if !Validate::username(username) {
+        if !Validate::username_with_policy(username, policy) {
             errors.push(CreateUserError::InvalidUsername);
         }
         if !Validate::email(email) {
@@ -0,0 +1,276 @@
+use super::User;
+use crate::error::ErrorCode;
+use chrono::{DateTime, Duration, Utc};
+use rand::{distr::Alphanumeric, RngExt};
+
+/// How long a verification token stays valid after being issued
+pub const VERIFICATION_TOKEN_TTL_HOURS: i64 = 24;
+
+const VERIFICATION_TOKEN_LENGTH: usize = 32;
+
+/// AccountStatus enum
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_03::AccountStatus;
+///
+/// assert_eq!(format!("{}", AccountStatus::PendingVerification), "pending verification");
+/// assert_eq!(format!("{}", AccountStatus::Active), "active");
+/// assert_eq!(AccountStatus::PendingVerification.can_transition_to(&AccountStatus::Active), true);
+/// assert_eq!(AccountStatus::Deleted.can_transition_to(&AccountStatus::Active), false);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccountStatus {
+    PendingVerification,
+    Active,
+    Locked,
+    Deactivated,
+    Deleted,
+}
+
+impl AccountStatus {
+    pub fn can_transition_to(&self, next: &AccountStatus) -> bool {
+        matches!(
+            (self, next),
+            (AccountStatus::PendingVerification, AccountStatus::Active)
+                | (AccountStatus::PendingVerification, AccountStatus::Deleted)
+                | (AccountStatus::Active, AccountStatus::Locked)
+                | (AccountStatus::Active, AccountStatus::Deactivated)
+                | (AccountStatus::Active, AccountStatus::Deleted)
+                | (AccountStatus::Locked, AccountStatus::Active)
+                | (AccountStatus::Locked, AccountStatus::Deleted)
+                | (AccountStatus::Deactivated, AccountStatus::Active)
+                | (AccountStatus::Deactivated, AccountStatus::Deleted)
+        )
+    }
+}
+
+impl std::fmt::Display for AccountStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AccountStatus::PendingVerification => write!(f, "pending verification"),
+            AccountStatus::Active => write!(f, "active"),
+            AccountStatus::Locked => write!(f, "locked"),
+            AccountStatus::Deactivated => write!(f, "deactivated"),
+            AccountStatus::Deleted => write!(f, "deleted"),
+        }
+    }
+}
+
+/// AccountError enum
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_03::{AccountError, AccountStatus};
+///
+/// let error = AccountError::IllegalTransition {
+///     from: AccountStatus::Deleted,
+///     to: AccountStatus::Active,
+/// };
+///
+/// assert_eq!(format!("{}", error), "Account cannot go from deleted to active");
+/// assert_eq!(format!("{}", AccountError::TokenExpired), "Verification token has expired");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccountError {
+    IllegalTransition {
+        from: AccountStatus,
+        to: AccountStatus,
+    },
+    TokenMissing,
+    TokenInvalid,
+    TokenExpired,
+}
+
+impl std::fmt::Display for AccountError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AccountError::IllegalTransition { from, to } => {
+                write!(f, "Account cannot go from {} to {}", from, to)
+            }
+            AccountError::TokenMissing => write!(f, "No verification token has been issued"),
+            AccountError::TokenInvalid => write!(f, "Verification token is invalid"),
+            AccountError::TokenExpired => write!(f, "Verification token has expired"),
+        }
+    }
+}
+
+impl std::error::Error for AccountError {}
+
+impl ErrorCode for AccountError {
+    fn code(&self) -> &'static str {
+        match self {
+            AccountError::IllegalTransition { .. } => "account.illegal_transition",
+            AccountError::TokenMissing => "account.token_missing",
+            AccountError::TokenInvalid => "account.token_invalid",
+            AccountError::TokenExpired => "account.token_expired",
+        }
+    }
+}
+
+/// VerificationToken struct
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_03::VerificationToken;
+/// use chrono::{Duration, Utc};
+///
+/// let now = Utc::now();
+/// let token = VerificationToken::issue(now, Duration::hours(1));
+///
+/// assert_eq!(token.token.len(), 32);
+/// assert_eq!(token.is_expired(now), false);
+/// assert_eq!(token.is_expired(now + Duration::hours(1)), true);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationToken {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl VerificationToken {
+    pub fn issue(now: DateTime<Utc>, ttl: Duration) -> VerificationToken {
+        let token = rand::rng()
+            .sample_iter(Alphanumeric)
+            .take(VERIFICATION_TOKEN_LENGTH)
+            .map(char::from)
+            .collect();
+
+        VerificationToken {
+            token,
+            expires_at: now + ttl,
+        }
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now >= self.expires_at
+    }
+}
+
+/// Account lifecycle of a user
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_03::*;
+/// use chrono::Utc;
+///
+/// let now = Utc::now();
+/// let mut user = User::new("username".to_string(), "test@test.com".to_string(), "password".to_string());
+/// assert_eq!(user.status(), &AccountStatus::PendingVerification);
+///
+/// let token = user.issue_verification_token(now).unwrap().token.clone();
+/// assert_eq!(user.verify_email("wrong", now), Err(AccountError::TokenInvalid));
+/// assert_eq!(user.verify_email(&token, now), Ok(()));
+/// assert_eq!(user.status(), &AccountStatus::Active);
+///
+/// assert_eq!(user.lock(), Ok(()));
+/// assert_eq!(user.deactivate(), Err(AccountError::IllegalTransition {
+///     from: AccountStatus::Locked,
+///     to: AccountStatus::Deactivated,
+/// }));
+/// assert_eq!(user.unlock(), Ok(()));
+/// assert_eq!(user.delete(), Ok(()));
+/// assert_eq!(user.status(), &AccountStatus::Deleted);
+/// ```
+impl User {
+    pub fn issue_verification_token(
+        &mut self,
+        now: DateTime<Utc>,
+    ) -> Result<&VerificationToken, AccountError> {
+        if self.status != AccountStatus::PendingVerification {
+            return Err(AccountError::IllegalTransition {
+                from: self.status.clone(),
+                to: AccountStatus::PendingVerification,
+            });
+        }
+        let ttl = Duration::hours(VERIFICATION_TOKEN_TTL_HOURS);
+        Ok(self
+            .verification_token
+            .insert(VerificationToken::issue(now, ttl)))
+    }
+
+    pub fn verify_email(&mut self, token: &str, now: DateTime<Utc>) -> Result<(), AccountError> {
+        if self.status != AccountStatus::PendingVerification {
+            return Err(AccountError::IllegalTransition {
+                from: self.status.clone(),
+                to: AccountStatus::Active,
+            });
+        }
+
+        let issued_token = self
+            .verification_token
+            .as_ref()
+            .ok_or(AccountError::TokenMissing)?;
+        if !constant_time_eq(issued_token.token.as_bytes(), token.as_bytes()) {
+            return Err(AccountError::TokenInvalid);
+        }
+        if issued_token.is_expired(now) {
+            return Err(AccountError::TokenExpired);
+        }
+
+        self.verification_token = None;
+        self.status = AccountStatus::Active;
+        Ok(())
+    }
+
+    pub fn lock(&mut self) -> Result<(), AccountError> {
+        self.transition_to(AccountStatus::Locked)
+    }
+
+    pub fn unlock(&mut self) -> Result<(), AccountError> {
+        match self.status {
+            AccountStatus::Locked => self.transition_to(AccountStatus::Active),
+            _ => Err(AccountError::IllegalTransition {
+                from: self.status.clone(),
+                to: AccountStatus::Active,
+            }),
+        }
+    }
+
+    pub fn deactivate(&mut self) -> Result<(), AccountError> {
+        self.transition_to(AccountStatus::Deactivated)
+    }
+
+    pub fn reactivate(&mut self) -> Result<(), AccountError> {
+        match self.status {
+            AccountStatus::Deactivated => self.transition_to(AccountStatus::Active),
+            _ => Err(AccountError::IllegalTransition {
+                from: self.status.clone(),
+                to: AccountStatus::Active,
+            }),
+        }
+    }
+
+    /// Deleting an account also wipes its credentials
+    pub fn delete(&mut self) -> Result<(), AccountError> {
+        self.transition_to(AccountStatus::Deleted)?;
+        self.password.clear();
+        self.verification_token = None;
+        Ok(())
+    }
+
+    fn transition_to(&mut self, next: AccountStatus) -> Result<(), AccountError> {
+        if !self.status.can_transition_to(&next) {
+            return Err(AccountError::IllegalTransition {
+                from: self.status.clone(),
+                to: next,
+            });
+        }
+        self.status = next;
+        Ok(())
+    }
+}
+
+/// Compares every byte whatever the first difference, so the time taken
+/// doesn't tell how much of a guessed token was right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .fold(0, |difference, (x, y)| difference | (x ^ y))
+            == 0
+}
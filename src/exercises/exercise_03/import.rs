@@ -0,0 +1,235 @@
+use super::{create_user, username_key, CreateUserError, User, Validate};
+use crate::error::ErrorCode;
+use std::collections::HashSet;
+
+/// ImportField enum, the part of a CSV row a failure refers to
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_03::ImportField;
+///
+/// assert_eq!(format!("{}", ImportField::Username), "username");
+/// assert_eq!(format!("{}", ImportField::Row), "row");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportField {
+    Username,
+    Email,
+    Password,
+    Row,
+}
+
+impl std::fmt::Display for ImportField {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ImportField::Username => write!(f, "username"),
+            ImportField::Email => write!(f, "email"),
+            ImportField::Password => write!(f, "password"),
+            ImportField::Row => write!(f, "row"),
+        }
+    }
+}
+
+/// ImportFailure struct, `row` is the line number in the file, the header
+/// being row 1
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_03::{CreateUserError, ImportField, ImportFailure};
+///
+/// let failure = ImportFailure::new(3, ImportField::Email, CreateUserError::InvalidEmail);
+///
+/// assert_eq!(format!("{}", failure), "Row 3, email: Invalid email");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportFailure {
+    pub row: u64,
+    pub field: ImportField,
+    pub error: CreateUserError,
+}
+
+impl ImportFailure {
+    pub fn new(row: u64, field: ImportField, error: CreateUserError) -> ImportFailure {
+        ImportFailure { row, field, error }
+    }
+}
+
+impl std::fmt::Display for ImportFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Row {}, {}: {}", self.row, self.field, self.error)
+    }
+}
+
+/// ImportError enum, problems that prevent reading the file at all
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_03::ImportError;
+///
+/// assert_eq!(format!("{}", ImportError::MissingColumn("email")), "Missing column: email");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportError {
+    UnreadableHeader(String),
+    MissingColumn(&'static str),
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ImportError::UnreadableHeader(reason) => write!(f, "Unreadable header: {}", reason),
+            ImportError::MissingColumn(column) => write!(f, "Missing column: {}", column),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl ErrorCode for ImportError {
+    fn code(&self) -> &'static str {
+        match self {
+            ImportError::UnreadableHeader(_) => "import.unreadable_header",
+            ImportError::MissingColumn(_) => "import.missing_column",
+        }
+    }
+}
+
+/// ImportReport struct
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub users: Vec<User>,
+    pub failures: Vec<ImportFailure>,
+}
+
+impl ImportReport {
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Imports users from CSV data with `username`, `email` and `password`
+/// columns, bad rows are reported and skipped
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_03::*;
+///
+/// let data = "\
+/// username,email,password
+/// username,test@test.com,password
+/// user name,test@test,password
+/// USERNAME,other@test.com,password
+/// ";
+///
+/// let report = import_users_csv(data.as_bytes()).unwrap();
+///
+/// assert_eq!(report.users.len(), 1);
+/// assert_eq!(report.failures, vec![
+///     ImportFailure::new(3, ImportField::Username, CreateUserError::InvalidUsername),
+///     ImportFailure::new(3, ImportField::Email, CreateUserError::InvalidEmail),
+///     ImportFailure::new(4, ImportField::Username, CreateUserError::DuplicateUsername),
+/// ]);
+/// ```
+pub fn import_users_csv<R: std::io::Read>(reader: R) -> Result<ImportReport, ImportError> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+
+    let headers = csv_reader
+        .headers()
+        .map_err(|e| ImportError::UnreadableHeader(e.to_string()))?
+        .clone();
+    let column = |name: &'static str| {
+        headers
+            .iter()
+            .position(|header| header.eq_ignore_ascii_case(name))
+            .ok_or(ImportError::MissingColumn(name))
+    };
+    let username_column = column("username")?;
+    let email_column = column("email")?;
+    let password_column = column("password")?;
+
+    let mut report = ImportReport::default();
+    let mut usernames = HashSet::new();
+    let mut emails = HashSet::new();
+
+    for (index, record) in csv_reader.records().enumerate() {
+        // Header is row 1, used when the reader can't tell the line
+        let fallback_row = index as u64 + 2;
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let row = e.position().map_or(fallback_row, |p| p.line());
+                report.failures.push(ImportFailure::new(
+                    row,
+                    ImportField::Row,
+                    CreateUserError::InvalidUserData,
+                ));
+                continue;
+            }
+        };
+        let row = record.position().map_or(fallback_row, |p| p.line());
+
+        let (Some(username), Some(email), Some(password)) = (
+            record.get(username_column),
+            record.get(email_column),
+            record.get(password_column),
+        ) else {
+            report.failures.push(ImportFailure::new(
+                row,
+                ImportField::Row,
+                CreateUserError::InvalidUserData,
+            ));
+            continue;
+        };
+
+        let mut row_failures = vec![];
+        let username_key = username_key(username);
+        let email_key = email.to_lowercase();
+
+        if !Validate::username(username) {
+            row_failures.push((ImportField::Username, CreateUserError::InvalidUsername));
+        } else if usernames.contains(&username_key) {
+            row_failures.push((ImportField::Username, CreateUserError::DuplicateUsername));
+        }
+        if !Validate::email(email) {
+            row_failures.push((ImportField::Email, CreateUserError::InvalidEmail));
+        } else if emails.contains(&email_key) {
+            row_failures.push((ImportField::Email, CreateUserError::DuplicateEmail));
+        }
+        if !Validate::password(password) {
+            row_failures.push((ImportField::Password, CreateUserError::InvalidPassword));
+        }
+
+        if !row_failures.is_empty() {
+            report.failures.extend(
+                row_failures
+                    .into_iter()
+                    .map(|(field, error)| ImportFailure::new(row, field, error)),
+            );
+            continue;
+        }
+
+        match create_user(
+            username.to_string(),
+            email.to_string(),
+            password.to_string(),
+        ) {
+            Ok(user) => {
+                usernames.insert(username_key);
+                emails.insert(email_key);
+                report.users.push(user);
+            }
+            Err(error) => report
+                .failures
+                .push(ImportFailure::new(row, ImportField::Row, error)),
+        }
+    }
+
+    Ok(report)
+}
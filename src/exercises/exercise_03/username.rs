@@ -0,0 +1,267 @@
+use crate::error::ErrorCode;
+use caseless::Caseless;
+use icu_properties::props::DefaultIgnorableCodePoint;
+use icu_properties::CodePointSetData;
+use unicode_normalization::UnicodeNormalization;
+use unicode_script::{Script, UnicodeScript};
+use unicode_security::{skeleton, GeneralSecurityProfile, MixedScript};
+
+/// UsernameError enum
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_03::UsernameError;
+///
+/// assert_eq!(format!("{}", UsernameError::TooShort(5)), "Username must have at least 5 characters");
+/// assert_eq!(format!("{}", UsernameError::InvalidCharacter(' ')), "Username contains an invalid character: ' '");
+/// assert_eq!(format!("{}", UsernameError::MixedScripts), "Username mixes characters from different scripts");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum UsernameError {
+    TooShort(usize),
+    TooLong(usize),
+    InvalidCharacter(char),
+    DisallowedScript(Script),
+    MixedScripts,
+    Confusable(String),
+}
+
+impl std::fmt::Display for UsernameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UsernameError::TooShort(min) => {
+                write!(f, "Username must have at least {} characters", min)
+            }
+            UsernameError::TooLong(max) => {
+                write!(f, "Username must have at most {} characters", max)
+            }
+            UsernameError::InvalidCharacter(c) => {
+                write!(f, "Username contains an invalid character: {:?}", c)
+            }
+            UsernameError::DisallowedScript(script) => {
+                write!(
+                    f,
+                    "Username uses a disallowed script: {}",
+                    script.full_name()
+                )
+            }
+            UsernameError::MixedScripts => {
+                write!(f, "Username mixes characters from different scripts")
+            }
+            UsernameError::Confusable(existing) => {
+                write!(f, "Username is confusable with {}", existing)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UsernameError {}
+
+impl ErrorCode for UsernameError {
+    fn code(&self) -> &'static str {
+        match self {
+            UsernameError::TooShort(_) => "username.too_short",
+            UsernameError::TooLong(_) => "username.too_long",
+            UsernameError::InvalidCharacter(_) => "username.invalid_character",
+            UsernameError::DisallowedScript(_) => "username.disallowed_script",
+            UsernameError::MixedScripts => "username.mixed_scripts",
+            UsernameError::Confusable(_) => "username.confusable",
+        }
+    }
+}
+
+/// ScriptPolicy enum, decides which writing systems a username may use
+///
+/// `Recommended` accepts the scripts recommended by Unicode for identifiers
+/// (UAX #31), `Allowed` restricts usernames to an explicit list.
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_03::ScriptPolicy;
+/// use unicode_script::Script;
+///
+/// assert_eq!(ScriptPolicy::Recommended.allows(Script::Cyrillic), true);
+/// assert_eq!(ScriptPolicy::Allowed(vec![Script::Latin]).allows(Script::Cyrillic), false);
+/// assert_eq!(ScriptPolicy::Allowed(vec![Script::Latin]).allows(Script::Common), true);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptPolicy {
+    Recommended,
+    Allowed(Vec<Script>),
+}
+
+impl ScriptPolicy {
+    pub fn allows(&self, script: Script) -> bool {
+        if script == Script::Common || script == Script::Inherited {
+            return true;
+        }
+        match self {
+            ScriptPolicy::Recommended => script.is_recommended(),
+            ScriptPolicy::Allowed(scripts) => scripts.contains(&script),
+        }
+    }
+}
+
+/// UsernamePolicy struct, the rules a username has to follow
+///
+/// Usernames are NFKC normalized before being checked, so the length is
+/// measured on the normalized form.
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_03::{ScriptPolicy, UsernameError, UsernamePolicy};
+/// use unicode_script::Script;
+///
+/// let policy = UsernamePolicy::default();
+///
+/// assert_eq!(policy.validate("José_Luis"), Ok("José_Luis".to_string()));
+/// assert_eq!(policy.validate("ｕｓｅｒｎａｍｅ"), Ok("username".to_string()));
+/// assert_eq!(policy.validate("user"), Err(UsernameError::TooShort(5)));
+/// assert_eq!(policy.validate("user name"), Err(UsernameError::InvalidCharacter(' ')));
+/// assert_eq!(policy.validate("pаypal"), Err(UsernameError::MixedScripts));
+///
+/// let latin_only = UsernamePolicy::new(5, 32, ScriptPolicy::Allowed(vec![Script::Latin]));
+/// assert_eq!(latin_only.validate("Дмитрий"), Err(UsernameError::DisallowedScript(Script::Cyrillic)));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsernamePolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub scripts: ScriptPolicy,
+}
+
+impl Default for UsernamePolicy {
+    fn default() -> Self {
+        UsernamePolicy::new(5, 32, ScriptPolicy::Recommended)
+    }
+}
+
+impl UsernamePolicy {
+    pub fn new(min_length: usize, max_length: usize, scripts: ScriptPolicy) -> UsernamePolicy {
+        UsernamePolicy {
+            min_length,
+            max_length,
+            scripts,
+        }
+    }
+
+    /// Returns the normalized username when it follows the policy
+    pub fn validate(&self, input: &str) -> Result<String, UsernameError> {
+        let username = normalize_username(input);
+        let length = username.chars().count();
+
+        if length < self.min_length {
+            return Err(UsernameError::TooShort(self.min_length));
+        }
+        if length > self.max_length {
+            return Err(UsernameError::TooLong(self.max_length));
+        }
+        if let Some(c) = username.chars().find(|c| !is_username_char(*c)) {
+            return Err(UsernameError::InvalidCharacter(c));
+        }
+        if let Some(script) = username
+            .chars()
+            .map(|c| c.script())
+            .find(|script| !self.scripts.allows(*script))
+        {
+            return Err(UsernameError::DisallowedScript(script));
+        }
+        if !username.as_str().is_single_script() {
+            return Err(UsernameError::MixedScripts);
+        }
+
+        Ok(username)
+    }
+}
+
+fn is_username_char(c: char) -> bool {
+    let is_letter_or_mark = c.is_alphanumeric() || c.script() == Script::Inherited;
+    c == '_' || (is_letter_or_mark && c.identifier_allowed())
+}
+
+/// Normalizes a username to its NFKC form
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_03::normalize_username;
+///
+/// assert_eq!(normalize_username("ｕｓｅｒ"), "user");
+/// assert_eq!(normalize_username("Jose\u{301}"), "José");
+/// ```
+pub fn normalize_username(input: &str) -> String {
+    input.nfkc().collect()
+}
+
+/// Returns the NFKC_Casefold key used to decide if two usernames are the same
+///
+/// Unlike lowercasing, full case folding also matches `ß` with `ss` and
+/// final sigmas with `σ`, and default ignorable characters are dropped.
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_03::username_key;
+///
+/// assert_eq!(username_key("UserName"), username_key("username"));
+/// assert_eq!(username_key("ＵＳＥＲＮＡＭＥ"), "username");
+/// assert_eq!(username_key("Straße"), username_key("STRASSE"));
+/// ```
+pub fn username_key(input: &str) -> String {
+    let ignorable = CodePointSetData::new::<DefaultIgnorableCodePoint>();
+    // Compatibility caseless matching from the Unicode standard, section 3.13
+    normalize_username(input)
+        .chars()
+        .filter(|&c| !ignorable.contains(c))
+        .nfd()
+        .default_case_fold()
+        .nfkd()
+        .default_case_fold()
+        .nfkc()
+        .collect()
+}
+
+/// Returns the confusable skeleton (UTS #39) of the username key,
+/// usernames that look alike share the same skeleton
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_03::username_skeleton;
+///
+/// assert_eq!(username_skeleton("раураl"), username_skeleton("paypal"));
+/// assert_ne!(username_skeleton("paypal"), username_skeleton("paypat"));
+/// ```
+pub fn username_skeleton(input: &str) -> String {
+    skeleton(&username_key(input)).collect()
+}
+
+/// Looks for an existing username the candidate could be mistaken for
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_03::{find_confusable, UsernameError};
+///
+/// let existing = vec!["paypal", "username"];
+///
+/// assert_eq!(find_confusable("раураl", existing.clone()), Err(UsernameError::Confusable("paypal".to_string())));
+/// assert_eq!(find_confusable("UserName", existing.clone()), Err(UsernameError::Confusable("username".to_string())));
+/// assert_eq!(find_confusable("another", existing), Ok(()));
+/// ```
+pub fn find_confusable<'a>(
+    candidate: &str,
+    existing: impl IntoIterator<Item = &'a str>,
+) -> Result<(), UsernameError> {
+    let candidate_skeleton = username_skeleton(candidate);
+    match existing
+        .into_iter()
+        .find(|username| username_skeleton(username) == candidate_skeleton)
+    {
+        Some(username) => Err(UsernameError::Confusable(username.to_string())),
+        None => Ok(()),
+    }
+}
@@ -1,3 +1,37 @@
+pub mod audit;
+pub use audit::*;
+pub mod batch;
+pub use batch::*;
+pub mod exchange;
+pub use exchange::*;
+pub mod fees;
+pub use fees::*;
+pub mod formats;
+pub use formats::*;
+pub mod idempotency;
+pub use idempotency::*;
+pub mod installment;
+pub use installment::*;
+pub mod ledger;
+pub use ledger::*;
+pub mod money;
+pub use money::*;
+pub mod processor;
+pub use processor::*;
+pub mod reconciliation;
+pub use reconciliation::*;
+// Only adds methods to `Transaction`, there's nothing to re-export
+pub mod refund;
+pub mod report;
+pub use report::*;
+pub mod risk;
+pub use risk::*;
+
+use crate::error::ErrorCode;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
 /// TransactionType enum
 ///
 /// # Examples
@@ -8,7 +42,8 @@
 /// assert_eq!(format!("{}", TransactionType::Payment), "payment");
 /// assert_eq!(format!("{}", TransactionType::Refund), "refund");
 /// ```
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum TransactionType {
     Payment,
     Refund,
@@ -25,25 +60,88 @@ impl std::fmt::Display for TransactionType {
 
 /// TransactionStatus enum
 ///
+/// A transaction starts `Pending`, gets `Authorized`, `Captured` and finally
+/// `Settled`. `Failed`, `Voided` and `Refunded` are final, a `Disputed`
+/// transaction ends up `Settled` or `Refunded` once the dispute is resolved.
+///
 /// # Examples
 ///
 /// ```
 /// use clean_code_notes_exercises::exercises::exercise_04::TransactionStatus;
 ///
-/// assert_eq!(format!("{}", TransactionStatus::Open), "open");
-/// assert_eq!(format!("{}", TransactionStatus::Closed), "closed");
+/// assert_eq!(format!("{}", TransactionStatus::Pending), "pending");
+/// assert_eq!(format!("{}", TransactionStatus::Settled), "settled");
+/// assert_eq!(TransactionStatus::Pending.can_transition_to(&TransactionStatus::Authorized), true);
+/// assert_eq!(TransactionStatus::Pending.can_transition_to(&TransactionStatus::Settled), false);
+/// assert_eq!(TransactionStatus::Authorized.next(), Some(TransactionStatus::Captured));
 /// ```
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum TransactionStatus {
-    Open,
-    Closed,
+    Pending,
+    Authorized,
+    Captured,
+    Settled,
+    Failed,
+    Refunded,
+    Voided,
+    Disputed,
+}
+
+impl TransactionStatus {
+    pub fn can_transition_to(&self, next: &TransactionStatus) -> bool {
+        use TransactionStatus::*;
+        matches!(
+            (self, next),
+            (Pending, Authorized)
+                | (Pending, Failed)
+                | (Pending, Voided)
+                | (Authorized, Captured)
+                | (Authorized, Failed)
+                | (Authorized, Voided)
+                | (Captured, Settled)
+                | (Captured, Refunded)
+                | (Captured, Disputed)
+                | (Settled, Refunded)
+                | (Settled, Disputed)
+                | (Disputed, Settled)
+                | (Disputed, Refunded)
+        )
+    }
+
+    /// The next status when processing goes well
+    pub fn next(&self) -> Option<TransactionStatus> {
+        match self {
+            TransactionStatus::Pending => Some(TransactionStatus::Authorized),
+            TransactionStatus::Authorized => Some(TransactionStatus::Captured),
+            TransactionStatus::Captured => Some(TransactionStatus::Settled),
+            _ => None,
+        }
+    }
+
+    /// Whether there is nothing left to process
+    pub fn is_closed(&self) -> bool {
+        matches!(
+            self,
+            TransactionStatus::Settled
+                | TransactionStatus::Failed
+                | TransactionStatus::Refunded
+                | TransactionStatus::Voided
+        )
+    }
 }
 
 impl std::fmt::Display for TransactionStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            TransactionStatus::Open => write!(f, "open"),
-            TransactionStatus::Closed => write!(f, "closed"),
+            TransactionStatus::Pending => write!(f, "pending"),
+            TransactionStatus::Authorized => write!(f, "authorized"),
+            TransactionStatus::Captured => write!(f, "captured"),
+            TransactionStatus::Settled => write!(f, "settled"),
+            TransactionStatus::Failed => write!(f, "failed"),
+            TransactionStatus::Refunded => write!(f, "refunded"),
+            TransactionStatus::Voided => write!(f, "voided"),
+            TransactionStatus::Disputed => write!(f, "disputed"),
         }
     }
 }
@@ -58,12 +156,16 @@ impl std::fmt::Display for TransactionStatus {
 /// assert_eq!(format!("{}", PaymentMethod::CreditCard), "credit card");
 /// assert_eq!(format!("{}", PaymentMethod::PayPal), "PayPal");
 /// assert_eq!(format!("{}", PaymentMethod::Plan), "plan");
+/// assert_eq!(format!("{}", PaymentMethod::Other(String::from("gift card"))), "gift card");
+/// assert_eq!("paypal".parse::<PaymentMethod>(), Ok(PaymentMethod::PayPal));
 /// ```
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum PaymentMethod {
     CreditCard,
     PayPal,
     Plan,
+    /// A method without built-in support, handled by a registered `PaymentProcessor`
+    Other(String),
 }
 
 impl std::fmt::Display for PaymentMethod {
@@ -72,10 +174,42 @@ impl std::fmt::Display for PaymentMethod {
             PaymentMethod::CreditCard => write!(f, "credit card"),
             PaymentMethod::PayPal => write!(f, "PayPal"),
             PaymentMethod::Plan => write!(f, "plan"),
+            PaymentMethod::Other(name) => write!(f, "{}", name),
         }
     }
 }
 
+/// Parses the `Display` strings ignoring case, anything else is an `Other` method
+impl std::str::FromStr for PaymentMethod {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_str() {
+            "credit card" => PaymentMethod::CreditCard,
+            "paypal" => PaymentMethod::PayPal,
+            "plan" => PaymentMethod::Plan,
+            _ => PaymentMethod::Other(s.trim().to_string()),
+        })
+    }
+}
+
+/// Built-in methods are serialized as their lowercase `Display` string
+impl Serialize for PaymentMethod {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            PaymentMethod::Other(name) => serializer.serialize_str(name),
+            method => serializer.serialize_str(&method.to_string().to_lowercase()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for PaymentMethod {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let method = String::deserialize(deserializer)?;
+        method.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 /// TransactionError enum
 ///
 /// # Examples
@@ -88,34 +222,84 @@ impl std::fmt::Display for PaymentMethod {
 /// assert_eq!(format!("{}", TransactionError::Closed(None)), "Your transaction is already closed");
 /// assert_eq!(format!("{}", transaction_closed_error!()), "Your transaction is already closed");
 ///
+/// let illegal_transition = TransactionError::IllegalTransition {
+///   id: String::from("t1"),
+///   from: TransactionStatus::Pending,
+///   to: TransactionStatus::Settled,
+/// };
+///
+/// assert_eq!(format!("{}", illegal_transition), "Transaction t1 cannot go from pending to settled");
+///
 /// let payment_transaction = Transaction::new(
 ///   String::from("t1"),
 ///   TransactionType::Payment,
-///   TransactionStatus::Closed,
+///   TransactionStatus::Settled,
 ///   PaymentMethod::CreditCard,
-///   23.99,
+///   Money::new(2399, Currency::USD),
 /// );
 ///
-/// let payment_expected_error = TransactionError::Closed(Some(payment_transaction.clone()));
+/// let payment_expected_error = TransactionError::Closed(Some(Box::new(payment_transaction.clone())));
 ///
-/// assert_eq!(format!("{}", payment_expected_error), "Your payment is already closed | Transaction: id: t1, type: payment, status: closed, method: credit card, amount: 23.99");
+/// assert_eq!(format!("{}", payment_expected_error), "Your payment is already closed | Transaction: id: t1, type: payment, status: settled, method: credit card, amount: 23.99 USD");
 ///
 /// let refund_transaction = Transaction::new(
 ///   String::from("t2"),
 ///   TransactionType::Refund,
-///   TransactionStatus::Closed,
+///   TransactionStatus::Settled,
 ///   PaymentMethod::PayPal,
-///   100.43,
+///   Money::new(10043, Currency::USD),
 /// );
 ///
-/// let refund_expected_error = TransactionError::Closed(Some(refund_transaction.clone()));
+/// let refund_expected_error = TransactionError::Closed(Some(Box::new(refund_transaction.clone())));
 ///
-/// assert_eq!(format!("{}", payment_expected_error), "Your payment is already closed | Transaction: id: t1, type: payment, status: closed, method: credit card, amount: 23.99");
+/// assert_eq!(format!("{}", payment_expected_error), "Your payment is already closed | Transaction: id: t1, type: payment, status: settled, method: credit card, amount: 23.99 USD");
 /// ```
 #[derive(Debug, Clone, PartialEq)]
 pub enum TransactionError {
     Empty,
-    Closed(Option<Transaction>),
+    Closed(Option<Box<Transaction>>),
+    Amount(MoneyError),
+    IllegalTransition {
+        id: String,
+        from: TransactionStatus,
+        to: TransactionStatus,
+    },
+    OverRefund {
+        id: String,
+        requested: Money,
+        refundable: Money,
+    },
+    Processor(ProcessorError),
+    IdempotencyConflict(String),
+    Store(StoreError),
+    /// The store failed part way through a batch, `completed` are the results
+    /// of the transactions before the failure, the rest were left untouched
+    Interrupted {
+        completed: Vec<TransactionResult>,
+        error: StoreError,
+    },
+    RiskDenied {
+        id: String,
+        rules: Vec<String>,
+    },
+}
+
+impl From<MoneyError> for TransactionError {
+    fn from(error: MoneyError) -> Self {
+        TransactionError::Amount(error)
+    }
+}
+
+impl From<ProcessorError> for TransactionError {
+    fn from(error: ProcessorError) -> Self {
+        TransactionError::Processor(error)
+    }
+}
+
+impl From<StoreError> for TransactionError {
+    fn from(error: StoreError) -> Self {
+        TransactionError::Store(error)
+    }
 }
 
 #[macro_export]
@@ -124,7 +308,7 @@ macro_rules! transaction_closed_error {
         TransactionError::Closed(None)
     };
     ($transaction:expr) => {
-        TransactionError::Closed(Some($transaction))
+        TransactionError::Closed(Some(Box::new($transaction)))
     };
 }
 
@@ -142,6 +326,77 @@ impl std::fmt::Display for TransactionError {
                     }
                 )
             }
+            TransactionError::Amount(error) => write!(f, "Invalid transaction amount: {}", error),
+            TransactionError::IllegalTransition { id, from, to } => {
+                write!(f, "Transaction {} cannot go from {} to {}", id, from, to)
+            }
+            TransactionError::OverRefund {
+                id,
+                requested,
+                refundable,
+            } => write!(
+                f,
+                "Refund of {} exceeds the {} left to refund on transaction {}",
+                requested, refundable, id
+            ),
+            TransactionError::Processor(error) => write!(f, "Payment processor error: {}", error),
+            TransactionError::IdempotencyConflict(id) => write!(
+                f,
+                "Transaction {} was already submitted with different contents",
+                id
+            ),
+            TransactionError::Store(error) => write!(f, "{}", error),
+            TransactionError::Interrupted { completed, error } => write!(
+                f,
+                "Processing stopped after {} transactions: {}",
+                completed.len(),
+                error
+            ),
+            TransactionError::RiskDenied { id, rules } => write!(
+                f,
+                "Transaction {} was denied by risk rules: {}",
+                id,
+                rules.join(", ")
+            ),
+        }
+    }
+}
+
+impl TransactionError {
+    /// Whether processing again may work, like when a gateway is down
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            TransactionError::Processor(ProcessorError::Unavailable(_))
+        )
+    }
+}
+
+impl std::error::Error for TransactionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TransactionError::Amount(error) => Some(error),
+            TransactionError::Processor(error) => Some(error),
+            TransactionError::Store(error) => Some(error),
+            TransactionError::Interrupted { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl ErrorCode for TransactionError {
+    fn code(&self) -> &'static str {
+        match self {
+            TransactionError::Empty => "transaction.empty",
+            TransactionError::Closed(_) => "transaction.closed",
+            TransactionError::Amount(_) => "transaction.amount",
+            TransactionError::IllegalTransition { .. } => "transaction.illegal_transition",
+            TransactionError::OverRefund { .. } => "transaction.over_refund",
+            TransactionError::Processor(_) => "transaction.processor",
+            TransactionError::IdempotencyConflict(_) => "transaction.idempotency_conflict",
+            TransactionError::Store(_) => "transaction.store",
+            TransactionError::Interrupted { .. } => "transaction.interrupted",
+            TransactionError::RiskDenied { .. } => "transaction.risk_denied",
         }
     }
 }
@@ -153,40 +408,67 @@ impl std::fmt::Display for TransactionError {
 /// ```
 /// use clean_code_notes_exercises::exercises::exercise_04::*;
 ///
-/// let transaction = Transaction::new(
+/// let mut transaction = Transaction::new(
 ///    String::from("t1"),
 ///    TransactionType::Payment,
-///    TransactionStatus::Open,
+///    TransactionStatus::Pending,
 ///    PaymentMethod::CreditCard,
-///    23.99,
+///    Money::new(2399, Currency::USD),
 /// );
 ///
 /// assert_eq!(transaction.id, "t1");
 /// assert_eq!(transaction.transaction_type, TransactionType::Payment);
-/// assert_eq!(transaction.status, TransactionStatus::Open);
+/// assert_eq!(transaction.status(), &TransactionStatus::Pending);
 /// assert_eq!(transaction.method, PaymentMethod::CreditCard);
-/// assert_eq!(transaction.amount, 23.99);
+/// assert_eq!(transaction.amount, Money::new(2399, Currency::USD));
+/// assert_eq!(format!("{}", transaction), "Transaction: id: t1, type: payment, status: pending, method: credit card, amount: 23.99 USD");
 ///
-/// assert_eq!(transaction.process(), Ok("Processing payment credit card for amount: 23.99".to_string()));
+/// assert_eq!(transaction.process(), Ok("Processing payment credit card for amount: 23.99 USD".to_string()));
+/// assert_eq!(transaction.status(), &TransactionStatus::Authorized);
+/// assert_eq!(transaction.process().is_ok(), true);
+/// assert_eq!(transaction.process().is_ok(), true);
+/// assert_eq!(transaction.status(), &TransactionStatus::Settled);
+/// assert_eq!(transaction.process(), Err(TransactionError::Closed(Some(Box::new(transaction.clone())))));
 ///
-/// let closed_transaction = Transaction::new(
+/// let mut pending_transaction = Transaction::new(
 ///   String::from("t2"),
 ///   TransactionType::Payment,
-///   TransactionStatus::Closed,
+///   TransactionStatus::Pending,
 ///   PaymentMethod::PayPal,
-///   100.43,
+///   Money::new(10043, Currency::USD),
 /// );
 ///
-/// assert_eq!(closed_transaction.process(), Err(TransactionError::Closed(Some(closed_transaction.clone()))));
-/// assert_eq!(format!("{}", transaction), "Transaction: id: t1, type: payment, status: open, method: credit card, amount: 23.99");
+/// assert_eq!(pending_transaction.settle(), Err(TransactionError::IllegalTransition {
+///   id: String::from("t2"),
+///   from: TransactionStatus::Pending,
+///   to: TransactionStatus::Settled,
+/// }));
+/// assert_eq!(pending_transaction.void(), Ok(()));
+/// assert_eq!(pending_transaction.status(), &TransactionStatus::Voided);
 /// ```
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Transaction {
     pub id: String,
     pub transaction_type: TransactionType,
-    pub status: TransactionStatus,
+    /// Changed only through the transition methods, which keep the audit log
+    status: TransactionStatus,
     pub method: PaymentMethod,
-    pub amount: f64,
+    pub amount: Money,
+    /// Id of the payment a refund reverses, set only by `issue_refund`
+    refund_of: Option<String>,
+    /// How much of a payment has been refunded so far, changed only by refunds
+    refunded: Money,
+    pub created_at: DateTime<Utc>,
+    /// When the status last changed
+    updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub customer: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// Required when deserializing, a transaction can't come back without its history
+    audit_log: Vec<StatusChange>,
 }
 
 impl Transaction {
@@ -195,32 +477,133 @@ impl Transaction {
         transaction_type: TransactionType,
         status: TransactionStatus,
         method: PaymentMethod,
-        amount: f64,
+        amount: Money,
     ) -> Transaction {
+        let now = Utc::now();
         Transaction {
             id,
             transaction_type,
             status,
             method,
+            refund_of: None,
+            refunded: Money::zero(amount.currency),
             amount,
+            created_at: now,
+            updated_at: now,
+            customer: None,
+            description: None,
+            metadata: BTreeMap::new(),
+            audit_log: vec![],
         }
     }
 
+    /// Sets when the transaction was created, before any status change
+    pub fn with_created_at(mut self, created_at: DateTime<Utc>) -> Self {
+        self.created_at = created_at;
+        self.updated_at = created_at;
+        self
+    }
+
+    pub fn with_customer(mut self, customer: &str) -> Self {
+        self.customer = Some(customer.to_string());
+        self
+    }
+
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    pub fn with_metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn status(&self) -> &TransactionStatus {
+        &self.status
+    }
+
+    /// Id of the payment this refund reverses, `None` for payments
+    pub fn refund_of(&self) -> Option<&str> {
+        self.refund_of.as_deref()
+    }
+
+    /// How much of the payment has been refunded so far
+    pub fn refunded(&self) -> Money {
+        self.refunded
+    }
+
+    /// When the status last changed, the creation time if it never did
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.amount.currency
+    }
+
     fn get_processor(&self) -> String {
         format!(
-            "Processing {} {} for amount: {:.2}",
+            "Processing {} {} for amount: {}",
             self.transaction_type, self.method, self.amount
         )
     }
 
-    pub fn process(&self) -> Result<String, TransactionError> {
-        match self.status {
-            TransactionStatus::Closed => {
-                return Err(TransactionError::Closed(Some(self.clone())));
+    /// Advances the transaction one step towards `Settled`
+    pub fn process(&mut self) -> Result<String, TransactionError> {
+        match self.status.next() {
+            Some(next) => {
+                let message = self.get_processor();
+                self.transition_to(next)?;
+                Ok(message)
             }
-            TransactionStatus::Open => Ok(self.get_processor()),
+            None if self.status.is_closed() => {
+                Err(TransactionError::Closed(Some(Box::new(self.clone()))))
+            }
+            None => Err(TransactionError::IllegalTransition {
+                id: self.id.clone(),
+                from: self.status.clone(),
+                to: TransactionStatus::Settled,
+            }),
         }
     }
+
+    /// Changes the status on behalf of the system, see `change_status`
+    pub fn transition_to(&mut self, next: TransactionStatus) -> Result<(), TransactionError> {
+        self.change_status(next, SYSTEM_ACTOR, "", Utc::now())?;
+        Ok(())
+    }
+
+    pub fn authorize(&mut self) -> Result<(), TransactionError> {
+        self.transition_to(TransactionStatus::Authorized)
+    }
+
+    pub fn capture(&mut self) -> Result<(), TransactionError> {
+        self.transition_to(TransactionStatus::Captured)
+    }
+
+    pub fn settle(&mut self) -> Result<(), TransactionError> {
+        self.transition_to(TransactionStatus::Settled)
+    }
+
+    pub fn fail(&mut self) -> Result<(), TransactionError> {
+        self.transition_to(TransactionStatus::Failed)
+    }
+
+    pub fn void(&mut self) -> Result<(), TransactionError> {
+        self.transition_to(TransactionStatus::Voided)
+    }
+
+    /// Refunds whatever is left of the transaction, see `issue_refund` for partial refunds
+    pub fn refund(&mut self) -> Result<(), TransactionError> {
+        self.transition_to(TransactionStatus::Refunded)?;
+        self.refunded = self.amount;
+        Ok(())
+    }
+
+    pub fn dispute(&mut self) -> Result<(), TransactionError> {
+        self.transition_to(TransactionStatus::Disputed)
+    }
 }
 
 impl std::fmt::Display for Transaction {
@@ -241,13 +624,13 @@ impl std::fmt::Display for Transaction {
 /// use clean_code_notes_exercises::exercises::exercise_04::*;
 ///
 /// let transactions = vec![
-///    Transaction {
-///       id: String::from("t1"),
-///       transaction_type: TransactionType::Payment,
-///       status: TransactionStatus::Open,
-///       method: PaymentMethod::CreditCard,
-///       amount: 23.99,
-///   },
+///    Transaction::new(
+///       String::from("t1"),
+///       TransactionType::Payment,
+///       TransactionStatus::Pending,
+///       PaymentMethod::CreditCard,
+///       Money::new(2399, Currency::USD),
+///   ),
 /// ];
 ///
 /// assert_eq!(validate_transactions(&transactions), Ok(()));
@@ -255,7 +638,7 @@ impl std::fmt::Display for Transaction {
 /// let empty_transactions: Vec<Transaction> = vec![];
 /// assert_eq!(validate_transactions(&empty_transactions), Err(TransactionError::Empty));
 /// ```
-pub fn validate_transactions(transactions: &Vec<Transaction>) -> Result<(), TransactionError> {
+pub fn validate_transactions(transactions: &[Transaction]) -> Result<(), TransactionError> {
     match transactions.is_empty() {
         true => Err(TransactionError::Empty),
         false => Ok(()),
@@ -264,47 +647,314 @@ pub fn validate_transactions(transactions: &Vec<Transaction>) -> Result<(), Tran
 
 /// Processes a list of transactions.
 ///
+/// A transaction id repeated in the list with the same status is processed
+/// once, see `process_transactions_with_store` to remember ids across calls.
+///
 /// # Example
 ///
 /// ```
 /// use clean_code_notes_exercises::exercises::exercise_04::*;
 ///
-/// let transactions = vec![
-///     Transaction {
-///         id: String::from("t1"),
-///         transaction_type: TransactionType::Payment,
-///         status: TransactionStatus::Open,
-///         method: PaymentMethod::CreditCard,
-///         amount: 23.99,
-///     },
-///     Transaction {
-///         id: String::from("t2"),
-///         transaction_type: TransactionType::Payment,
-///         status: TransactionStatus::Closed,
-///         method: PaymentMethod::Plan,
-///         amount: 15.99,
-///     },
+/// let mut transactions = vec![
+///     Transaction::new(
+///         String::from("t1"),
+///         TransactionType::Payment,
+///         TransactionStatus::Pending,
+///         PaymentMethod::CreditCard,
+///         Money::new(2399, Currency::USD),
+///     ),
+///     Transaction::new(
+///         String::from("t2"),
+///         TransactionType::Payment,
+///         TransactionStatus::Settled,
+///         PaymentMethod::Plan,
+///         Money::new(1599, Currency::USD),
+///     ),
 /// ];
 ///
 /// let expected = vec![
-///    "Processing payment credit card for amount: 23.99".to_string(),
-///    "Your payment is already closed | Transaction: id: t2, type: payment, status: closed, method: plan, amount: 15.99".to_string(),
+///    "Processing payment credit card for amount: 23.99 USD".to_string(),
+///    "Your payment is already closed | Transaction: id: t2, type: payment, status: settled, method: plan, amount: 15.99 USD".to_string(),
 /// ];
 ///
-/// assert_eq!(process_transactions(&transactions), Ok(expected));
+/// let report = process_transactions(&mut transactions).unwrap();
 ///
-/// let empty_transactions: Vec<Transaction> = vec![];
-/// assert_eq!(process_transactions(&empty_transactions), Err(TransactionError::Empty));
+/// assert_eq!(format_results(&report.results), expected);
+/// assert_eq!(report.summary.processed, 1);
+/// assert_eq!(report.summary.rejected, 1);
+/// assert_eq!(report.summary.total_for_type(&TransactionType::Payment, Currency::USD), Money::new(2399, Currency::USD));
+/// assert_eq!(transactions[0].status(), &TransactionStatus::Authorized);
+///
+/// let mut empty_transactions: Vec<Transaction> = vec![];
+/// assert_eq!(process_transactions(&mut empty_transactions), Err(TransactionError::Empty));
 /// ```
 pub fn process_transactions(
-    transactions: &Vec<Transaction>,
-) -> Result<Vec<String>, TransactionError> {
+    transactions: &mut [Transaction],
+) -> Result<ProcessingReport, TransactionError> {
+    process_transactions_with_store(transactions, &mut MemoryStore::new())
+}
+
+/// Processes a list of transactions, skipping the steps `store` has already seen
+///
+/// Every step of a transaction is recorded under its id and the status it
+/// starts from, so the same payment can be resubmitted to move it along.
+///
+/// # Example
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// let payment = Transaction::new(
+///     String::from("t1"),
+///     TransactionType::Payment,
+///     TransactionStatus::Pending,
+///     PaymentMethod::CreditCard,
+///     Money::new(2399, Currency::USD),
+/// );
+/// let mut store = MemoryStore::new();
+///
+/// let first = process_transactions_with_store(&mut [payment.clone()], &mut store).unwrap();
+/// let replay = process_transactions_with_store(&mut [payment.clone()], &mut store).unwrap();
+///
+/// assert_eq!(replay.summary.replayed, 1);
+/// assert_eq!(format_results(&replay.results), format_results(&first.results));
+///
+/// let mut changed = payment.clone();
+/// changed.amount = Money::new(100, Currency::USD);
+/// let conflict = process_transactions_with_store(&mut [changed], &mut store).unwrap();
+///
+/// assert_eq!(conflict.results[0].error(), Some(&TransactionError::IdempotencyConflict(String::from("t1"))));
+/// ```
+pub fn process_transactions_with_store(
+    transactions: &mut [Transaction],
+    store: &mut impl IdempotencyStore,
+) -> Result<ProcessingReport, TransactionError> {
+    process_transactions_with_fees(transactions, store, &FeeTable::default())
+}
+
+/// Processes a list of transactions, charging the fees and taxes of `fees`
+///
+/// # Example
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// let fees = FeeTable::new()
+///     .with_schedule(PaymentMethod::CreditCard, FeeSchedule::new(Money::new(30, Currency::USD), 290))
+///     .with_schedule(PaymentMethod::PayPal, FeeSchedule::new(Money::zero(Currency::USD), 349))
+///     .with_tax(TaxRule::new("Sales tax", 500, TaxBase::Amount).only_in(Currency::USD));
+/// let mut transactions = vec![
+///     Transaction::new(
+///         String::from("t1"),
+///         TransactionType::Payment,
+///         TransactionStatus::Pending,
+///         PaymentMethod::CreditCard,
+///         Money::new(10000, Currency::USD),
+///     ),
+///     Transaction::new(
+///         String::from("t2"),
+///         TransactionType::Payment,
+///         TransactionStatus::Pending,
+///         PaymentMethod::PayPal,
+///         Money::new(2000, Currency::USD),
+///     ),
+/// ];
+///
+/// let mut store = MemoryStore::new();
+/// let authorized = process_transactions_with_fees(&mut transactions, &mut store, &fees).unwrap();
+/// assert_eq!(authorized.results[0].charges, None);
+///
+/// // Fees and taxes are charged once, when the payments are captured
+/// let report = process_transactions_with_fees(&mut transactions, &mut store, &fees).unwrap();
+///
+/// assert_eq!(report.results[1].charges.unwrap().fee, Money::new(70, Currency::USD));
+/// assert_eq!(report.summary.total_fees(Currency::USD), Money::new(390, Currency::USD));
+/// assert_eq!(report.summary.total_taxes(Currency::USD), Money::new(600, Currency::USD));
+/// assert_eq!(report.summary.total_net(Currency::USD), Money::new(11010, Currency::USD));
+///
+/// let settled = process_transactions_with_fees(&mut transactions, &mut store, &fees).unwrap();
+/// assert_eq!(settled.summary.total_fees(Currency::USD), Money::zero(Currency::USD));
+/// ```
+pub fn process_transactions_with_fees(
+    transactions: &mut [Transaction],
+    store: &mut impl IdempotencyStore,
+    fees: &FeeTable,
+) -> Result<ProcessingReport, TransactionError> {
+    process_each(transactions, store, fees, Transaction::process)
+}
+
+/// Processes a list of transactions through the gateways of `registry`
+///
+/// Payments are authorized and captured by the gateway of their method, and
+/// refunds are sent to it, see `ProcessorRegistry::process`. A step the
+/// store has already seen is replayed without calling the gateway again.
+///
+/// # Example
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// let mut registry = ProcessorRegistry::simulated();
+/// let mut store = MemoryStore::new();
+/// let fees = FeeTable::default();
+/// let mut payment = [Transaction::new(
+///     String::from("t1"),
+///     TransactionType::Payment,
+///     TransactionStatus::Pending,
+///     PaymentMethod::CreditCard,
+///     Money::new(2399, Currency::USD),
+/// )];
+///
+/// process_transactions_with_registry(&mut payment, &mut store, &fees, &mut registry).unwrap();
+/// let captured =
+///     process_transactions_with_registry(&mut payment, &mut store, &fees, &mut registry).unwrap();
+/// assert_eq!(
+///     format_results(&captured.results),
+///     vec!["Processing payment credit card for amount: 23.99 USD | reference: CARD-000002"]
+/// );
+///
+/// let mut refund = [payment[0]
+///     .issue_refund(String::from("r1"), Money::new(399, Currency::USD))
+///     .unwrap()];
+/// let refunded =
+///     process_transactions_with_registry(&mut refund, &mut store, &fees, &mut registry).unwrap();
+/// assert_eq!(
+///     format_results(&refunded.results),
+///     vec!["Processing refund credit card for amount: 3.99 USD | reference: CARD-R000001"]
+/// );
+/// ```
+pub fn process_transactions_with_registry(
+    transactions: &mut [Transaction],
+    store: &mut impl IdempotencyStore,
+    fees: &FeeTable,
+    registry: &mut ProcessorRegistry,
+) -> Result<ProcessingReport, TransactionError> {
+    process_each(transactions, store, fees, |transaction| {
+        registry.process(transaction)
+    })
+}
+
+/// Processes each transaction once, moving it a step with `advance`
+fn process_each(
+    transactions: &mut [Transaction],
+    store: &mut impl IdempotencyStore,
+    fees: &FeeTable,
+    mut advance: impl FnMut(&mut Transaction) -> Result<String, TransactionError>,
+) -> Result<ProcessingReport, TransactionError> {
     validate_transactions(transactions)?;
 
-    let results: Vec<String> = transactions
+    let mut results = Vec::with_capacity(transactions.len());
+    for transaction in transactions.iter_mut() {
+        match idempotency::process_once(transaction, store, fees, &mut advance) {
+            Ok(result) => results.push(result),
+            Err(error) => {
+                return Err(TransactionError::Interrupted {
+                    completed: results,
+                    error,
+                })
+            }
+        }
+    }
+    let summary = ProcessingSummary::from_results(&results)?;
+
+    Ok(ProcessingReport { results, summary })
+}
+
+/// Rounding used when converting amounts to the base currency
+pub const CONVERSION_ROUNDING: RoundingMode = RoundingMode::HalfEven;
+
+/// NormalizedReport struct, processing results with totals in a base currency
+#[derive(Debug, Clone, PartialEq)]
+pub struct NormalizedReport {
+    pub results: Vec<TransactionResult>,
+    pub payments: Money,
+    pub refunds: Money,
+    /// Ids of processed transactions left out of the totals for lack of a rate
+    pub missing_rates: Vec<String>,
+}
+
+impl NormalizedReport {
+    /// Payments minus refunds
+    pub fn net(&self) -> Result<Money, MoneyError> {
+        self.payments.checked_sub(&self.refunds)
+    }
+}
+
+/// Processes a list of transactions and totals the processed ones in `base`
+///
+/// # Example
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// let mut transactions = vec![
+///     Transaction::new(
+///         String::from("t1"),
+///         TransactionType::Payment,
+///         TransactionStatus::Pending,
+///         PaymentMethod::CreditCard,
+///         Money::new(1000, Currency::EUR),
+///     ),
+///     Transaction::new(
+///         String::from("t2"),
+///         TransactionType::Refund,
+///         TransactionStatus::Pending,
+///         PaymentMethod::PayPal,
+///         Money::new(250, Currency::USD),
+///     ),
+///     Transaction::new(
+///         String::from("t3"),
+///         TransactionType::Payment,
+///         TransactionStatus::Pending,
+///         PaymentMethod::Plan,
+///         Money::new(500, Currency::GBP),
+///     ),
+/// ];
+///
+/// let rates = StaticRateTable::new()
+///     .with_rate(Currency::EUR, Currency::USD, "1.10")
+///     .unwrap();
+///
+/// let report = process_transactions_normalized(&mut transactions, Currency::USD, &rates).unwrap();
+///
+/// assert_eq!(report.payments, Money::new(1100, Currency::USD));
+/// assert_eq!(report.refunds, Money::new(250, Currency::USD));
+/// assert_eq!(report.net(), Ok(Money::new(850, Currency::USD)));
+/// assert_eq!(report.missing_rates, vec!["t3".to_string()]);
+/// ```
+pub fn process_transactions_normalized(
+    transactions: &mut [Transaction],
+    base: Currency,
+    rates: &impl ExchangeRateProvider,
+) -> Result<NormalizedReport, TransactionError> {
+    let processed = process_transactions(transactions)?;
+
+    let mut report = NormalizedReport {
+        results: vec![],
+        payments: Money::zero(base),
+        refunds: Money::zero(base),
+        missing_rates: vec![],
+    };
+
+    for result in processed
+        .results
         .iter()
-        .map(|transaction| transaction.process().unwrap_or_else(|e| e.to_string()))
-        .collect();
+        .filter(|result| result.is_processed())
+    {
+        let amount = match rates.convert(&result.amount, base, CONVERSION_ROUNDING) {
+            Ok(amount) => amount,
+            Err(MoneyError::MissingRate(_, _)) => {
+                report.missing_rates.push(result.id.clone());
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        match result.transaction_type {
+            TransactionType::Payment => report.payments = report.payments.checked_add(&amount)?,
+            TransactionType::Refund => report.refunds = report.refunds.checked_add(&amount)?,
+        }
+    }
+    report.results = processed.results;
 
-    Ok(results)
+    Ok(report)
 }
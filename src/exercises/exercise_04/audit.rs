@@ -0,0 +1,95 @@
+use super::{Transaction, TransactionError, TransactionStatus};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Actor recorded for status changes made by the processing code itself
+pub const SYSTEM_ACTOR: &str = "system";
+
+/// StatusChange struct, an entry of a transaction's audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusChange {
+    pub from: TransactionStatus,
+    pub to: TransactionStatus,
+    pub actor: String,
+    pub reason: String,
+    pub at: DateTime<Utc>,
+}
+
+/// Audit log of a transaction
+///
+/// Every status change is appended to the log, the ones made through
+/// `transition_to` and its shortcuts are recorded as the system's.
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+/// use chrono::{TimeZone, Utc};
+///
+/// let now = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+/// let mut payment = Transaction::new(
+///    String::from("t1"),
+///    TransactionType::Payment,
+///    TransactionStatus::Pending,
+///    PaymentMethod::CreditCard,
+///    Money::new(2399, Currency::USD),
+/// )
+/// .with_created_at(now);
+///
+/// payment.authorize().unwrap();
+/// payment.change_status(TransactionStatus::Voided, "ops@shop.com", "Customer cancelled", now).unwrap();
+///
+/// assert_eq!(payment.audit_log().len(), 2);
+/// assert_eq!(payment.audit_log()[0].actor, SYSTEM_ACTOR);
+/// assert_eq!(payment.changes_by("ops@shop.com")[0].from, TransactionStatus::Authorized);
+/// assert_eq!(payment.updated_at(), now);
+/// ```
+impl Transaction {
+    /// Moves the transaction to `next` on behalf of `actor` and records why
+    pub fn change_status(
+        &mut self,
+        next: TransactionStatus,
+        actor: &str,
+        reason: &str,
+        now: DateTime<Utc>,
+    ) -> Result<&StatusChange, TransactionError> {
+        if !self.status.can_transition_to(&next) {
+            return Err(TransactionError::IllegalTransition {
+                id: self.id.clone(),
+                from: self.status.clone(),
+                to: next,
+            });
+        }
+
+        let from = std::mem::replace(&mut self.status, next.clone());
+        self.updated_at = now;
+        self.audit_log.push(StatusChange {
+            from,
+            to: next,
+            actor: actor.to_string(),
+            reason: reason.to_string(),
+            at: now,
+        });
+        Ok(&self.audit_log[self.audit_log.len() - 1])
+    }
+
+    pub fn audit_log(&self) -> &[StatusChange] {
+        &self.audit_log
+    }
+
+    pub fn changes_by(&self, actor: &str) -> Vec<&StatusChange> {
+        self.audit_log
+            .iter()
+            .filter(|change| change.actor == actor)
+            .collect()
+    }
+
+    /// When the transaction last moved to `status`, if it ever did
+    pub fn changed_to(&self, status: &TransactionStatus) -> Option<DateTime<Utc>> {
+        self.audit_log
+            .iter()
+            .rev()
+            .find(|change| change.to == *status)
+            .map(|change| change.at)
+    }
+}
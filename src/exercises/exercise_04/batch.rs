@@ -0,0 +1,236 @@
+use super::fees::FeeTable;
+use super::idempotency::{process_once, IdempotencyStore, StoreError};
+use super::report::TransactionResult;
+use super::Transaction;
+
+/// ProcessedTransaction struct, a transaction after processing and what it returned
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessedTransaction {
+    pub transaction: Transaction,
+    pub result: TransactionResult,
+}
+
+/// ProcessedStream struct, processes transactions as they are pulled from it
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// let transactions = (1..=3).map(|n| {
+///     Transaction::new(
+///         format!("t{}", n),
+///         TransactionType::Payment,
+///         TransactionStatus::Pending,
+///         PaymentMethod::CreditCard,
+///         Money::new(1000, Currency::USD),
+///     )
+/// });
+///
+/// let mut store = MemoryStore::new();
+/// let mut summary = ProcessingSummary::default();
+/// for processed in process_stream(transactions, &mut store, &FeeTable::default()) {
+///     let processed = processed.unwrap();
+///     assert_eq!(processed.transaction.status(), &TransactionStatus::Authorized);
+///     summary.add(&processed.result).unwrap();
+/// }
+///
+/// assert_eq!(summary.processed, 3);
+/// assert_eq!(summary.total_for_type(&TransactionType::Payment, Currency::USD), Money::new(3000, Currency::USD));
+/// ```
+pub struct ProcessedStream<'s, I, S> {
+    transactions: I,
+    store: &'s mut S,
+    fees: &'s FeeTable,
+}
+
+impl<I, S> Iterator for ProcessedStream<'_, I, S>
+where
+    I: Iterator<Item = Transaction>,
+    S: IdempotencyStore,
+{
+    type Item = Result<ProcessedTransaction, StoreError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut transaction = self.transactions.next()?;
+        Some(
+            process_once(
+                &mut transaction,
+                self.store,
+                self.fees,
+                Transaction::process,
+            )
+            .map(|result| ProcessedTransaction {
+                transaction,
+                result,
+            }),
+        )
+    }
+}
+
+/// Processes transactions one at a time without collecting them first,
+/// charging the fees and taxes of `fees` like `process_transactions_with_fees`
+pub fn process_stream<'s, I, S>(
+    transactions: I,
+    store: &'s mut S,
+    fees: &'s FeeTable,
+) -> ProcessedStream<'s, I::IntoIter, S>
+where
+    I: IntoIterator<Item = Transaction>,
+    S: IdempotencyStore,
+{
+    ProcessedStream {
+        transactions: transactions.into_iter(),
+        store,
+        fees,
+    }
+}
+
+#[cfg(feature = "parallel")]
+mod parallel {
+    use super::super::fees::FeeTable;
+    use super::super::idempotency::{
+        conflict, fingerprint, idempotency_key, remember, replay, submission_key,
+        IdempotencyRecord, IdempotencyStore,
+    };
+    use super::super::report::{ProcessingReport, ProcessingSummary, TransactionResult};
+    use super::super::{validate_transactions, Transaction, TransactionError};
+    use rayon::prelude::*;
+    use std::collections::{HashMap, HashSet};
+
+    /// What a transaction of the batch gets, worked out before processing
+    enum Step {
+        Process,
+        /// Replays the step processed earlier in the batch at this index
+        ReplayOf(usize),
+        Replay(IdempotencyRecord),
+        Conflict,
+    }
+
+    /// Processes a list of transactions on all cores, results keep the input order
+    ///
+    /// Gives the same report as `process_transactions_with_fees` with the
+    /// same store and fees. The store is read and written in input order,
+    /// only the processing of the steps runs in parallel, so a repeated step
+    /// of an id is processed once and copies with other contents than the
+    /// first submission of their id are conflicts.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clean_code_notes_exercises::exercises::exercise_04::*;
+    ///
+    /// let mut transactions: Vec<Transaction> = (1..=100)
+    ///     .map(|n| {
+    ///         Transaction::new(
+    ///             format!("t{}", n),
+    ///             TransactionType::Payment,
+    ///             TransactionStatus::Pending,
+    ///             PaymentMethod::PayPal,
+    ///             Money::new(n, Currency::EUR),
+    ///         )
+    ///     })
+    ///     .collect();
+    ///
+    /// let mut store = MemoryStore::new();
+    /// let report =
+    ///     process_transactions_parallel(&mut transactions, &mut store, &FeeTable::default()).unwrap();
+    ///
+    /// assert_eq!(report.results[41].id, "t42");
+    /// assert_eq!(report.summary.processed, 100);
+    /// ```
+    pub fn process_transactions_parallel(
+        transactions: &mut [Transaction],
+        store: &mut impl IdempotencyStore,
+        fees: &FeeTable,
+    ) -> Result<ProcessingReport, TransactionError> {
+        validate_transactions(transactions)?;
+
+        let fingerprints: Vec<String> = transactions.par_iter().map(fingerprint).collect();
+        let interrupted = |error| TransactionError::Interrupted {
+            completed: vec![],
+            error,
+        };
+        let mut submitted: HashMap<String, String> = HashMap::new();
+        let mut new_submissions: HashSet<String> = HashSet::new();
+        let mut first_step: HashMap<String, usize> = HashMap::new();
+        let mut plan: Vec<Step> = Vec::with_capacity(transactions.len());
+        for (index, transaction) in transactions.iter().enumerate() {
+            if !submitted.contains_key(&transaction.id) {
+                let first = match store
+                    .get(&submission_key(transaction))
+                    .map_err(interrupted)?
+                {
+                    Some(record) => record.fingerprint,
+                    None => {
+                        new_submissions.insert(transaction.id.clone());
+                        fingerprints[index].clone()
+                    }
+                };
+                submitted.insert(transaction.id.clone(), first);
+            }
+            if submitted[&transaction.id] != fingerprints[index] {
+                plan.push(Step::Conflict);
+                continue;
+            }
+
+            let key = idempotency_key(transaction);
+            let step = match first_step.get(&key) {
+                Some(&first) => Step::ReplayOf(first),
+                None => match store.get(&key).map_err(interrupted)? {
+                    Some(record) => Step::Replay(record),
+                    None => {
+                        first_step.insert(key, index);
+                        Step::Process
+                    }
+                },
+            };
+            plan.push(step);
+        }
+
+        let processed: Vec<Option<(Transaction, TransactionResult)>> = transactions
+            .par_iter()
+            .zip(plan.par_iter())
+            .map(|(transaction, step)| {
+                matches!(step, Step::Process).then(|| {
+                    let mut processed = transaction.clone();
+                    let result = TransactionResult::process_with_fees(&mut processed, fees);
+                    (processed, result)
+                })
+            })
+            .collect();
+
+        let mut results: Vec<TransactionResult> = Vec::with_capacity(transactions.len());
+        for (index, (step, processed)) in plan.into_iter().zip(processed).enumerate() {
+            let transaction = &transactions[index];
+            let result = match (step, processed) {
+                (_, Some((processed, result))) => {
+                    let record = IdempotencyRecord::new(fingerprints[index].clone(), &result);
+                    let first_submission = new_submissions.remove(&transaction.id);
+                    if let Err(error) = remember(store, transaction, record, first_submission) {
+                        return Err(TransactionError::Interrupted {
+                            completed: results,
+                            error,
+                        });
+                    }
+                    transactions[index] = processed;
+                    result
+                }
+                (Step::ReplayOf(first), None) => {
+                    let record =
+                        IdempotencyRecord::new(fingerprints[first].clone(), &results[first]);
+                    replay(transaction, &fingerprints[index], record)
+                }
+                (Step::Replay(record), None) => replay(transaction, &fingerprints[index], record),
+                _ => conflict(transaction),
+            };
+            results.push(result);
+        }
+        let summary = ProcessingSummary::from_results(&results)?;
+
+        Ok(ProcessingReport { results, summary })
+    }
+}
+
+#[cfg(feature = "parallel")]
+pub use parallel::process_transactions_parallel;
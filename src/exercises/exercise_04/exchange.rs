@@ -0,0 +1,154 @@
+use super::money::{parse_decimal, Currency, Money, MoneyError, RoundingMode};
+use std::collections::HashMap;
+
+/// ExchangeRate struct, how many units of `to` one unit of `from` buys
+///
+/// The rate is kept as a decimal, `mantissa * 10^-scale`, so it converts
+/// without floating point errors.
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// let rate = ExchangeRate::parse(Currency::USD, Currency::JPY, "151.25").unwrap();
+/// let dollars = Money::new(1050, Currency::USD);
+///
+/// assert_eq!(rate.convert(&dollars, RoundingMode::HalfEven), Ok(Money::new(1588, Currency::JPY)));
+/// assert_eq!(format!("{}", rate), "1 USD = 151.25 JPY");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExchangeRate {
+    pub from: Currency,
+    pub to: Currency,
+    pub mantissa: i64,
+    pub scale: u32,
+}
+
+impl ExchangeRate {
+    pub fn parse(from: Currency, to: Currency, rate: &str) -> Result<ExchangeRate, MoneyError> {
+        let (mantissa, scale) = parse_decimal(rate)?;
+        if mantissa <= 0 {
+            return Err(MoneyError::InvalidAmount(rate.to_string()));
+        }
+        let mantissa = i64::try_from(mantissa).map_err(|_| MoneyError::Overflow)?;
+        Ok(ExchangeRate {
+            from,
+            to,
+            mantissa,
+            scale,
+        })
+    }
+
+    pub fn identity(currency: Currency) -> ExchangeRate {
+        ExchangeRate {
+            from: currency,
+            to: currency,
+            mantissa: 1,
+            scale: 0,
+        }
+    }
+
+    pub fn convert(&self, amount: &Money, mode: RoundingMode) -> Result<Money, MoneyError> {
+        if amount.currency != self.from {
+            return Err(MoneyError::CurrencyMismatch(self.from, amount.currency));
+        }
+
+        let numerator = (amount.minor as i128)
+            .checked_mul(self.mantissa as i128)
+            .and_then(|n| n.checked_mul(10_i128.pow(self.to.minor_units())))
+            .ok_or(MoneyError::Overflow)?;
+        let denominator = 10_i128
+            .checked_pow(self.scale + self.from.minor_units())
+            .ok_or(MoneyError::Overflow)?;
+        let minor =
+            i64::try_from(mode.divide(numerator, denominator)).map_err(|_| MoneyError::Overflow)?;
+
+        Ok(Money::new(minor, self.to))
+    }
+}
+
+impl std::fmt::Display for ExchangeRate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let digits = format!(
+            "{:0>width$}",
+            self.mantissa,
+            width = self.scale as usize + 1
+        );
+        let (whole, fraction) = digits.split_at(digits.len() - self.scale as usize);
+        match fraction.is_empty() {
+            true => write!(f, "1 {} = {} {}", self.from, whole, self.to),
+            false => write!(f, "1 {} = {}.{} {}", self.from, whole, fraction, self.to),
+        }
+    }
+}
+
+/// Source of exchange rates, implement it to plug in any rate feed
+pub trait ExchangeRateProvider {
+    fn rate(&self, from: Currency, to: Currency) -> Option<ExchangeRate>;
+
+    /// Converts an amount, same currency amounts are returned as they are
+    fn convert(
+        &self,
+        amount: &Money,
+        to: Currency,
+        mode: RoundingMode,
+    ) -> Result<Money, MoneyError> {
+        if amount.currency == to {
+            return Ok(*amount);
+        }
+        let rate = self
+            .rate(amount.currency, to)
+            .ok_or(MoneyError::MissingRate(amount.currency, to))?;
+        rate.convert(amount, mode)
+    }
+}
+
+/// StaticRateTable struct, a fixed set of rates
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// let rates = StaticRateTable::new()
+///     .with_rate(Currency::EUR, Currency::USD, "1.10")
+///     .unwrap();
+/// let euros = Money::new(1000, Currency::EUR);
+///
+/// assert_eq!(rates.convert(&euros, Currency::USD, RoundingMode::HalfEven), Ok(Money::new(1100, Currency::USD)));
+/// assert_eq!(rates.convert(&euros, Currency::GBP, RoundingMode::HalfEven), Err(MoneyError::MissingRate(Currency::EUR, Currency::GBP)));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StaticRateTable {
+    rates: HashMap<(Currency, Currency), ExchangeRate>,
+}
+
+impl StaticRateTable {
+    pub fn new() -> Self {
+        StaticRateTable::default()
+    }
+
+    pub fn with_rate(
+        mut self,
+        from: Currency,
+        to: Currency,
+        rate: &str,
+    ) -> Result<Self, MoneyError> {
+        self.insert(ExchangeRate::parse(from, to, rate)?);
+        Ok(self)
+    }
+
+    pub fn insert(&mut self, rate: ExchangeRate) {
+        self.rates.insert((rate.from, rate.to), rate);
+    }
+}
+
+impl ExchangeRateProvider for StaticRateTable {
+    fn rate(&self, from: Currency, to: Currency) -> Option<ExchangeRate> {
+        if from == to {
+            return Some(ExchangeRate::identity(from));
+        }
+        self.rates.get(&(from, to)).copied()
+    }
+}
@@ -0,0 +1,214 @@
+use super::money::{Currency, Money, MoneyError, RoundingMode};
+use super::{PaymentMethod, Transaction};
+use std::collections::HashMap;
+
+/// Rounding used for percentage fees and taxes
+pub const FEE_ROUNDING: RoundingMode = RoundingMode::HalfUp;
+
+/// FeeSchedule struct, what a payment method costs in one currency
+///
+/// The percentage is in basis points, 290 is 2.9%. The fixed part and the
+/// percentage are added up and then kept between `min` and `max`.
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// let schedule = FeeSchedule::new(Money::new(30, Currency::USD), 290)
+///     .with_min(Money::new(50, Currency::USD))
+///     .with_max(Money::new(1000, Currency::USD));
+///
+/// assert_eq!(schedule.fee(&Money::new(10000, Currency::USD)), Ok(Money::new(320, Currency::USD)));
+/// assert_eq!(schedule.fee(&Money::new(500, Currency::USD)), Ok(Money::new(50, Currency::USD)));
+/// assert_eq!(schedule.fee(&Money::new(100000, Currency::USD)), Ok(Money::new(1000, Currency::USD)));
+/// assert_eq!(
+///     schedule.fee(&Money::new(100, Currency::EUR)),
+///     Err(MoneyError::CurrencyMismatch(Currency::USD, Currency::EUR))
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeSchedule {
+    pub fixed: Money,
+    pub basis_points: u32,
+    pub min: Option<Money>,
+    pub max: Option<Money>,
+}
+
+impl FeeSchedule {
+    pub fn new(fixed: Money, basis_points: u32) -> Self {
+        FeeSchedule {
+            fixed,
+            basis_points,
+            min: None,
+            max: None,
+        }
+    }
+
+    pub fn with_min(mut self, min: Money) -> Self {
+        self.min = Some(min);
+        self
+    }
+
+    pub fn with_max(mut self, max: Money) -> Self {
+        self.max = Some(max);
+        self
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.fixed.currency
+    }
+
+    pub fn fee(&self, amount: &Money) -> Result<Money, MoneyError> {
+        let percentage = amount.mul_ratio(self.basis_points as i64, 10_000, FEE_ROUNDING)?;
+        let mut fee = self.fixed.checked_add(&percentage)?;
+        if let Some(min) = &self.min {
+            if fee.checked_sub(min)?.is_negative() {
+                fee = *min;
+            }
+        }
+        if let Some(max) = &self.max {
+            if max.checked_sub(&fee)?.is_negative() {
+                fee = *max;
+            }
+        }
+        Ok(fee)
+    }
+}
+
+/// TaxBase enum, what a tax is charged on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaxBase {
+    /// The processing fee, like VAT on a processor's service
+    Fee,
+    /// The whole transaction amount
+    Amount,
+}
+
+/// TaxRule struct, a tax in basis points charged on fees or amounts
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaxRule {
+    pub name: String,
+    pub basis_points: u32,
+    pub base: TaxBase,
+    /// Only transactions in this currency pay the tax, all of them when `None`
+    pub currency: Option<Currency>,
+}
+
+impl TaxRule {
+    pub fn new(name: &str, basis_points: u32, base: TaxBase) -> Self {
+        TaxRule {
+            name: name.to_string(),
+            basis_points,
+            base,
+            currency: None,
+        }
+    }
+
+    pub fn only_in(mut self, currency: Currency) -> Self {
+        self.currency = Some(currency);
+        self
+    }
+
+    pub fn applies_to(&self, transaction: &Transaction) -> bool {
+        self.currency
+            .is_none_or(|currency| currency == transaction.currency())
+    }
+}
+
+/// Charges struct, how a transaction's amount splits into fee, tax and net
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Charges {
+    pub gross: Money,
+    pub fee: Money,
+    pub tax: Money,
+    /// Gross minus fee and tax
+    pub net: Money,
+}
+
+/// FeeTable struct, the fee schedules per payment method and currency and the tax rules
+///
+/// Methods without a schedule for the transaction's currency are free, taxes
+/// on the amount still apply to them.
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// let fees = FeeTable::new()
+///     .with_schedule(PaymentMethod::CreditCard, FeeSchedule::new(Money::new(30, Currency::USD), 290))
+///     .with_tax(TaxRule::new("VAT on fees", 2000, TaxBase::Fee));
+/// let payment = Transaction::new(
+///    String::from("t1"),
+///    TransactionType::Payment,
+///    TransactionStatus::Pending,
+///    PaymentMethod::CreditCard,
+///    Money::new(10000, Currency::USD),
+/// );
+///
+/// let charges = fees.charges(&payment).unwrap();
+///
+/// assert_eq!(charges.fee, Money::new(320, Currency::USD));
+/// assert_eq!(charges.tax, Money::new(64, Currency::USD));
+/// assert_eq!(charges.net, Money::new(9616, Currency::USD));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeeTable {
+    schedules: HashMap<(PaymentMethod, Currency), FeeSchedule>,
+    taxes: Vec<TaxRule>,
+}
+
+impl FeeTable {
+    pub fn new() -> Self {
+        FeeTable::default()
+    }
+
+    /// Sets the schedule of `method` for the currency of the schedule
+    pub fn with_schedule(mut self, method: PaymentMethod, schedule: FeeSchedule) -> Self {
+        self.schedules
+            .insert((method, schedule.currency()), schedule);
+        self
+    }
+
+    pub fn with_tax(mut self, rule: TaxRule) -> Self {
+        self.taxes.push(rule);
+        self
+    }
+
+    pub fn schedule(&self, method: &PaymentMethod, currency: Currency) -> Option<&FeeSchedule> {
+        self.schedules.get(&(method.clone(), currency))
+    }
+
+    pub fn charges(&self, transaction: &Transaction) -> Result<Charges, MoneyError> {
+        let gross = transaction.amount;
+        let fee = match self.schedule(&transaction.method, transaction.currency()) {
+            Some(schedule) => schedule.fee(&gross)?,
+            None => Money::zero(gross.currency),
+        };
+
+        let mut tax = Money::zero(gross.currency);
+        for rule in self
+            .taxes
+            .iter()
+            .filter(|rule| rule.applies_to(transaction))
+        {
+            let base = match rule.base {
+                TaxBase::Fee => fee,
+                TaxBase::Amount => gross,
+            };
+            tax = tax.checked_add(&base.mul_ratio(
+                rule.basis_points as i64,
+                10_000,
+                FEE_ROUNDING,
+            )?)?;
+        }
+
+        Ok(Charges {
+            gross,
+            fee,
+            tax,
+            net: gross.checked_sub(&fee)?.checked_sub(&tax)?,
+        })
+    }
+}
@@ -0,0 +1,545 @@
+use super::money::{Currency, Money, MoneyError};
+use super::{PaymentMethod, StatusChange, Transaction, TransactionStatus, TransactionType};
+use crate::error::ErrorCode;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+/// Namespace of the pain.001 version the export follows
+pub const PAIN_001_NAMESPACE: &str = "urn:iso:std:iso:20022:tech:xsd:pain.001.001.09";
+
+/// FormatError enum, parse errors keep the error of the parser behind them
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// assert_eq!(format!("{}", FormatError::NotAPayment(String::from("r1"))), "Transaction r1 is not a payment");
+/// assert_eq!(format!("{}", FormatError::MissingElement("EndToEndId")), "Missing XML element EndToEndId");
+/// ```
+#[derive(Debug, Clone)]
+pub enum FormatError {
+    Csv(Arc<csv::Error>),
+    Json(Arc<serde_json::Error>),
+    Xml(roxmltree::Error),
+    MissingElement(&'static str),
+    InvalidElement {
+        name: &'static str,
+        value: String,
+    },
+    NotAPayment(String),
+    NoPayments,
+    /// The status of an imported transaction isn't where its audit log ends
+    InconsistentStatus {
+        id: String,
+        status: TransactionStatus,
+        logged: TransactionStatus,
+    },
+}
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FormatError::Csv(reason) => write!(f, "Invalid CSV: {}", reason),
+            FormatError::Json(reason) => write!(f, "Invalid JSON: {}", reason),
+            FormatError::Xml(reason) => write!(f, "Invalid XML: {}", reason),
+            FormatError::MissingElement(name) => write!(f, "Missing XML element {}", name),
+            FormatError::InvalidElement { name, value } => {
+                write!(f, "Invalid XML element {}: {}", name, value)
+            }
+            FormatError::NotAPayment(id) => write!(f, "Transaction {} is not a payment", id),
+            FormatError::NoPayments => write!(f, "There are no payments to export"),
+            FormatError::InconsistentStatus { id, status, logged } => write!(
+                f,
+                "Transaction {} is {} but its audit log ends at {}",
+                id, status, logged
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FormatError::Csv(error) => Some(error.as_ref()),
+            FormatError::Json(error) => Some(error.as_ref()),
+            FormatError::Xml(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Parser errors aren't comparable, they are equal when their messages are
+impl PartialEq for FormatError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (FormatError::Csv(a), FormatError::Csv(b)) => a.to_string() == b.to_string(),
+            (FormatError::Json(a), FormatError::Json(b)) => a.to_string() == b.to_string(),
+            (FormatError::Xml(a), FormatError::Xml(b)) => a == b,
+            (FormatError::MissingElement(a), FormatError::MissingElement(b)) => a == b,
+            (
+                FormatError::InvalidElement { name, value },
+                FormatError::InvalidElement {
+                    name: other_name,
+                    value: other_value,
+                },
+            ) => name == other_name && value == other_value,
+            (FormatError::NotAPayment(a), FormatError::NotAPayment(b)) => a == b,
+            (FormatError::NoPayments, FormatError::NoPayments) => true,
+            (
+                FormatError::InconsistentStatus { id, status, logged },
+                FormatError::InconsistentStatus {
+                    id: other_id,
+                    status: other_status,
+                    logged: other_logged,
+                },
+            ) => id == other_id && status == other_status && logged == other_logged,
+            _ => false,
+        }
+    }
+}
+
+impl ErrorCode for FormatError {
+    fn code(&self) -> &'static str {
+        match self {
+            FormatError::Csv(_) => "format.csv",
+            FormatError::Json(_) => "format.json",
+            FormatError::Xml(_) => "format.xml",
+            FormatError::MissingElement(_) => "format.missing_element",
+            FormatError::InvalidElement { .. } => "format.invalid_element",
+            FormatError::NotAPayment(_) => "format.not_a_payment",
+            FormatError::NoPayments => "format.no_payments",
+            FormatError::InconsistentStatus { .. } => "format.inconsistent_status",
+        }
+    }
+}
+
+impl From<csv::Error> for FormatError {
+    fn from(error: csv::Error) -> Self {
+        FormatError::Csv(Arc::new(error))
+    }
+}
+
+impl From<serde_json::Error> for FormatError {
+    fn from(error: serde_json::Error) -> Self {
+        FormatError::Json(Arc::new(error))
+    }
+}
+
+impl From<roxmltree::Error> for FormatError {
+    fn from(error: roxmltree::Error) -> Self {
+        FormatError::Xml(error)
+    }
+}
+
+/// Serializes transactions as a JSON array
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// use chrono::{TimeZone, Utc};
+///
+/// let transactions = vec![Transaction::new(
+///    String::from("t1"),
+///    TransactionType::Payment,
+///    TransactionStatus::Pending,
+///    PaymentMethod::PayPal,
+///    Money::new(2399, Currency::USD),
+/// )
+/// .with_created_at(Utc.with_ymd_and_hms(2024, 3, 1, 9, 30, 0).unwrap())
+/// .with_customer("cust42")];
+///
+/// let json = transactions_to_json(&transactions).unwrap();
+///
+/// assert_eq!(
+///     json,
+///     r#"[{"id":"t1","transaction_type":"payment","status":"pending","method":"paypal","amount":"23.99 USD","refund_of":null,"refunded":"0.00 USD","created_at":"2024-03-01T09:30:00Z","updated_at":"2024-03-01T09:30:00Z","customer":"cust42","description":null,"metadata":{},"audit_log":[]}]"#
+/// );
+/// assert_eq!(transactions_from_json(&json), Ok(transactions));
+/// ```
+pub fn transactions_to_json(transactions: &[Transaction]) -> Result<String, FormatError> {
+    Ok(serde_json::to_string(transactions)?)
+}
+
+pub fn transactions_from_json(json: &str) -> Result<Vec<Transaction>, FormatError> {
+    Ok(serde_json::from_str(json)?)
+}
+
+/// A transaction as a flat CSV record, metadata and audit log are JSON
+#[derive(Debug, Serialize, Deserialize)]
+struct TransactionRow {
+    id: String,
+    transaction_type: TransactionType,
+    status: TransactionStatus,
+    method: PaymentMethod,
+    amount: Money,
+    refund_of: Option<String>,
+    refunded: Money,
+    #[serde(default)]
+    created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    updated_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    customer: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    metadata: String,
+    /// Required, an empty cell is a transaction created in its current status
+    audit_log: String,
+}
+
+impl TransactionRow {
+    fn new(transaction: &Transaction) -> Result<Self, FormatError> {
+        let metadata = match transaction.metadata.is_empty() {
+            true => String::new(),
+            false => serde_json::to_string(&transaction.metadata)?,
+        };
+        let audit_log = match transaction.audit_log.is_empty() {
+            true => String::new(),
+            false => serde_json::to_string(&transaction.audit_log)?,
+        };
+
+        Ok(TransactionRow {
+            id: transaction.id.clone(),
+            transaction_type: transaction.transaction_type.clone(),
+            status: transaction.status.clone(),
+            method: transaction.method.clone(),
+            amount: transaction.amount,
+            refund_of: transaction.refund_of().map(String::from),
+            refunded: transaction.refunded(),
+            created_at: Some(transaction.created_at),
+            updated_at: Some(transaction.updated_at),
+            customer: transaction.customer.clone(),
+            description: transaction.description.clone(),
+            metadata,
+            audit_log,
+        })
+    }
+
+    /// Rows without timestamps were created now and never changed since, rows
+    /// with a history must be in the status their last change left them in
+    fn into_transaction(self) -> Result<Transaction, FormatError> {
+        let metadata: BTreeMap<String, String> = match self.metadata.is_empty() {
+            true => BTreeMap::new(),
+            false => serde_json::from_str(&self.metadata)?,
+        };
+        let audit_log: Vec<StatusChange> = match self.audit_log.is_empty() {
+            true => vec![],
+            false => serde_json::from_str(&self.audit_log)?,
+        };
+        if let Some(last) = audit_log.last() {
+            if last.to != self.status {
+                return Err(FormatError::InconsistentStatus {
+                    id: self.id,
+                    status: self.status,
+                    logged: last.to.clone(),
+                });
+            }
+        }
+        let created_at = self.created_at.unwrap_or_else(Utc::now);
+
+        Ok(Transaction {
+            id: self.id,
+            transaction_type: self.transaction_type,
+            status: self.status,
+            method: self.method,
+            amount: self.amount,
+            refund_of: self.refund_of,
+            refunded: self.refunded,
+            created_at,
+            updated_at: self.updated_at.unwrap_or(created_at),
+            customer: self.customer,
+            description: self.description,
+            metadata,
+            audit_log,
+        })
+    }
+}
+
+/// Writes transactions as CSV with a header row
+pub fn export_transactions_csv<W: Write>(
+    transactions: &[Transaction],
+    writer: W,
+) -> Result<(), FormatError> {
+    let mut writer = csv::Writer::from_writer(writer);
+    for transaction in transactions {
+        writer.serialize(TransactionRow::new(transaction)?)?;
+    }
+    writer.flush().map_err(csv::Error::from)?;
+    Ok(())
+}
+
+/// Reads transactions written by `export_transactions_csv`
+///
+/// The columns up to `refunded` and the `audit_log` are required, a
+/// transaction with a history must be in the status its last change reached.
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// let csv = "\
+/// id,transaction_type,status,method,amount,refund_of,refunded,customer,metadata,audit_log
+/// t1,payment,authorized,credit card,23.99 USD,,0.00 USD,cust42,\"{\"\"order\"\":\"\"1001\"\"}\",\"[{\"\"from\"\":\"\"pending\"\",\"\"to\"\":\"\"authorized\"\",\"\"actor\"\":\"\"system\"\",\"\"reason\"\":\"\"Processed\"\",\"\"at\"\":\"\"2024-03-01T09:30:00Z\"\"}]\"
+/// r1,refund,pending,credit card,5.00 USD,t1,0.00 USD,,,
+/// ";
+///
+/// let transactions = import_transactions_csv(csv.as_bytes()).unwrap();
+///
+/// assert_eq!(transactions[0].status(), &TransactionStatus::Authorized);
+/// assert_eq!(transactions[0].audit_log().len(), 1);
+/// assert_eq!(transactions[0].customer, Some(String::from("cust42")));
+/// assert_eq!(transactions[0].metadata["order"], "1001");
+/// assert_eq!(transactions[1].refund_of(), Some("t1"));
+///
+/// assert_eq!(
+///     import_transactions_csv(csv.replace(",authorized,", ",settled,").as_bytes()),
+///     Err(FormatError::InconsistentStatus {
+///         id: String::from("t1"),
+///         status: TransactionStatus::Settled,
+///         logged: TransactionStatus::Authorized,
+///     })
+/// );
+/// ```
+pub fn import_transactions_csv<R: Read>(reader: R) -> Result<Vec<Transaction>, FormatError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    reader
+        .deserialize()
+        .map(|row: Result<TransactionRow, csv::Error>| row?.into_transaction())
+        .collect()
+}
+
+/// Pain001Header struct, the group header and debtor of a pain.001 message
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pain001Header {
+    pub message_id: String,
+    pub created_at: DateTime<Utc>,
+    pub initiating_party: String,
+    pub debtor: String,
+    pub debtor_iban: String,
+}
+
+/// Exports payments as an ISO 20022 pain.001 customer credit transfer initiation
+///
+/// The payment method goes in each transfer's local instrument and the
+/// description in its remittance information, so `import_pain001` can read
+/// the transactions back as pending payments.
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+/// use chrono::{TimeZone, Utc};
+///
+/// let header = Pain001Header {
+///     message_id: String::from("MSG-1"),
+///     created_at: Utc.with_ymd_and_hms(2024, 3, 1, 9, 30, 0).unwrap(),
+///     initiating_party: String::from("Clean Code Shop"),
+///     debtor: String::from("Clean Code Shop"),
+///     debtor_iban: String::from("ES9121000418450200051332"),
+/// };
+/// let payments = vec![Transaction::new(
+///    String::from("t1"),
+///    TransactionType::Payment,
+///    TransactionStatus::Pending,
+///    PaymentMethod::CreditCard,
+///    Money::new(2399, Currency::EUR),
+/// )
+/// .with_created_at(header.created_at)
+/// .with_description("Order 1001")];
+///
+/// let xml = export_pain001(&header, &payments).unwrap();
+///
+/// assert_eq!(xml.contains(r#"<InstdAmt Ccy="EUR">23.99</InstdAmt>"#), true);
+/// assert_eq!(import_pain001(&xml), Ok(payments));
+/// ```
+pub fn export_pain001(
+    header: &Pain001Header,
+    payments: &[Transaction],
+) -> Result<String, FormatError> {
+    if payments.is_empty() {
+        return Err(FormatError::NoPayments);
+    }
+    if let Some(refund) = payments
+        .iter()
+        .find(|payment| payment.transaction_type != TransactionType::Payment)
+    {
+        return Err(FormatError::NotAPayment(refund.id.clone()));
+    }
+
+    let count = payments.len();
+    let control_sum = control_sum(payments);
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!("<Document xmlns=\"{}\">\n", PAIN_001_NAMESPACE));
+    xml.push_str("  <CstmrCdtTrfInitn>\n");
+    xml.push_str("    <GrpHdr>\n");
+    xml.push_str(&format!(
+        "      <MsgId>{}</MsgId>\n",
+        escape(&header.message_id)
+    ));
+    xml.push_str(&format!(
+        "      <CreDtTm>{}</CreDtTm>\n",
+        header.created_at.format("%Y-%m-%dT%H:%M:%SZ")
+    ));
+    xml.push_str(&format!("      <NbOfTxs>{}</NbOfTxs>\n", count));
+    xml.push_str(&format!("      <CtrlSum>{}</CtrlSum>\n", control_sum));
+    xml.push_str(&format!(
+        "      <InitgPty><Nm>{}</Nm></InitgPty>\n",
+        escape(&header.initiating_party)
+    ));
+    xml.push_str("    </GrpHdr>\n");
+    xml.push_str("    <PmtInf>\n");
+    xml.push_str(&format!(
+        "      <PmtInfId>{}-1</PmtInfId>\n",
+        escape(&header.message_id)
+    ));
+    xml.push_str("      <PmtMtd>TRF</PmtMtd>\n");
+    xml.push_str(&format!("      <NbOfTxs>{}</NbOfTxs>\n", count));
+    xml.push_str(&format!("      <CtrlSum>{}</CtrlSum>\n", control_sum));
+    xml.push_str(&format!(
+        "      <ReqdExctnDt><Dt>{}</Dt></ReqdExctnDt>\n",
+        header.created_at.format("%Y-%m-%d")
+    ));
+    xml.push_str(&format!(
+        "      <Dbtr><Nm>{}</Nm></Dbtr>\n",
+        escape(&header.debtor)
+    ));
+    xml.push_str(&format!(
+        "      <DbtrAcct><Id><IBAN>{}</IBAN></Id></DbtrAcct>\n",
+        escape(&header.debtor_iban)
+    ));
+    xml.push_str(
+        "      <DbtrAgt><FinInstnId><Othr><Id>NOTPROVIDED</Id></Othr></FinInstnId></DbtrAgt>\n",
+    );
+    for payment in payments {
+        xml.push_str("      <CdtTrfTxInf>\n");
+        xml.push_str(&format!(
+            "        <PmtId><EndToEndId>{}</EndToEndId></PmtId>\n",
+            escape(&payment.id)
+        ));
+        xml.push_str(&format!(
+            "        <PmtTpInf><LclInstrm><Prtry>{}</Prtry></LclInstrm></PmtTpInf>\n",
+            escape(&payment.method.to_string())
+        ));
+        xml.push_str(&format!(
+            "        <Amt><InstdAmt Ccy=\"{}\">{}</InstdAmt></Amt>\n",
+            payment.currency(),
+            payment.amount.to_decimal()
+        ));
+        if let Some(description) = &payment.description {
+            xml.push_str(&format!(
+                "        <RmtInf><Ustrd>{}</Ustrd></RmtInf>\n",
+                escape(description)
+            ));
+        }
+        xml.push_str("      </CdtTrfTxInf>\n");
+    }
+    xml.push_str("    </PmtInf>\n");
+    xml.push_str("  </CstmrCdtTrfInitn>\n");
+    xml.push_str("</Document>\n");
+
+    Ok(xml)
+}
+
+/// Reads the transfers of a pain.001 message back as pending payments
+/// created when the message was
+pub fn import_pain001(xml: &str) -> Result<Vec<Transaction>, FormatError> {
+    let document = roxmltree::Document::parse(xml)?;
+    let root = document.root_element();
+    let created_at = child_text(root, "CreDtTm")?;
+    let created_at: DateTime<Utc> =
+        created_at
+            .parse()
+            .map_err(|_| FormatError::InvalidElement {
+                name: "CreDtTm",
+                value: created_at,
+            })?;
+
+    document
+        .descendants()
+        .filter(|node| node.has_tag_name("CdtTrfTxInf"))
+        .map(|transfer| {
+            let id = child_text(transfer, "EndToEndId")?;
+            let method: PaymentMethod = child_text(transfer, "Prtry")?
+                .parse()
+                .unwrap_or_else(|never| match never {});
+            let amount = transfer
+                .descendants()
+                .find(|node| node.has_tag_name("InstdAmt"))
+                .ok_or(FormatError::MissingElement("InstdAmt"))?;
+            let currency = amount
+                .attribute("Ccy")
+                .ok_or(FormatError::MissingElement("Ccy"))?;
+            let currency: Currency =
+                currency
+                    .parse()
+                    .map_err(|_: MoneyError| FormatError::InvalidElement {
+                        name: "Ccy",
+                        value: currency.to_string(),
+                    })?;
+            let text = amount.text().unwrap_or("");
+            let amount = Money::parse(text, currency).map_err(|_| FormatError::InvalidElement {
+                name: "InstdAmt",
+                value: text.to_string(),
+            })?;
+
+            let mut payment = Transaction::new(
+                id,
+                TransactionType::Payment,
+                TransactionStatus::Pending,
+                method,
+                amount,
+            )
+            .with_created_at(created_at);
+            payment.description = child_text(transfer, "Ustrd").ok();
+            Ok(payment)
+        })
+        .collect()
+}
+
+fn child_text(node: roxmltree::Node, name: &'static str) -> Result<String, FormatError> {
+    node.descendants()
+        .find(|child| child.has_tag_name(name))
+        .and_then(|child| child.text())
+        .map(|text| text.trim().to_string())
+        .ok_or(FormatError::MissingElement(name))
+}
+
+/// Sum of the amounts as plain decimals, whatever their currency, as pain.001 asks
+fn control_sum(payments: &[Transaction]) -> String {
+    const SCALE: u32 = 3;
+    let total: i128 = payments
+        .iter()
+        .map(|payment| {
+            payment.amount.minor as i128 * 10_i128.pow(SCALE - payment.currency().minor_units())
+        })
+        .sum();
+    // Totals between -1 and 0 have no sign in their integer part
+    let sign = if total < 0 { "-" } else { "" };
+    let total = total.unsigned_abs();
+    let unit = 10_u128.pow(SCALE);
+    let fraction = format!("{:03}", total % unit);
+    let fraction = fraction.trim_end_matches('0');
+    match fraction.is_empty() {
+        true => format!("{}{}", sign, total / unit),
+        false => format!("{}{}.{}", sign, total / unit, fraction),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
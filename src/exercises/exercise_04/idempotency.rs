@@ -0,0 +1,343 @@
+use super::fees::FeeTable;
+use super::report::{TransactionOutcome, TransactionResult};
+use super::{Transaction, TransactionError};
+use crate::error::ErrorCode;
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+
+/// StoreError enum
+#[derive(Debug, Clone)]
+pub enum StoreError {
+    Io(Arc<std::io::Error>),
+    Corrupt { line: usize },
+}
+
+impl std::fmt::Display for StoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            StoreError::Io(reason) => write!(f, "Idempotency store I/O error: {}", reason),
+            StoreError::Corrupt { line } => {
+                write!(f, "Idempotency store is corrupt at line {}", line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StoreError::Io(error) => Some(error.as_ref()),
+            StoreError::Corrupt { .. } => None,
+        }
+    }
+}
+
+/// I/O errors aren't comparable, they are equal when their kinds and messages are
+impl PartialEq for StoreError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (StoreError::Io(a), StoreError::Io(b)) => {
+                a.kind() == b.kind() && a.to_string() == b.to_string()
+            }
+            (StoreError::Corrupt { line }, StoreError::Corrupt { line: other_line }) => {
+                line == other_line
+            }
+            _ => false,
+        }
+    }
+}
+
+impl ErrorCode for StoreError {
+    fn code(&self) -> &'static str {
+        match self {
+            StoreError::Io(_) => "store.io",
+            StoreError::Corrupt { .. } => "store.corrupt",
+        }
+    }
+}
+
+impl From<std::io::Error> for StoreError {
+    fn from(error: std::io::Error) -> Self {
+        StoreError::Io(Arc::new(error))
+    }
+}
+
+/// IdempotencyRecord struct, what the first submission of a transaction step returned
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdempotencyRecord {
+    /// The submitted transaction contents, to tell replays from conflicts
+    pub fingerprint: String,
+    pub processed: bool,
+    pub message: String,
+}
+
+impl IdempotencyRecord {
+    pub fn new(fingerprint: String, result: &TransactionResult) -> Self {
+        IdempotencyRecord {
+            fingerprint,
+            processed: result.is_processed(),
+            message: result.to_string(),
+        }
+    }
+}
+
+/// Transaction contents as submitted, what must not change between steps
+pub fn fingerprint(transaction: &Transaction) -> String {
+    format!(
+        "{}|{}|{}|{}",
+        transaction.transaction_type,
+        transaction.method,
+        transaction.amount,
+        transaction.refund_of.as_deref().unwrap_or("")
+    )
+}
+
+/// Key of the record of one processing step, the id and the status it starts from
+///
+/// A payment resubmitted for its next step, like `Authorized` to `Captured`,
+/// gets a record of its own instead of replaying the first step.
+pub fn idempotency_key(transaction: &Transaction) -> String {
+    format!("{}@{}", transaction.id, transaction.status)
+}
+
+/// Key of the record of the first submission of a transaction, its id alone
+///
+/// Its fingerprint is checked at every step, so a transaction can't come
+/// back at a later step with another amount or method.
+pub fn submission_key(transaction: &Transaction) -> String {
+    transaction.id.clone()
+}
+
+/// Remembers the result of every transaction step, implement it to plug in any storage
+pub trait IdempotencyStore {
+    fn get(&self, key: &str) -> Result<Option<IdempotencyRecord>, StoreError>;
+    fn put(&mut self, key: &str, record: IdempotencyRecord) -> Result<(), StoreError>;
+}
+
+/// MemoryStore struct, records that live as long as the store
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    records: HashMap<String, IdempotencyRecord>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+impl IdempotencyStore for MemoryStore {
+    fn get(&self, key: &str) -> Result<Option<IdempotencyRecord>, StoreError> {
+        Ok(self.records.get(key).cloned())
+    }
+
+    fn put(&mut self, key: &str, record: IdempotencyRecord) -> Result<(), StoreError> {
+        self.records.insert(key.to_string(), record);
+        Ok(())
+    }
+}
+
+/// FileStore struct, records appended to a file, one per line
+///
+/// Records are loaded when the store opens so they survive restarts.
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// let path = std::env::temp_dir().join("file_store_doctest.log");
+/// let _ = std::fs::remove_file(&path);
+///
+/// let mut store = FileStore::open(&path).unwrap();
+/// let record = IdempotencyRecord {
+///     fingerprint: String::from("payment|credit card|23.99 USD|"),
+///     processed: true,
+///     message: String::from("Processing payment"),
+/// };
+/// store.put("t1@pending", record.clone()).unwrap();
+///
+/// let reopened = FileStore::open(&path).unwrap();
+/// assert_eq!(reopened.get("t1@pending"), Ok(Some(record)));
+/// ```
+#[derive(Debug)]
+pub struct FileStore {
+    path: std::path::PathBuf,
+    records: HashMap<String, IdempotencyRecord>,
+}
+
+impl FileStore {
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, StoreError> {
+        let path = path.as_ref().to_path_buf();
+        let mut records = HashMap::new();
+
+        if path.exists() {
+            let file = std::fs::File::open(&path)?;
+            for (index, line) in std::io::BufReader::new(file).lines().enumerate() {
+                let line = line?;
+                if line.is_empty() {
+                    continue;
+                }
+                let (key, record) =
+                    decode_line(&line).ok_or(StoreError::Corrupt { line: index + 1 })?;
+                records.insert(key, record);
+            }
+        }
+
+        Ok(FileStore { path, records })
+    }
+}
+
+impl IdempotencyStore for FileStore {
+    fn get(&self, key: &str) -> Result<Option<IdempotencyRecord>, StoreError> {
+        Ok(self.records.get(key).cloned())
+    }
+
+    fn put(&mut self, key: &str, record: IdempotencyRecord) -> Result<(), StoreError> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", encode_line(key, &record))?;
+        self.records.insert(key.to_string(), record);
+        Ok(())
+    }
+}
+
+fn encode_line(key: &str, record: &IdempotencyRecord) -> String {
+    [
+        escape(key),
+        escape(&record.fingerprint),
+        String::from(if record.processed {
+            "processed"
+        } else {
+            "rejected"
+        }),
+        escape(&record.message),
+    ]
+    .join("\t")
+}
+
+fn decode_line(line: &str) -> Option<(String, IdempotencyRecord)> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    let [key, fingerprint, processed, message] = fields[..] else {
+        return None;
+    };
+    let processed = match processed {
+        "processed" => true,
+        "rejected" => false,
+        _ => return None,
+    };
+    Some((
+        unescape(key)?,
+        IdempotencyRecord {
+            fingerprint: unescape(fingerprint)?,
+            processed,
+            message: unescape(message)?,
+        },
+    ))
+}
+
+fn escape(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(field: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next()? {
+            '\\' => unescaped.push('\\'),
+            't' => unescaped.push('\t'),
+            'n' => unescaped.push('\n'),
+            _ => return None,
+        }
+    }
+    Some(unescaped)
+}
+
+/// Processes each step of a transaction once
+///
+/// A replay of a step with the same contents gets the original result back without
+/// processing. Any step submitted with other contents than the first submission
+/// of the transaction is rejected as a conflict. The transaction only moves
+/// once its step is recorded, it's left untouched when the store fails.
+/// `advance` moves it a step, see `TransactionResult::process_through`.
+pub(crate) fn process_once(
+    transaction: &mut Transaction,
+    store: &mut impl IdempotencyStore,
+    fees: &FeeTable,
+    advance: impl FnOnce(&mut Transaction) -> Result<String, TransactionError>,
+) -> Result<TransactionResult, StoreError> {
+    let fingerprint = fingerprint(transaction);
+    let submitted = store.get(&submission_key(transaction))?;
+    if submitted
+        .as_ref()
+        .is_some_and(|first| first.fingerprint != fingerprint)
+    {
+        return Ok(conflict(transaction));
+    }
+
+    let key = idempotency_key(transaction);
+    if let Some(record) = store.get(&key)? {
+        return Ok(replay(transaction, &fingerprint, record));
+    }
+
+    let mut processed = transaction.clone();
+    let result = TransactionResult::process_through(&mut processed, fees, advance);
+    let record = IdempotencyRecord::new(fingerprint, &result);
+    remember(store, transaction, record, submitted.is_none())?;
+    *transaction = processed;
+    Ok(result)
+}
+
+/// Stores the record of a processed step, and of the submission when it's the first
+pub(crate) fn remember(
+    store: &mut impl IdempotencyStore,
+    transaction: &Transaction,
+    record: IdempotencyRecord,
+    first_submission: bool,
+) -> Result<(), StoreError> {
+    if first_submission {
+        store.put(&submission_key(transaction), record.clone())?;
+    }
+    store.put(&idempotency_key(transaction), record)
+}
+
+/// The result of a transaction submitted with other contents than before
+pub(crate) fn conflict(transaction: &Transaction) -> TransactionResult {
+    TransactionResult::new(
+        transaction,
+        TransactionOutcome::Rejected(TransactionError::IdempotencyConflict(
+            transaction.id.clone(),
+        )),
+    )
+}
+
+/// The result of a transaction step that was already processed
+pub(crate) fn replay(
+    transaction: &Transaction,
+    fingerprint: &str,
+    record: IdempotencyRecord,
+) -> TransactionResult {
+    match record.fingerprint == fingerprint {
+        true => TransactionResult::new(transaction, TransactionOutcome::Replayed(record)),
+        false => conflict(transaction),
+    }
+}
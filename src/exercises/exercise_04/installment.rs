@@ -0,0 +1,375 @@
+use super::money::{Money, MoneyError, RoundingMode};
+use super::{PaymentMethod, Transaction};
+use crate::error::ErrorCode;
+use chrono::{DateTime, Duration, Months, Utc};
+
+/// Frequency enum, how often an installment falls due
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Frequency {
+    Weekly,
+    Biweekly,
+    Monthly,
+}
+
+impl Frequency {
+    /// The date `periods` periods after `start`
+    pub fn advance(&self, start: DateTime<Utc>, periods: u32) -> Option<DateTime<Utc>> {
+        match self {
+            Frequency::Weekly => start.checked_add_signed(Duration::weeks(periods as i64)),
+            Frequency::Biweekly => start.checked_add_signed(Duration::weeks(2 * periods as i64)),
+            Frequency::Monthly => start.checked_add_months(Months::new(periods)),
+        }
+    }
+}
+
+/// Remainder enum, which installment takes the cents an even split leaves over
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Remainder {
+    First,
+    Last,
+}
+
+/// InstallmentStatus enum
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InstallmentStatus {
+    Scheduled,
+    Paid,
+    Overdue,
+    Defaulted,
+}
+
+impl std::fmt::Display for InstallmentStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            InstallmentStatus::Scheduled => write!(f, "scheduled"),
+            InstallmentStatus::Paid => write!(f, "paid"),
+            InstallmentStatus::Overdue => write!(f, "overdue"),
+            InstallmentStatus::Defaulted => write!(f, "defaulted"),
+        }
+    }
+}
+
+/// InstallmentError enum
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// assert_eq!(format!("{}", InstallmentError::NoInstallments), "A plan needs at least one installment");
+/// assert_eq!(format!("{}", InstallmentError::AlreadyPaid(2)), "Installment 2 is already paid");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstallmentError {
+    NotAPlan(PaymentMethod),
+    NoInstallments,
+    Amount(MoneyError),
+    DateOutOfRange,
+    UnknownInstallment(u32),
+    AlreadyPaid(u32),
+    Defaulted(u32),
+    WrongAmount {
+        number: u32,
+        expected: Money,
+        paid: Money,
+    },
+}
+
+impl From<MoneyError> for InstallmentError {
+    fn from(error: MoneyError) -> Self {
+        InstallmentError::Amount(error)
+    }
+}
+
+impl std::fmt::Display for InstallmentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            InstallmentError::NotAPlan(method) => {
+                write!(
+                    f,
+                    "Only plan payments can be paid in installments, not {}",
+                    method
+                )
+            }
+            InstallmentError::NoInstallments => {
+                write!(f, "A plan needs at least one installment")
+            }
+            InstallmentError::Amount(error) => write!(f, "Invalid plan amount: {}", error),
+            InstallmentError::DateOutOfRange => write!(f, "Due date is out of range"),
+            InstallmentError::UnknownInstallment(number) => {
+                write!(f, "There is no installment {}", number)
+            }
+            InstallmentError::AlreadyPaid(number) => {
+                write!(f, "Installment {} is already paid", number)
+            }
+            InstallmentError::Defaulted(number) => {
+                write!(f, "Installment {} has defaulted", number)
+            }
+            InstallmentError::WrongAmount {
+                number,
+                expected,
+                paid,
+            } => write!(
+                f,
+                "Installment {} is {}, {} was paid",
+                number, expected, paid
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InstallmentError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            InstallmentError::Amount(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl ErrorCode for InstallmentError {
+    fn code(&self) -> &'static str {
+        match self {
+            InstallmentError::NotAPlan(_) => "installment.not_a_plan",
+            InstallmentError::NoInstallments => "installment.no_installments",
+            InstallmentError::Amount(_) => "installment.amount",
+            InstallmentError::DateOutOfRange => "installment.date_out_of_range",
+            InstallmentError::UnknownInstallment(_) => "installment.unknown_installment",
+            InstallmentError::AlreadyPaid(_) => "installment.already_paid",
+            InstallmentError::Defaulted(_) => "installment.defaulted",
+            InstallmentError::WrongAmount { .. } => "installment.wrong_amount",
+        }
+    }
+}
+
+/// PlanTerms struct
+///
+/// Interest is a flat rate on the principal in basis points, 250 is 2.5%.
+/// An installment is overdue once `grace_period` has passed after its due
+/// date and defaults once it has been overdue for `default_after`.
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+/// use chrono::Duration;
+///
+/// let terms = PlanTerms::new(3, Frequency::Monthly);
+///
+/// assert_eq!(terms.interest_basis_points, 0);
+/// assert_eq!(terms.remainder, Remainder::First);
+/// assert_eq!(terms.default_after, Duration::days(30));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanTerms {
+    pub installments: u32,
+    pub frequency: Frequency,
+    pub interest_basis_points: u32,
+    pub fee: Option<Money>,
+    pub remainder: Remainder,
+    pub grace_period: Duration,
+    pub default_after: Duration,
+}
+
+impl PlanTerms {
+    pub fn new(installments: u32, frequency: Frequency) -> Self {
+        PlanTerms {
+            installments,
+            frequency,
+            interest_basis_points: 0,
+            fee: None,
+            remainder: Remainder::First,
+            grace_period: Duration::days(0),
+            default_after: Duration::days(30),
+        }
+    }
+}
+
+/// Installment struct
+#[derive(Debug, Clone, PartialEq)]
+pub struct Installment {
+    pub number: u32,
+    pub due: DateTime<Utc>,
+    pub amount: Money,
+    pub status: InstallmentStatus,
+    pub paid_at: Option<DateTime<Utc>>,
+}
+
+/// InstallmentPlan struct, the schedule a plan payment is paid back on
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+/// use chrono::{Duration, TimeZone, Utc};
+///
+/// let payment = Transaction::new(
+///    String::from("t1"),
+///    TransactionType::Payment,
+///    TransactionStatus::Captured,
+///    PaymentMethod::Plan,
+///    Money::new(10000, Currency::USD),
+/// );
+/// let start = Utc.with_ymd_and_hms(2024, 1, 31, 0, 0, 0).unwrap();
+///
+/// let mut plan = InstallmentPlan::new(&payment, &PlanTerms::new(3, Frequency::Monthly), start).unwrap();
+///
+/// let amounts: Vec<Money> = plan.installments.iter().map(|i| i.amount).collect();
+/// assert_eq!(amounts, vec![Money::new(3334, Currency::USD), Money::new(3333, Currency::USD), Money::new(3333, Currency::USD)]);
+/// assert_eq!(plan.installments[0].due, Utc.with_ymd_and_hms(2024, 2, 29, 0, 0, 0).unwrap());
+///
+/// plan.pay(1, Money::new(3334, Currency::USD), start + Duration::days(20)).unwrap();
+/// assert_eq!(plan.remaining(), Ok(Money::new(6666, Currency::USD)));
+///
+/// plan.refresh(start + Duration::days(65));
+/// assert_eq!(plan.installments[1].status, InstallmentStatus::Overdue);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstallmentPlan {
+    pub transaction_id: String,
+    pub principal: Money,
+    pub total: Money,
+    pub default_after: Duration,
+    pub grace_period: Duration,
+    pub installments: Vec<Installment>,
+}
+
+impl InstallmentPlan {
+    pub fn new(
+        transaction: &Transaction,
+        terms: &PlanTerms,
+        start: DateTime<Utc>,
+    ) -> Result<InstallmentPlan, InstallmentError> {
+        if transaction.method != PaymentMethod::Plan {
+            return Err(InstallmentError::NotAPlan(transaction.method.clone()));
+        }
+        if terms.installments == 0 {
+            return Err(InstallmentError::NoInstallments);
+        }
+
+        let principal = transaction.amount;
+        let interest = principal.mul_ratio(
+            terms.interest_basis_points as i64,
+            10_000,
+            RoundingMode::HalfEven,
+        )?;
+        let mut total = principal.checked_add(&interest)?;
+        if let Some(fee) = terms.fee {
+            total = total.checked_add(&fee)?;
+        }
+
+        let count = terms.installments as i64;
+        let share = total.minor / count;
+        let leftover = total.minor % count;
+        let remainder_number = match terms.remainder {
+            Remainder::First => 1,
+            Remainder::Last => terms.installments,
+        };
+
+        let installments = (1..=terms.installments)
+            .map(|number| {
+                let minor = match number == remainder_number {
+                    true => share + leftover,
+                    false => share,
+                };
+                Ok(Installment {
+                    number,
+                    due: terms
+                        .frequency
+                        .advance(start, number)
+                        .ok_or(InstallmentError::DateOutOfRange)?,
+                    amount: Money::new(minor, total.currency),
+                    status: InstallmentStatus::Scheduled,
+                    paid_at: None,
+                })
+            })
+            .collect::<Result<Vec<Installment>, InstallmentError>>()?;
+
+        Ok(InstallmentPlan {
+            transaction_id: transaction.id.clone(),
+            principal,
+            total,
+            default_after: terms.default_after,
+            grace_period: terms.grace_period,
+            installments,
+        })
+    }
+
+    /// Pays an installment in full, it has to be paid before it defaults
+    pub fn pay(
+        &mut self,
+        number: u32,
+        amount: Money,
+        now: DateTime<Utc>,
+    ) -> Result<(), InstallmentError> {
+        self.refresh(now);
+        let installment = self
+            .installments
+            .iter_mut()
+            .find(|installment| installment.number == number)
+            .ok_or(InstallmentError::UnknownInstallment(number))?;
+
+        match installment.status {
+            InstallmentStatus::Paid => return Err(InstallmentError::AlreadyPaid(number)),
+            InstallmentStatus::Defaulted => return Err(InstallmentError::Defaulted(number)),
+            InstallmentStatus::Scheduled | InstallmentStatus::Overdue => {}
+        }
+        if amount != installment.amount {
+            return Err(InstallmentError::WrongAmount {
+                number,
+                expected: installment.amount,
+                paid: amount,
+            });
+        }
+
+        installment.status = InstallmentStatus::Paid;
+        installment.paid_at = Some(now);
+        Ok(())
+    }
+
+    /// Marks unpaid installments overdue or defaulted as of `now`
+    pub fn refresh(&mut self, now: DateTime<Utc>) {
+        for installment in self.installments.iter_mut() {
+            if installment.status == InstallmentStatus::Paid {
+                continue;
+            }
+            let overdue_since = installment.due + self.grace_period;
+            installment.status = if now >= overdue_since + self.default_after {
+                InstallmentStatus::Defaulted
+            } else if now > overdue_since {
+                InstallmentStatus::Overdue
+            } else {
+                InstallmentStatus::Scheduled
+            };
+        }
+    }
+
+    /// What is left to pay
+    pub fn remaining(&self) -> Result<Money, MoneyError> {
+        let unpaid = self
+            .installments
+            .iter()
+            .filter(|installment| installment.status != InstallmentStatus::Paid)
+            .map(|installment| &installment.amount);
+        Ok(Money::checked_sum(unpaid)?.unwrap_or(Money::zero(self.total.currency)))
+    }
+
+    pub fn with_status(&self, status: InstallmentStatus) -> Vec<&Installment> {
+        self.installments
+            .iter()
+            .filter(|installment| installment.status == status)
+            .collect()
+    }
+
+    pub fn is_paid_off(&self) -> bool {
+        self.installments
+            .iter()
+            .all(|installment| installment.status == InstallmentStatus::Paid)
+    }
+
+    pub fn is_defaulted(&self) -> bool {
+        self.installments
+            .iter()
+            .any(|installment| installment.status == InstallmentStatus::Defaulted)
+    }
+}
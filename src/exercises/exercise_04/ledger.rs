@@ -0,0 +1,375 @@
+use super::money::{Currency, Money, MoneyError};
+use super::{Transaction, TransactionStatus, TransactionType};
+use crate::error::ErrorCode;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Accounts transactions post to, clearing accounts are suffixed with the payment method
+pub const CASH_ACCOUNT: &str = "cash";
+pub const CLEARING_ACCOUNT: &str = "clearing";
+pub const REVENUE_ACCOUNT: &str = "revenue";
+pub const REFUNDS_ACCOUNT: &str = "refunds";
+
+/// Side enum
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Debit,
+    Credit,
+}
+
+impl std::fmt::Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Side::Debit => write!(f, "debit"),
+            Side::Credit => write!(f, "credit"),
+        }
+    }
+}
+
+/// LedgerError enum
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// let error = LedgerError::Unbalanced { posting: String::from("p1"), currency: Currency::USD };
+///
+/// assert_eq!(format!("{}", error), "Posting p1 does not balance in USD");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum LedgerError {
+    NoEntries(String),
+    NotPositive { posting: String, amount: Money },
+    Unbalanced { posting: String, currency: Currency },
+    DuplicatePosting(String),
+    Amount(MoneyError),
+}
+
+impl From<MoneyError> for LedgerError {
+    fn from(error: MoneyError) -> Self {
+        LedgerError::Amount(error)
+    }
+}
+
+impl std::fmt::Display for LedgerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LedgerError::NoEntries(posting) => write!(f, "Posting {} has no entries", posting),
+            LedgerError::NotPositive { posting, amount } => write!(
+                f,
+                "Posting {} has an entry of {}, entries must be positive",
+                posting, amount
+            ),
+            LedgerError::Unbalanced { posting, currency } => {
+                write!(f, "Posting {} does not balance in {}", posting, currency)
+            }
+            LedgerError::DuplicatePosting(posting) => {
+                write!(f, "Posting {} is already in the ledger", posting)
+            }
+            LedgerError::Amount(error) => write!(f, "Invalid ledger amount: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for LedgerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LedgerError::Amount(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl ErrorCode for LedgerError {
+    fn code(&self) -> &'static str {
+        match self {
+            LedgerError::NoEntries(_) => "ledger.no_entries",
+            LedgerError::NotPositive { .. } => "ledger.not_positive",
+            LedgerError::Unbalanced { .. } => "ledger.unbalanced",
+            LedgerError::DuplicatePosting(_) => "ledger.duplicate_posting",
+            LedgerError::Amount(_) => "ledger.amount",
+        }
+    }
+}
+
+/// Entry struct, one side of a posting
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub account: String,
+    pub side: Side,
+    pub amount: Money,
+}
+
+/// Posting struct, entries whose debits and credits add up to the same amount
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+/// use chrono::Utc;
+///
+/// let posting = Posting::new("p1", Utc::now())
+///     .debit("cash", Money::new(1000, Currency::USD))
+///     .credit("revenue", Money::new(1000, Currency::USD));
+///
+/// assert_eq!(posting.validate(), Ok(()));
+///
+/// let unbalanced = Posting::new("p2", Utc::now())
+///     .debit("cash", Money::new(1000, Currency::USD))
+///     .credit("revenue", Money::new(900, Currency::USD));
+///
+/// assert_eq!(unbalanced.validate(), Err(LedgerError::Unbalanced { posting: String::from("p2"), currency: Currency::USD }));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Posting {
+    pub id: String,
+    pub posted_at: DateTime<Utc>,
+    pub entries: Vec<Entry>,
+}
+
+impl Posting {
+    pub fn new(id: &str, posted_at: DateTime<Utc>) -> Self {
+        Posting {
+            id: id.to_string(),
+            posted_at,
+            entries: vec![],
+        }
+    }
+
+    pub fn debit(self, account: &str, amount: Money) -> Self {
+        self.entry(account, Side::Debit, amount)
+    }
+
+    pub fn credit(self, account: &str, amount: Money) -> Self {
+        self.entry(account, Side::Credit, amount)
+    }
+
+    fn entry(mut self, account: &str, side: Side, amount: Money) -> Self {
+        self.entries.push(Entry {
+            account: account.to_string(),
+            side,
+            amount,
+        });
+        self
+    }
+
+    /// Every entry is positive and debits equal credits in every currency
+    pub fn validate(&self) -> Result<(), LedgerError> {
+        if self.entries.is_empty() {
+            return Err(LedgerError::NoEntries(self.id.clone()));
+        }
+
+        let mut net: HashMap<Currency, Money> = HashMap::new();
+        for entry in &self.entries {
+            if entry.amount.minor <= 0 {
+                return Err(LedgerError::NotPositive {
+                    posting: self.id.clone(),
+                    amount: entry.amount,
+                });
+            }
+            let currency = entry.amount.currency;
+            let total = net.entry(currency).or_insert(Money::zero(currency));
+            *total = match entry.side {
+                Side::Debit => total.checked_add(&entry.amount)?,
+                Side::Credit => total.checked_sub(&entry.amount)?,
+            };
+        }
+
+        let unbalanced = self
+            .entries
+            .iter()
+            .map(|entry| entry.amount.currency)
+            .find(|currency| net.get(currency).is_some_and(|total| !total.is_zero()));
+        match unbalanced {
+            Some(currency) => Err(LedgerError::Unbalanced {
+                posting: self.id.clone(),
+                currency,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// What a transaction posts in its current status, nothing until money moves
+    ///
+    /// Captured payments are owed by the method's clearing account until
+    /// they settle into cash, refunds go the other way.
+    pub fn for_transaction(transaction: &Transaction, posted_at: DateTime<Utc>) -> Option<Self> {
+        let clearing = format!("{}:{}", CLEARING_ACCOUNT, transaction.method);
+        let id = format!("{}:{}", transaction.id, transaction.status);
+        let posting = Posting::new(&id, posted_at);
+        let amount = transaction.amount;
+
+        match (&transaction.transaction_type, &transaction.status) {
+            (TransactionType::Payment, TransactionStatus::Captured) => Some(
+                posting
+                    .debit(&clearing, amount)
+                    .credit(REVENUE_ACCOUNT, amount),
+            ),
+            (TransactionType::Payment, TransactionStatus::Settled) => Some(
+                posting
+                    .debit(CASH_ACCOUNT, amount)
+                    .credit(&clearing, amount),
+            ),
+            (TransactionType::Refund, TransactionStatus::Captured) => Some(
+                posting
+                    .debit(REFUNDS_ACCOUNT, amount)
+                    .credit(&clearing, amount),
+            ),
+            (TransactionType::Refund, TransactionStatus::Settled) => Some(
+                posting
+                    .debit(&clearing, amount)
+                    .credit(CASH_ACCOUNT, amount),
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// TrialBalanceLine struct
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrialBalanceLine {
+    pub account: String,
+    /// Debits minus credits
+    pub balance: Money,
+}
+
+/// TrialBalance struct, the balance of every account at a point in time
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrialBalance {
+    pub at: DateTime<Utc>,
+    pub lines: Vec<TrialBalanceLine>,
+}
+
+impl TrialBalance {
+    /// Debit balances minus credit balances in `currency`, zero in a sound ledger
+    pub fn difference(&self, currency: Currency) -> Result<Money, MoneyError> {
+        let balances = self
+            .lines
+            .iter()
+            .filter(|line| line.balance.currency == currency)
+            .map(|line| &line.balance);
+        Ok(Money::checked_sum(balances)?.unwrap_or(Money::zero(currency)))
+    }
+
+    pub fn is_balanced(&self) -> bool {
+        self.lines
+            .iter()
+            .map(|line| line.balance.currency)
+            .collect::<HashSet<Currency>>()
+            .into_iter()
+            .all(|currency| self.difference(currency).is_ok_and(|total| total.is_zero()))
+    }
+}
+
+/// Ledger struct, an append-only journal of balanced postings
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+/// use chrono::{Duration, Utc};
+///
+/// let now = Utc::now();
+/// let mut payment = Transaction::new(
+///    String::from("t1"),
+///    TransactionType::Payment,
+///    TransactionStatus::Captured,
+///    PaymentMethod::CreditCard,
+///    Money::new(2399, Currency::USD),
+/// );
+/// let mut ledger = Ledger::new();
+///
+/// ledger.post_transaction(&payment, now).unwrap();
+/// payment.settle().unwrap();
+/// ledger.post_transaction(&payment, now + Duration::days(2)).unwrap();
+///
+/// assert_eq!(ledger.balance("cash", Currency::USD, now + Duration::days(1)), Ok(Money::zero(Currency::USD)));
+/// assert_eq!(ledger.balance("cash", Currency::USD, now + Duration::days(2)), Ok(Money::new(2399, Currency::USD)));
+/// assert_eq!(ledger.balance("revenue", Currency::USD, now), Ok(Money::new(-2399, Currency::USD)));
+/// assert_eq!(ledger.trial_balance(now + Duration::days(2)).unwrap().is_balanced(), true);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Ledger {
+    postings: Vec<Posting>,
+    /// Ids of the postings, to reject duplicates without scanning them all
+    posted: HashSet<String>,
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Ledger::default()
+    }
+
+    pub fn postings(&self) -> &[Posting] {
+        &self.postings
+    }
+
+    pub fn post(&mut self, posting: Posting) -> Result<(), LedgerError> {
+        posting.validate()?;
+        if !self.posted.insert(posting.id.clone()) {
+            return Err(LedgerError::DuplicatePosting(posting.id));
+        }
+        self.postings.push(posting);
+        Ok(())
+    }
+
+    /// Posts what the transaction's status calls for, returns whether anything was posted
+    pub fn post_transaction(
+        &mut self,
+        transaction: &Transaction,
+        posted_at: DateTime<Utc>,
+    ) -> Result<bool, LedgerError> {
+        match Posting::for_transaction(transaction, posted_at) {
+            Some(posting) => self.post(posting).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Debits minus credits of an account, counting postings up to `at`
+    pub fn balance(
+        &self,
+        account: &str,
+        currency: Currency,
+        at: DateTime<Utc>,
+    ) -> Result<Money, MoneyError> {
+        self.entries_until(at)
+            .filter(|entry| entry.account == account && entry.amount.currency == currency)
+            .try_fold(Money::zero(currency), |balance, entry| match entry.side {
+                Side::Debit => balance.checked_add(&entry.amount),
+                Side::Credit => balance.checked_sub(&entry.amount),
+            })
+    }
+
+    /// Balance of every account and currency, sorted by account and currency code
+    pub fn trial_balance(&self, at: DateTime<Utc>) -> Result<TrialBalance, MoneyError> {
+        let mut balances: BTreeMap<(&str, &str), Money> = BTreeMap::new();
+        for entry in self.entries_until(at) {
+            let currency = entry.amount.currency;
+            let balance = balances
+                .entry((entry.account.as_str(), currency.code()))
+                .or_insert(Money::zero(currency));
+            *balance = match entry.side {
+                Side::Debit => balance.checked_add(&entry.amount)?,
+                Side::Credit => balance.checked_sub(&entry.amount)?,
+            };
+        }
+
+        let lines = balances
+            .into_iter()
+            .map(|((account, _), balance)| TrialBalanceLine {
+                account: account.to_string(),
+                balance,
+            })
+            .collect();
+
+        Ok(TrialBalance { at, lines })
+    }
+
+    fn entries_until(&self, at: DateTime<Utc>) -> impl Iterator<Item = &Entry> {
+        self.postings
+            .iter()
+            .filter(move |posting| posting.posted_at <= at)
+            .flat_map(|posting| posting.entries.iter())
+    }
+}
@@ -0,0 +1,457 @@
+use crate::error::ErrorCode;
+
+/// Currency enum, ISO 4217 currencies with their minor units
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::Currency;
+///
+/// assert_eq!(format!("{}", Currency::USD), "USD");
+/// assert_eq!(Currency::USD.minor_units(), 2);
+/// assert_eq!(Currency::JPY.minor_units(), 0);
+/// assert_eq!("KWD".parse::<Currency>(), Ok(Currency::KWD));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Currency {
+    USD,
+    EUR,
+    GBP,
+    CHF,
+    CAD,
+    MXN,
+    JPY,
+    KWD,
+}
+
+impl Currency {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Currency::USD => "USD",
+            Currency::EUR => "EUR",
+            Currency::GBP => "GBP",
+            Currency::CHF => "CHF",
+            Currency::CAD => "CAD",
+            Currency::MXN => "MXN",
+            Currency::JPY => "JPY",
+            Currency::KWD => "KWD",
+        }
+    }
+
+    /// Number of decimal places used by the currency
+    pub fn minor_units(&self) -> u32 {
+        match self {
+            Currency::JPY => 0,
+            Currency::KWD => 3,
+            _ => 2,
+        }
+    }
+
+    /// How many minor units make a major unit, 100 cents for a dollar
+    pub fn minor_per_major(&self) -> i64 {
+        10_i64.pow(self.minor_units())
+    }
+}
+
+impl std::fmt::Display for Currency {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+impl std::str::FromStr for Currency {
+    type Err = MoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_uppercase().as_str() {
+            "USD" => Ok(Currency::USD),
+            "EUR" => Ok(Currency::EUR),
+            "GBP" => Ok(Currency::GBP),
+            "CHF" => Ok(Currency::CHF),
+            "CAD" => Ok(Currency::CAD),
+            "MXN" => Ok(Currency::MXN),
+            "JPY" => Ok(Currency::JPY),
+            "KWD" => Ok(Currency::KWD),
+            _ => Err(MoneyError::UnknownCurrency(s.to_string())),
+        }
+    }
+}
+
+/// RoundingMode enum
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::RoundingMode;
+///
+/// assert_eq!(RoundingMode::HalfUp.divide(5, 2), 3);
+/// assert_eq!(RoundingMode::HalfEven.divide(5, 2), 2);
+/// assert_eq!(RoundingMode::Down.divide(-7, 2), -3);
+/// assert_eq!(RoundingMode::Floor.divide(-7, 2), -4);
+/// assert_eq!(RoundingMode::Ceiling.divide(7, 2), 4);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// Half away from zero, the usual commercial rounding
+    HalfUp,
+    /// Half to the nearest even digit, the banker's rounding
+    HalfEven,
+    /// Towards zero
+    Down,
+    /// Away from zero
+    Up,
+    /// Towards negative infinity
+    Floor,
+    /// Towards positive infinity
+    Ceiling,
+}
+
+impl RoundingMode {
+    /// Divides `numerator` by `denominator` rounding the quotient with this mode
+    pub fn divide(&self, numerator: i128, denominator: i128) -> i128 {
+        let quotient = numerator / denominator;
+        let remainder = numerator % denominator;
+        if remainder == 0 {
+            return quotient;
+        }
+
+        let is_negative = (numerator < 0) != (denominator < 0);
+        let away_from_zero = if is_negative {
+            quotient - 1
+        } else {
+            quotient + 1
+        };
+        let twice_remainder = remainder.abs() * 2;
+        let denominator = denominator.abs();
+
+        match self {
+            RoundingMode::Down => quotient,
+            RoundingMode::Up => away_from_zero,
+            RoundingMode::Floor if is_negative => away_from_zero,
+            RoundingMode::Floor => quotient,
+            RoundingMode::Ceiling if is_negative => quotient,
+            RoundingMode::Ceiling => away_from_zero,
+            RoundingMode::HalfUp if twice_remainder >= denominator => away_from_zero,
+            RoundingMode::HalfUp => quotient,
+            RoundingMode::HalfEven if twice_remainder > denominator => away_from_zero,
+            RoundingMode::HalfEven if twice_remainder == denominator && quotient % 2 != 0 => {
+                away_from_zero
+            }
+            RoundingMode::HalfEven => quotient,
+        }
+    }
+}
+
+/// MoneyError enum
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::{Currency, MoneyError};
+///
+/// let error = MoneyError::CurrencyMismatch(Currency::USD, Currency::EUR);
+///
+/// assert_eq!(format!("{}", error), "Currency mismatch: USD and EUR");
+/// assert_eq!(format!("{}", MoneyError::Overflow), "Amount is out of range");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum MoneyError {
+    CurrencyMismatch(Currency, Currency),
+    Overflow,
+    DivisionByZero,
+    UnknownCurrency(String),
+    InvalidAmount(String),
+    TooPrecise(String, Currency),
+    MissingRate(Currency, Currency),
+}
+
+impl std::fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MoneyError::CurrencyMismatch(left, right) => {
+                write!(f, "Currency mismatch: {} and {}", left, right)
+            }
+            MoneyError::Overflow => write!(f, "Amount is out of range"),
+            MoneyError::DivisionByZero => write!(f, "Division by zero"),
+            MoneyError::UnknownCurrency(code) => write!(f, "Unknown currency: {}", code),
+            MoneyError::InvalidAmount(amount) => write!(f, "Invalid amount: {}", amount),
+            MoneyError::TooPrecise(amount, currency) => write!(
+                f,
+                "Amount {} has more than {} decimals for {}",
+                amount,
+                currency.minor_units(),
+                currency
+            ),
+            MoneyError::MissingRate(from, to) => {
+                write!(f, "No exchange rate from {} to {}", from, to)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MoneyError {}
+
+impl ErrorCode for MoneyError {
+    fn code(&self) -> &'static str {
+        match self {
+            MoneyError::CurrencyMismatch(_, _) => "money.currency_mismatch",
+            MoneyError::Overflow => "money.overflow",
+            MoneyError::DivisionByZero => "money.division_by_zero",
+            MoneyError::UnknownCurrency(_) => "money.unknown_currency",
+            MoneyError::InvalidAmount(_) => "money.invalid_amount",
+            MoneyError::TooPrecise(_, _) => "money.too_precise",
+            MoneyError::MissingRate(_, _) => "money.missing_rate",
+        }
+    }
+}
+
+/// Money struct, a fixed-point amount counted in the minor units of its currency
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::{Currency, Money, MoneyError};
+///
+/// let price = Money::parse("0.10", Currency::USD).unwrap();
+/// let tax = Money::parse("0.20", Currency::USD).unwrap();
+///
+/// assert_eq!(price.checked_add(&tax), Ok(Money::new(30, Currency::USD)));
+/// assert_eq!(format!("{}", Money::new(2399, Currency::USD)), "23.99 USD");
+/// assert_eq!(format!("{}", Money::new(500, Currency::JPY)), "500 JPY");
+/// assert_eq!("1.250 KWD".parse::<Money>(), Ok(Money::new(1250, Currency::KWD)));
+///
+/// let euros = Money::new(100, Currency::EUR);
+/// assert_eq!(price.checked_add(&euros), Err(MoneyError::CurrencyMismatch(Currency::USD, Currency::EUR)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Money {
+    pub minor: i64,
+    pub currency: Currency,
+}
+
+impl Money {
+    pub fn new(minor: i64, currency: Currency) -> Money {
+        Money { minor, currency }
+    }
+
+    pub fn zero(currency: Currency) -> Money {
+        Money::new(0, currency)
+    }
+
+    /// Parses a decimal amount, failing if it has more decimals than the currency allows
+    pub fn parse(amount: &str, currency: Currency) -> Result<Money, MoneyError> {
+        let (digits, scale) = parse_decimal(amount)?;
+        let minor_units = currency.minor_units();
+        if scale > minor_units {
+            return Err(MoneyError::TooPrecise(amount.trim().to_string(), currency));
+        }
+        let minor = digits
+            .checked_mul(10_i128.pow(minor_units - scale))
+            .ok_or(MoneyError::Overflow)?;
+        Money::from_i128(minor, currency)
+    }
+
+    /// Parses a decimal amount, rounding any extra decimals with `mode`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use clean_code_notes_exercises::exercises::exercise_04::{Currency, Money, RoundingMode};
+    ///
+    /// let amount = Money::parse_rounded("2.345", Currency::USD, RoundingMode::HalfEven).unwrap();
+    /// assert_eq!(amount, Money::new(234, Currency::USD));
+    /// ```
+    pub fn parse_rounded(
+        amount: &str,
+        currency: Currency,
+        mode: RoundingMode,
+    ) -> Result<Money, MoneyError> {
+        let (digits, scale) = parse_decimal(amount)?;
+        let minor_units = currency.minor_units();
+        let minor = if scale > minor_units {
+            mode.divide(digits, 10_i128.pow(scale - minor_units))
+        } else {
+            digits
+                .checked_mul(10_i128.pow(minor_units - scale))
+                .ok_or(MoneyError::Overflow)?
+        };
+        Money::from_i128(minor, currency)
+    }
+
+    fn from_i128(minor: i128, currency: Currency) -> Result<Money, MoneyError> {
+        let minor = i64::try_from(minor).map_err(|_| MoneyError::Overflow)?;
+        Ok(Money::new(minor, currency))
+    }
+
+    /// The amount without its currency, `"23.99"`
+    pub fn to_decimal(&self) -> String {
+        let minor_units = self.currency.minor_units();
+        let sign = if self.is_negative() { "-" } else { "" };
+        let absolute = self.minor.unsigned_abs();
+
+        if minor_units == 0 {
+            return format!("{}{}", sign, absolute);
+        }
+        let minor_per_major = self.currency.minor_per_major() as u64;
+        format!(
+            "{}{}.{:0width$}",
+            sign,
+            absolute / minor_per_major,
+            absolute % minor_per_major,
+            width = minor_units as usize
+        )
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.minor == 0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.minor < 0
+    }
+
+    fn same_currency(&self, other: &Money) -> Result<(), MoneyError> {
+        match self.currency == other.currency {
+            true => Ok(()),
+            false => Err(MoneyError::CurrencyMismatch(self.currency, other.currency)),
+        }
+    }
+
+    pub fn checked_add(&self, other: &Money) -> Result<Money, MoneyError> {
+        self.same_currency(other)?;
+        let minor = self
+            .minor
+            .checked_add(other.minor)
+            .ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(minor, self.currency))
+    }
+
+    pub fn checked_sub(&self, other: &Money) -> Result<Money, MoneyError> {
+        self.same_currency(other)?;
+        let minor = self
+            .minor
+            .checked_sub(other.minor)
+            .ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(minor, self.currency))
+    }
+
+    pub fn checked_mul(&self, factor: i64) -> Result<Money, MoneyError> {
+        let minor = self.minor.checked_mul(factor).ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(minor, self.currency))
+    }
+
+    pub fn checked_neg(&self) -> Result<Money, MoneyError> {
+        let minor = self.minor.checked_neg().ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(minor, self.currency))
+    }
+
+    /// Multiplies by `numerator / denominator`, rounding the result with `mode`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use clean_code_notes_exercises::exercises::exercise_04::{Currency, Money, RoundingMode};
+    ///
+    /// let amount = Money::new(1000, Currency::USD);
+    ///
+    /// assert_eq!(amount.mul_ratio(1, 3, RoundingMode::HalfUp), Ok(Money::new(333, Currency::USD)));
+    /// assert_eq!(amount.mul_ratio(2, 3, RoundingMode::Down), Ok(Money::new(666, Currency::USD)));
+    /// ```
+    pub fn mul_ratio(
+        &self,
+        numerator: i64,
+        denominator: i64,
+        mode: RoundingMode,
+    ) -> Result<Money, MoneyError> {
+        if denominator == 0 {
+            return Err(MoneyError::DivisionByZero);
+        }
+        let minor = mode.divide(self.minor as i128 * numerator as i128, denominator as i128);
+        Money::from_i128(minor, self.currency)
+    }
+
+    /// Sums amounts of the same currency, `None` when the iterator is empty
+    pub fn checked_sum<'a>(
+        amounts: impl IntoIterator<Item = &'a Money>,
+    ) -> Result<Option<Money>, MoneyError> {
+        let mut total: Option<Money> = None;
+        for amount in amounts {
+            total = Some(match total {
+                Some(total) => total.checked_add(amount)?,
+                None => *amount,
+            });
+        }
+        Ok(total)
+    }
+}
+
+/// Splits a decimal string into its digits and the number of decimals
+pub(crate) fn parse_decimal(amount: &str) -> Result<(i128, u32), MoneyError> {
+    let invalid = || MoneyError::InvalidAmount(amount.to_string());
+    let trimmed = amount.trim();
+    let (is_negative, unsigned) = match trimmed.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+
+    let is_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) {
+        return Err(invalid());
+    }
+    if unsigned.ends_with('.') || fraction.len() > 18 {
+        return Err(invalid());
+    }
+
+    let digits: i128 = format!("{}{}", whole, fraction)
+        .parse()
+        .map_err(|_| invalid())?;
+    let digits = if is_negative { -digits } else { digits };
+    Ok((digits, fraction.len() as u32))
+}
+
+impl std::fmt::Display for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {}", self.to_decimal(), self.currency)
+    }
+}
+
+/// Parses amounts written as `"23.99 USD"`
+impl std::str::FromStr for Money {
+    type Err = MoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (amount, currency) = s
+            .trim()
+            .rsplit_once(' ')
+            .ok_or_else(|| MoneyError::InvalidAmount(s.to_string()))?;
+        Money::parse(amount, currency.parse()?)
+    }
+}
+
+impl serde::Serialize for Currency {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Currency {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        code.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Money is serialized as it is displayed, `"23.99 USD"`
+impl serde::Serialize for Money {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Money {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let amount = String::deserialize(deserializer)?;
+        amount.parse().map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,395 @@
+use super::money::{Currency, Money};
+use super::{PaymentMethod, Transaction, TransactionError, TransactionStatus, TransactionType};
+use crate::error::ErrorCode;
+use crate::retry::{Clock, Retry, RetryError};
+use std::collections::HashMap;
+
+/// ProcessorError enum
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// assert_eq!(format!("{}", ProcessorError::NotRegistered(PaymentMethod::PayPal)), "No processor registered for PayPal");
+/// assert_eq!(format!("{}", ProcessorError::Unavailable(String::from("CARD"))), "Gateway CARD is unavailable");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProcessorError {
+    NotRegistered(PaymentMethod),
+    Declined { id: String, reason: String },
+    Unavailable(String),
+    UnknownAuthorization(String),
+}
+
+impl std::fmt::Display for ProcessorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ProcessorError::NotRegistered(method) => {
+                write!(f, "No processor registered for {}", method)
+            }
+            ProcessorError::Declined { id, reason } => {
+                write!(f, "Transaction {} was declined: {}", id, reason)
+            }
+            ProcessorError::Unavailable(gateway) => write!(f, "Gateway {} is unavailable", gateway),
+            ProcessorError::UnknownAuthorization(id) => {
+                write!(f, "Transaction {} has no authorization to capture", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProcessorError {}
+
+impl ErrorCode for ProcessorError {
+    fn code(&self) -> &'static str {
+        match self {
+            ProcessorError::NotRegistered(_) => "processor.not_registered",
+            ProcessorError::Declined { .. } => "processor.declined",
+            ProcessorError::Unavailable(_) => "processor.unavailable",
+            ProcessorError::UnknownAuthorization(_) => "processor.unknown_authorization",
+        }
+    }
+}
+
+/// Talks to the gateway behind a payment method
+///
+/// Every call returns the gateway's reference for the operation.
+pub trait PaymentProcessor {
+    fn authorize(&mut self, transaction: &Transaction) -> Result<String, ProcessorError>;
+    fn capture(&mut self, transaction: &Transaction) -> Result<String, ProcessorError>;
+    /// Sends a refund issued by `Transaction::issue_refund`, reversing part of
+    /// the payment in its `refund_of`. The same refund sent again must not be
+    /// reversed twice.
+    fn refund(&mut self, refund: &Transaction) -> Result<String, ProcessorError>;
+}
+
+/// SimulatedGateway struct, an in-memory gateway for local runs and tests
+///
+/// The same gateway backs every built-in method, cards only differ by their
+/// credit limit. Refunds are kept by their own id and get references of their
+/// own, so sending the same refund twice only reverses it once.
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// let mut gateway = SimulatedGateway::new("CARD");
+///
+/// assert_eq!(gateway.hold("t1", Money::new(2399, Currency::USD)), Ok(String::from("CARD-000001")));
+/// assert_eq!(gateway.capture("t1"), Ok(String::from("CARD-000002")));
+/// assert_eq!(gateway.captured("t1"), Some(Money::new(2399, Currency::USD)));
+///
+/// let refund = Money::new(399, Currency::USD);
+/// assert_eq!(gateway.reverse("t1", "r1", refund), Ok(String::from("CARD-R000001")));
+/// assert_eq!(gateway.reverse("t1", "r1", refund), Ok(String::from("CARD-R000001")));
+/// assert_eq!(gateway.reversed("t1"), Some(refund));
+///
+/// gateway.online = false;
+/// assert_eq!(gateway.hold("t2", Money::new(100, Currency::USD)), Err(ProcessorError::Unavailable(String::from("CARD"))));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SimulatedGateway {
+    name: String,
+    pub online: bool,
+    credit_limit: Option<Money>,
+    operations: u64,
+    refunds_issued: u64,
+    holds: HashMap<String, Money>,
+    captures: HashMap<String, Money>,
+    reversals: HashMap<String, Money>,
+    /// Reference of every refund, by refund id
+    refunds: HashMap<String, String>,
+}
+
+impl SimulatedGateway {
+    pub fn new(name: &str) -> Self {
+        SimulatedGateway {
+            name: name.to_string(),
+            online: true,
+            credit_limit: None,
+            operations: 0,
+            refunds_issued: 0,
+            holds: HashMap::new(),
+            captures: HashMap::new(),
+            reversals: HashMap::new(),
+            refunds: HashMap::new(),
+        }
+    }
+
+    /// Declines authorizations over the limit or in another currency
+    pub fn with_credit_limit(mut self, credit_limit: Money) -> Self {
+        self.credit_limit = Some(credit_limit);
+        self
+    }
+
+    fn ensure_online(&self) -> Result<(), ProcessorError> {
+        match self.online {
+            true => Ok(()),
+            false => Err(ProcessorError::Unavailable(self.name.clone())),
+        }
+    }
+
+    fn reference(&mut self) -> Result<String, ProcessorError> {
+        self.ensure_online()?;
+        self.operations += 1;
+        Ok(format!("{}-{:06}", self.name, self.operations))
+    }
+
+    fn refund_reference(&mut self) -> Result<String, ProcessorError> {
+        self.ensure_online()?;
+        self.refunds_issued += 1;
+        Ok(format!("{}-R{:06}", self.name, self.refunds_issued))
+    }
+
+    pub fn hold(&mut self, id: &str, amount: Money) -> Result<String, ProcessorError> {
+        let reference = self.reference()?;
+        self.holds.insert(id.to_string(), amount);
+        Ok(reference)
+    }
+
+    pub fn capture(&mut self, id: &str) -> Result<String, ProcessorError> {
+        if !self.holds.contains_key(id) {
+            return Err(ProcessorError::UnknownAuthorization(id.to_string()));
+        }
+        let reference = self.reference()?;
+        if let Some(amount) = self.holds.remove(id) {
+            self.captures.insert(id.to_string(), amount);
+        }
+        Ok(reference)
+    }
+
+    /// Reverses `amount` of the captured payment `id` as the refund `refund_id`
+    pub fn reverse(
+        &mut self,
+        id: &str,
+        refund_id: &str,
+        amount: Money,
+    ) -> Result<String, ProcessorError> {
+        if let Some(reference) = self.refunds.get(refund_id) {
+            return Ok(reference.clone());
+        }
+        let captured = self.captured(id).ok_or(ProcessorError::Declined {
+            id: id.to_string(),
+            reason: String::from("nothing was captured"),
+        })?;
+        let reversed = self.reversed(id).unwrap_or(Money::zero(captured.currency));
+        let total = reversed
+            .checked_add(&amount)
+            .map_err(|e| ProcessorError::Declined {
+                id: id.to_string(),
+                reason: e.to_string(),
+            })?;
+        if total.minor > captured.minor {
+            return Err(ProcessorError::Declined {
+                id: id.to_string(),
+                reason: String::from("refunds exceed the captured amount"),
+            });
+        }
+
+        let reference = self.refund_reference()?;
+        self.reversals.insert(id.to_string(), total);
+        self.refunds
+            .insert(refund_id.to_string(), reference.clone());
+        Ok(reference)
+    }
+
+    pub fn held(&self, id: &str) -> Option<Money> {
+        self.holds.get(id).copied()
+    }
+
+    pub fn captured(&self, id: &str) -> Option<Money> {
+        self.captures.get(id).copied()
+    }
+
+    pub fn reversed(&self, id: &str) -> Option<Money> {
+        self.reversals.get(id).copied()
+    }
+}
+
+impl PaymentProcessor for SimulatedGateway {
+    fn authorize(&mut self, transaction: &Transaction) -> Result<String, ProcessorError> {
+        if let Some(credit_limit) = self.credit_limit {
+            let declined = |reason: String| ProcessorError::Declined {
+                id: transaction.id.clone(),
+                reason,
+            };
+            if transaction.currency() != credit_limit.currency {
+                return Err(declined(format!(
+                    "card does not accept {}",
+                    transaction.currency()
+                )));
+            }
+            if transaction.amount.minor > credit_limit.minor {
+                return Err(declined(format!(
+                    "over the credit limit of {}",
+                    credit_limit
+                )));
+            }
+        }
+        self.hold(&transaction.id, transaction.amount)
+    }
+
+    fn capture(&mut self, transaction: &Transaction) -> Result<String, ProcessorError> {
+        SimulatedGateway::capture(self, &transaction.id)
+    }
+
+    fn refund(&mut self, refund: &Transaction) -> Result<String, ProcessorError> {
+        match refund.refund_of() {
+            Some(payment) => self.reverse(payment, &refund.id, refund.amount),
+            None => Err(ProcessorError::Declined {
+                id: refund.id.clone(),
+                reason: String::from("it refunds no payment"),
+            }),
+        }
+    }
+}
+
+/// ProcessorRegistry struct, picks the processor of a transaction by its payment method
+///
+/// Processors are registered at runtime, a new method only needs a
+/// `PaymentMethod::Other` name and a processor.
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// let mut registry = ProcessorRegistry::simulated();
+/// registry.register(
+///     PaymentMethod::Other(String::from("gift card")),
+///     SimulatedGateway::new("GIFT"),
+/// );
+///
+/// let mut transaction = Transaction::new(
+///    String::from("t1"),
+///    TransactionType::Payment,
+///    TransactionStatus::Pending,
+///    PaymentMethod::Other(String::from("gift card")),
+///    Money::new(2399, Currency::USD),
+/// );
+///
+/// assert_eq!(
+///     registry.process(&mut transaction),
+///     Ok(String::from("Processing payment gift card for amount: 23.99 USD | reference: GIFT-000001"))
+/// );
+/// assert_eq!(transaction.status(), &TransactionStatus::Authorized);
+/// ```
+#[derive(Default)]
+pub struct ProcessorRegistry {
+    processors: HashMap<PaymentMethod, Box<dyn PaymentProcessor>>,
+}
+
+impl ProcessorRegistry {
+    pub fn new() -> Self {
+        ProcessorRegistry::default()
+    }
+
+    /// Processors for the built-in methods backed by simulated gateways
+    pub fn simulated() -> Self {
+        let mut registry = ProcessorRegistry::new();
+        registry.register(
+            PaymentMethod::CreditCard,
+            SimulatedGateway::new("CARD").with_credit_limit(Money::new(500_000, Currency::USD)),
+        );
+        registry.register(PaymentMethod::PayPal, SimulatedGateway::new("PAYPAL"));
+        registry.register(PaymentMethod::Plan, SimulatedGateway::new("PLAN"));
+        registry
+    }
+
+    /// Registers a processor, replacing the one the method had
+    pub fn register(&mut self, method: PaymentMethod, processor: impl PaymentProcessor + 'static) {
+        self.processors.insert(method, Box::new(processor));
+    }
+
+    pub fn is_registered(&self, method: &PaymentMethod) -> bool {
+        self.processors.contains_key(method)
+    }
+
+    fn processor(
+        &mut self,
+        method: &PaymentMethod,
+    ) -> Result<&mut (dyn PaymentProcessor + 'static), ProcessorError> {
+        self.processors
+            .get_mut(method)
+            .map(|processor| processor.as_mut())
+            .ok_or(ProcessorError::NotRegistered(method.clone()))
+    }
+
+    /// Advances the transaction one step through its gateway
+    ///
+    /// Payments are authorized and captured, refunds are sent to the gateway
+    /// on their first step. The other steps only change the status.
+    pub fn process(&mut self, transaction: &mut Transaction) -> Result<String, TransactionError> {
+        let reference = match (&transaction.transaction_type, &transaction.status) {
+            (TransactionType::Refund, TransactionStatus::Pending) => {
+                Some(self.processor(&transaction.method)?.refund(transaction)?)
+            }
+            (TransactionType::Refund, _) => None,
+            (_, TransactionStatus::Pending) => Some(
+                self.processor(&transaction.method)?
+                    .authorize(transaction)?,
+            ),
+            (_, TransactionStatus::Authorized) => {
+                Some(self.processor(&transaction.method)?.capture(transaction)?)
+            }
+            _ => None,
+        };
+        let message = transaction.process()?;
+
+        Ok(match reference {
+            Some(reference) => format!("{} | reference: {}", message, reference),
+            None => message,
+        })
+    }
+
+    /// Processes the transaction, trying again while its gateway is unavailable
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use clean_code_notes_exercises::exercises::exercise_04::*;
+    /// use clean_code_notes_exercises::retry::*;
+    /// use chrono::Utc;
+    ///
+    /// let mut registry = ProcessorRegistry::simulated();
+    /// let mut payment = Transaction::new(
+    ///    String::from("t1"),
+    ///    TransactionType::Payment,
+    ///    TransactionStatus::Pending,
+    ///    PaymentMethod::CreditCard,
+    ///    Money::new(2399, Currency::USD),
+    /// );
+    /// let clock = ManualClock::new(Utc::now());
+    /// let mut retry = Retry::new(RetryPolicy::new(3), &clock);
+    ///
+    /// assert_eq!(registry.process_with_retry(&mut payment, &mut retry).is_ok(), true);
+    /// assert_eq!(payment.status(), &TransactionStatus::Authorized);
+    /// ```
+    pub fn process_with_retry<C: Clock>(
+        &mut self,
+        transaction: &mut Transaction,
+        retry: &mut Retry<C>,
+    ) -> Result<String, RetryError<TransactionError>> {
+        retry.run(TransactionError::is_transient, |_| {
+            self.process(transaction)
+        })
+    }
+
+    /// Refunds part of a payment through its gateway, see `Transaction::issue_refund`
+    ///
+    /// The gateway's reference for the refund is kept in its `reference` metadata.
+    pub fn refund(
+        &mut self,
+        payment: &mut Transaction,
+        refund_id: String,
+        amount: Money,
+    ) -> Result<Transaction, TransactionError> {
+        let mut refunded = payment.clone();
+        let refund = refunded.issue_refund(refund_id, amount)?;
+        let reference = self.processor(&payment.method)?.refund(&refund)?;
+        *payment = refunded;
+        Ok(refund.with_metadata("reference", &reference))
+    }
+}
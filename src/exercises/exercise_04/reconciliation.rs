@@ -0,0 +1,248 @@
+use super::formats::FormatError;
+use super::money::{Money, MoneyError};
+use super::{Transaction, TransactionType};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::io::Read;
+
+/// StatementLine struct, one record of a processor's settlement file
+///
+/// Amounts are signed, refunds are paid out as negative amounts.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct StatementLine {
+    pub reference: Option<String>,
+    pub amount: Money,
+    pub settled_at: DateTime<Utc>,
+}
+
+/// Reads a settlement file with `reference`, `amount` and `settled_at` columns
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// let csv = "\
+/// reference,amount,settled_at
+/// t1,23.99 USD,2024-03-01T10:00:00Z
+/// ,-5.00 USD,2024-03-01T11:00:00Z
+/// ";
+///
+/// let lines = import_statement_csv(csv.as_bytes()).unwrap();
+///
+/// assert_eq!(lines[0].reference, Some(String::from("t1")));
+/// assert_eq!(lines[1].reference, None);
+/// assert_eq!(lines[1].amount, Money::new(-500, Currency::USD));
+/// ```
+pub fn import_statement_csv<R: Read>(reader: R) -> Result<Vec<StatementLine>, FormatError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    let lines = reader
+        .deserialize()
+        .collect::<Result<Vec<StatementLine>, csv::Error>>()?;
+    Ok(lines)
+}
+
+/// ReconciliationConfig struct
+///
+/// `amount_tolerance` is in minor units. `date_tolerance` only applies to
+/// lines without a reference, which are matched on amount and date.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconciliationConfig {
+    pub amount_tolerance: i64,
+    pub date_tolerance: Duration,
+}
+
+impl Default for ReconciliationConfig {
+    fn default() -> Self {
+        ReconciliationConfig {
+            amount_tolerance: 0,
+            date_tolerance: Duration::days(1),
+        }
+    }
+}
+
+/// Match struct, a transaction found in the statement
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub transaction_id: String,
+    /// Index of the statement line
+    pub line: usize,
+    /// Statement amount minus ours, within the tolerance
+    pub difference: i64,
+    /// Matched on amount and date because the line had no reference
+    pub fuzzy: bool,
+}
+
+/// Mismatch struct, a transaction whose statement amount is off
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub transaction_id: String,
+    pub line: usize,
+    pub expected: Money,
+    pub found: Money,
+}
+
+/// ReconciliationReport struct
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ReconciliationReport {
+    pub matched: Vec<Match>,
+    pub mismatched: Vec<Mismatch>,
+    /// Ids of our transactions the statement doesn't have
+    pub missing: Vec<String>,
+    /// Statement lines we have no transaction for
+    pub extra: Vec<StatementLine>,
+}
+
+impl ReconciliationReport {
+    pub fn is_clean(&self) -> bool {
+        self.mismatched.is_empty() && self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+/// What a transaction should look like on a statement, refunds are negative
+pub fn settled_amount(transaction: &Transaction) -> Result<Money, MoneyError> {
+    match transaction.transaction_type {
+        TransactionType::Payment => Ok(transaction.amount),
+        TransactionType::Refund => transaction.amount.checked_neg(),
+    }
+}
+
+/// Reconciles our transactions with a settlement statement
+///
+/// Lines with a reference are matched by id, the rest are matched to the
+/// unmatched transaction with the closest amount and then the closest date.
+/// `booked_at` tells when each transaction settled on our side. Fails when
+/// the amount of a refund can't be negated.
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+/// use chrono::{Duration, TimeZone, Utc};
+///
+/// let day = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+/// let payment = |id: &str, minor: i64| Transaction::new(
+///    String::from(id),
+///    TransactionType::Payment,
+///    TransactionStatus::Settled,
+///    PaymentMethod::CreditCard,
+///    Money::new(minor, Currency::USD),
+/// );
+/// let ours = vec![payment("t1", 2399), payment("t2", 1000), payment("t3", 500)];
+/// let statement = vec![
+///     StatementLine { reference: Some(String::from("t1")), amount: Money::new(2399, Currency::USD), settled_at: day },
+///     StatementLine { reference: None, amount: Money::new(1000, Currency::USD), settled_at: day + Duration::hours(3) },
+///     StatementLine { reference: Some(String::from("t9")), amount: Money::new(700, Currency::USD), settled_at: day },
+/// ];
+///
+/// let report = reconcile(&ours, &statement, &ReconciliationConfig::default(), |_| day).unwrap();
+///
+/// assert_eq!(report.matched.len(), 2);
+/// assert_eq!(report.matched[1].fuzzy, true);
+/// assert_eq!(report.missing, vec![String::from("t3")]);
+/// assert_eq!(report.extra, vec![statement[2].clone()]);
+/// ```
+pub fn reconcile(
+    transactions: &[Transaction],
+    statement: &[StatementLine],
+    config: &ReconciliationConfig,
+    booked_at: impl Fn(&Transaction) -> DateTime<Utc>,
+) -> Result<ReconciliationReport, MoneyError> {
+    let mut report = ReconciliationReport::default();
+    let mut reconciled = vec![false; transactions.len()];
+    let mut unreferenced: Vec<usize> = vec![];
+    let expected_amounts = transactions
+        .iter()
+        .map(settled_amount)
+        .collect::<Result<Vec<Money>, MoneyError>>()?;
+    // Indexes of the transactions by id, in their order, for the lines with a reference
+    let mut by_reference: HashMap<&str, VecDeque<usize>> = HashMap::new();
+    for (index, transaction) in transactions.iter().enumerate() {
+        by_reference
+            .entry(transaction.id.as_str())
+            .or_default()
+            .push_back(index);
+    }
+
+    for (line, record) in statement.iter().enumerate() {
+        let Some(reference) = &record.reference else {
+            unreferenced.push(line);
+            continue;
+        };
+        let found = by_reference
+            .get_mut(reference.as_str())
+            .and_then(VecDeque::pop_front);
+        let Some(index) = found else {
+            report.extra.push(record.clone());
+            continue;
+        };
+
+        reconciled[index] = true;
+        let transaction = &transactions[index];
+        let expected = expected_amounts[index];
+        match difference(&expected, &record.amount, config.amount_tolerance) {
+            Some(difference) => report.matched.push(Match {
+                transaction_id: transaction.id.clone(),
+                line,
+                difference,
+                fuzzy: false,
+            }),
+            None => report.mismatched.push(Mismatch {
+                transaction_id: transaction.id.clone(),
+                line,
+                expected,
+                found: record.amount,
+            }),
+        }
+    }
+
+    for line in unreferenced {
+        let record = &statement[line];
+        let closest = transactions
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !reconciled[*index])
+            .filter_map(|(index, transaction)| {
+                let difference = difference(
+                    &expected_amounts[index],
+                    &record.amount,
+                    config.amount_tolerance,
+                )?;
+                let drift = (booked_at(transaction) - record.settled_at).abs();
+                (drift <= config.date_tolerance).then_some((index, difference, drift))
+            })
+            .min_by_key(|(_, difference, drift)| (difference.unsigned_abs(), *drift));
+
+        match closest {
+            Some((index, difference, _)) => {
+                reconciled[index] = true;
+                report.matched.push(Match {
+                    transaction_id: transactions[index].id.clone(),
+                    line,
+                    difference,
+                    fuzzy: true,
+                });
+            }
+            None => report.extra.push(record.clone()),
+        }
+    }
+
+    report.missing = transactions
+        .iter()
+        .zip(reconciled)
+        .filter(|(_, reconciled)| !reconciled)
+        .map(|(transaction, _)| transaction.id.clone())
+        .collect();
+    Ok(report)
+}
+
+/// `found - expected` in minor units when it's within the tolerance
+fn difference(expected: &Money, found: &Money, tolerance: i64) -> Option<i64> {
+    let difference = found.checked_sub(expected).ok()?.minor;
+    u64::try_from(tolerance)
+        .is_ok_and(|tolerance| difference.unsigned_abs() <= tolerance)
+        .then_some(difference)
+}
@@ -0,0 +1,96 @@
+use super::money::{Money, MoneyError};
+use super::{Transaction, TransactionError, TransactionStatus, TransactionType};
+
+/// Refunds of a payment
+///
+/// A payment can be refunded partially and many times over, as long as the
+/// refunds together don't go over the captured amount. Once it's fully
+/// refunded the payment moves to `Refunded`.
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// let mut payment = Transaction::new(
+///    String::from("t1"),
+///    TransactionType::Payment,
+///    TransactionStatus::Captured,
+///    PaymentMethod::CreditCard,
+///    Money::new(5000, Currency::USD),
+/// );
+///
+/// let refund = payment.issue_refund(String::from("r1"), Money::new(2000, Currency::USD)).unwrap();
+/// assert_eq!(refund.transaction_type, TransactionType::Refund);
+/// assert_eq!(refund.refund_of(), Some("t1"));
+/// assert_eq!(payment.refundable(), Money::new(3000, Currency::USD));
+///
+/// assert_eq!(
+///     payment.issue_refund(String::from("r2"), Money::new(3500, Currency::USD)),
+///     Err(TransactionError::OverRefund {
+///         id: String::from("t1"),
+///         requested: Money::new(3500, Currency::USD),
+///         refundable: Money::new(3000, Currency::USD),
+///     })
+/// );
+///
+/// payment.issue_refund(String::from("r2"), Money::new(3000, Currency::USD)).unwrap();
+/// assert_eq!(payment.status(), &TransactionStatus::Refunded);
+/// ```
+impl Transaction {
+    /// Amount of a payment still available to refund, refunds can't be refunded
+    pub fn refundable(&self) -> Money {
+        match self.transaction_type {
+            TransactionType::Payment => Money::new(
+                self.amount.minor.saturating_sub(self.refunded.minor),
+                self.amount.currency,
+            ),
+            TransactionType::Refund => Money::zero(self.amount.currency),
+        }
+    }
+
+    /// Creates a pending refund for part or all of what's left of this payment
+    pub fn issue_refund(
+        &mut self,
+        refund_id: String,
+        amount: Money,
+    ) -> Result<Transaction, TransactionError> {
+        if !self.status.can_transition_to(&TransactionStatus::Refunded) {
+            return Err(TransactionError::IllegalTransition {
+                id: self.id.clone(),
+                from: self.status.clone(),
+                to: TransactionStatus::Refunded,
+            });
+        }
+        if amount.minor <= 0 {
+            return Err(MoneyError::InvalidAmount(amount.to_string()).into());
+        }
+
+        let refundable = self.refundable();
+        if amount.currency != refundable.currency {
+            return Err(MoneyError::CurrencyMismatch(refundable.currency, amount.currency).into());
+        }
+        if amount.minor > refundable.minor {
+            return Err(TransactionError::OverRefund {
+                id: self.id.clone(),
+                requested: amount,
+                refundable,
+            });
+        }
+
+        self.refunded = self.refunded.checked_add(&amount)?;
+        if self.refundable().is_zero() {
+            self.refund()?;
+        }
+
+        let mut refund = Transaction::new(
+            refund_id,
+            TransactionType::Refund,
+            TransactionStatus::Pending,
+            self.method.clone(),
+            amount,
+        );
+        refund.refund_of = Some(self.id.clone());
+        Ok(refund)
+    }
+}
@@ -0,0 +1,275 @@
+use super::fees::{Charges, FeeTable};
+use super::idempotency::IdempotencyRecord;
+use super::money::{Currency, Money};
+use super::{PaymentMethod, Transaction, TransactionError, TransactionStatus, TransactionType};
+use std::collections::HashMap;
+
+/// TransactionOutcome enum
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransactionOutcome {
+    Processed {
+        message: String,
+        status: TransactionStatus,
+    },
+    Rejected(TransactionError),
+    /// A transaction id seen before, with what it returned back then
+    Replayed(IdempotencyRecord),
+}
+
+/// TransactionResult struct, what happened to one transaction of a batch
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// let mut transaction = Transaction::new(
+///    String::from("t1"),
+///    TransactionType::Payment,
+///    TransactionStatus::Pending,
+///    PaymentMethod::CreditCard,
+///    Money::new(2399, Currency::USD),
+/// );
+///
+/// let result = TransactionResult::process(&mut transaction);
+///
+/// assert_eq!(result.id, "t1");
+/// assert_eq!(result.is_processed(), true);
+/// assert_eq!(result.error(), None);
+/// assert_eq!(format!("{}", result), "Processing payment credit card for amount: 23.99 USD");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionResult {
+    pub id: String,
+    pub transaction_type: TransactionType,
+    pub method: PaymentMethod,
+    pub amount: Money,
+    pub outcome: TransactionOutcome,
+    /// Fee, tax and net amount of a processed transaction
+    pub charges: Option<Charges>,
+}
+
+impl TransactionResult {
+    pub fn process(transaction: &mut Transaction) -> TransactionResult {
+        TransactionResult::process_with_fees(transaction, &FeeTable::default())
+    }
+
+    /// Processes the transaction and works out its charges
+    ///
+    /// Fees and taxes are charged once, on the step that captures a payment.
+    /// Other steps and refunds have no charges. A payment whose charges can't
+    /// be worked out is rejected untouched.
+    pub fn process_with_fees(transaction: &mut Transaction, fees: &FeeTable) -> TransactionResult {
+        TransactionResult::process_through(transaction, fees, Transaction::process)
+    }
+
+    /// Like `process_with_fees`, with `advance` moving the transaction one
+    /// step, such as `ProcessorRegistry::process` going through its gateway
+    pub(crate) fn process_through(
+        transaction: &mut Transaction,
+        fees: &FeeTable,
+        advance: impl FnOnce(&mut Transaction) -> Result<String, TransactionError>,
+    ) -> TransactionResult {
+        let charges = match is_charged(transaction) {
+            true => match fees.charges(transaction) {
+                Ok(charges) => Some(charges),
+                Err(e) => {
+                    return TransactionResult::new(
+                        transaction,
+                        TransactionOutcome::Rejected(TransactionError::Amount(e)),
+                    )
+                }
+            },
+            false => None,
+        };
+
+        match advance(transaction) {
+            Ok(message) => {
+                let outcome = TransactionOutcome::Processed {
+                    message,
+                    status: transaction.status.clone(),
+                };
+                TransactionResult {
+                    charges,
+                    ..TransactionResult::new(transaction, outcome)
+                }
+            }
+            Err(e) => TransactionResult::new(transaction, TransactionOutcome::Rejected(e)),
+        }
+    }
+
+    pub fn new(transaction: &Transaction, outcome: TransactionOutcome) -> TransactionResult {
+        TransactionResult {
+            id: transaction.id.clone(),
+            transaction_type: transaction.transaction_type.clone(),
+            method: transaction.method.clone(),
+            amount: transaction.amount,
+            outcome,
+            charges: None,
+        }
+    }
+
+    pub fn is_processed(&self) -> bool {
+        matches!(self.outcome, TransactionOutcome::Processed { .. })
+    }
+
+    pub fn error(&self) -> Option<&TransactionError> {
+        match &self.outcome {
+            TransactionOutcome::Rejected(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for TransactionResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.outcome {
+            TransactionOutcome::Processed { message, .. } => write!(f, "{}", message),
+            TransactionOutcome::Rejected(e) => write!(f, "{}", e),
+            TransactionOutcome::Replayed(record) => write!(f, "{}", record.message),
+        }
+    }
+}
+
+/// Whether processing captures a payment, the only step that pays fees and taxes
+fn is_charged(transaction: &Transaction) -> bool {
+    transaction.transaction_type == TransactionType::Payment
+        && transaction.status.next() == Some(TransactionStatus::Captured)
+}
+
+/// ProcessingSummary struct, counts and totals of the processed transactions
+///
+/// Totals are kept per currency, amounts in different currencies are never added up.
+/// Replays were counted when first processed and don't add to the totals.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProcessingSummary {
+    pub processed: usize,
+    pub rejected: usize,
+    pub replayed: usize,
+    totals_by_type: HashMap<(TransactionType, Currency), Money>,
+    totals_by_method: HashMap<(PaymentMethod, Currency), Money>,
+    fees: HashMap<Currency, Money>,
+    taxes: HashMap<Currency, Money>,
+    net: HashMap<Currency, Money>,
+}
+
+impl ProcessingSummary {
+    pub fn from_results(results: &[TransactionResult]) -> Result<Self, TransactionError> {
+        let mut summary = ProcessingSummary::default();
+        for result in results {
+            summary.add(result)?;
+        }
+        Ok(summary)
+    }
+
+    pub fn add(&mut self, result: &TransactionResult) -> Result<(), TransactionError> {
+        match result.outcome {
+            TransactionOutcome::Processed { .. } => self.processed += 1,
+            TransactionOutcome::Rejected(_) => {
+                self.rejected += 1;
+                return Ok(());
+            }
+            TransactionOutcome::Replayed(_) => {
+                self.replayed += 1;
+                return Ok(());
+            }
+        }
+
+        let currency = result.amount.currency;
+        let by_type = self
+            .totals_by_type
+            .entry((result.transaction_type.clone(), currency))
+            .or_insert(Money::zero(currency));
+        *by_type = by_type.checked_add(&result.amount)?;
+
+        let by_method = self
+            .totals_by_method
+            .entry((result.method.clone(), currency))
+            .or_insert(Money::zero(currency));
+        *by_method = by_method.checked_add(&result.amount)?;
+
+        if let Some(charges) = &result.charges {
+            for (totals, amount) in [
+                (&mut self.fees, charges.fee),
+                (&mut self.taxes, charges.tax),
+                (&mut self.net, charges.net),
+            ] {
+                let total = totals.entry(currency).or_insert(Money::zero(currency));
+                *total = total.checked_add(&amount)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn total(&self) -> usize {
+        self.processed + self.rejected + self.replayed
+    }
+
+    pub fn total_for_type(&self, transaction_type: &TransactionType, currency: Currency) -> Money {
+        self.totals_by_type
+            .get(&(transaction_type.clone(), currency))
+            .copied()
+            .unwrap_or(Money::zero(currency))
+    }
+
+    pub fn total_for_method(&self, method: &PaymentMethod, currency: Currency) -> Money {
+        self.totals_by_method
+            .get(&(method.clone(), currency))
+            .copied()
+            .unwrap_or(Money::zero(currency))
+    }
+
+    pub fn total_fees(&self, currency: Currency) -> Money {
+        self.fees
+            .get(&currency)
+            .copied()
+            .unwrap_or(Money::zero(currency))
+    }
+
+    pub fn total_taxes(&self, currency: Currency) -> Money {
+        self.taxes
+            .get(&currency)
+            .copied()
+            .unwrap_or(Money::zero(currency))
+    }
+
+    /// What's left of the processed amounts once fees and taxes are taken
+    pub fn total_net(&self, currency: Currency) -> Money {
+        self.net
+            .get(&currency)
+            .copied()
+            .unwrap_or(Money::zero(currency))
+    }
+}
+
+/// ProcessingReport struct, returned by `process_transactions`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessingReport {
+    pub results: Vec<TransactionResult>,
+    pub summary: ProcessingSummary,
+}
+
+/// Renders every result as the message a person would read
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+///
+/// let mut transactions = vec![
+///     Transaction::new(
+///         String::from("t1"),
+///         TransactionType::Refund,
+///         TransactionStatus::Pending,
+///         PaymentMethod::PayPal,
+///         Money::new(1000, Currency::USD),
+///     ),
+/// ];
+///
+/// let report = process_transactions(&mut transactions).unwrap();
+///
+/// assert_eq!(format_results(&report.results), vec!["Processing refund PayPal for amount: 10.00 USD".to_string()]);
+/// ```
+pub fn format_results(results: &[TransactionResult]) -> Vec<String> {
+    results.iter().map(|result| result.to_string()).collect()
+}
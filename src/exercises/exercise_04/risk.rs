@@ -0,0 +1,396 @@
+use super::money::Money;
+use super::{PaymentMethod, Transaction, TransactionError, TransactionStatus};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, VecDeque};
+
+/// The remembered attempts are swept whenever their keys reach this count,
+/// or twice what was left after the last sweep
+const MIN_KEYS_TO_SWEEP: usize = 64;
+
+/// RiskDecision enum, ordered from the most to the least permissive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RiskDecision {
+    Allow,
+    Review,
+    Deny,
+}
+
+impl std::fmt::Display for RiskDecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RiskDecision::Allow => write!(f, "allow"),
+            RiskDecision::Review => write!(f, "review"),
+            RiskDecision::Deny => write!(f, "deny"),
+        }
+    }
+}
+
+/// RiskScope enum, what groups transactions together for velocity and blocklists
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RiskScope {
+    /// The part of the id before the separator, `"cust42"` in `"cust42-t1"`
+    IdPrefix(char),
+    Method,
+}
+
+impl RiskScope {
+    pub fn key(&self, transaction: &Transaction) -> String {
+        match self {
+            RiskScope::IdPrefix(separator) => transaction
+                .id
+                .split(*separator)
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            RiskScope::Method => transaction.method.to_string(),
+        }
+    }
+}
+
+/// RiskRule enum
+#[derive(Debug, Clone, PartialEq)]
+pub enum RiskRule {
+    /// More than `max` transactions of the same scope within `window`
+    Velocity {
+        scope: RiskScope,
+        max: usize,
+        window: Duration,
+        decision: RiskDecision,
+    },
+    /// Amounts over `limit` paid with `method`
+    AmountThreshold {
+        method: PaymentMethod,
+        limit: Money,
+        decision: RiskDecision,
+    },
+    /// The same amount more than `max` times in the same scope within `window`
+    DuplicateAmountBurst {
+        scope: RiskScope,
+        max: usize,
+        window: Duration,
+        decision: RiskDecision,
+    },
+    /// Scope keys that are never let through
+    Blocklist {
+        scope: RiskScope,
+        entries: Vec<String>,
+    },
+}
+
+impl RiskRule {
+    /// The scope and window of the rules that look back at earlier attempts
+    fn lookback(&self) -> Option<(&RiskScope, Duration)> {
+        match self {
+            RiskRule::Velocity { scope, window, .. } => Some((scope, *window)),
+            RiskRule::DuplicateAmountBurst { scope, window, .. } => Some((scope, *window)),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RiskRule::Velocity { .. } => "velocity",
+            RiskRule::AmountThreshold { .. } => "amount threshold",
+            RiskRule::DuplicateAmountBurst { .. } => "duplicate amount burst",
+            RiskRule::Blocklist { .. } => "blocklist",
+        }
+    }
+
+    fn evaluate(
+        &self,
+        transaction: &Transaction,
+        history: &History,
+        now: DateTime<Utc>,
+    ) -> Option<TriggeredRule> {
+        let triggered = |decision: RiskDecision, reason: String| {
+            Some(TriggeredRule {
+                rule: self.name(),
+                decision,
+                reason,
+            })
+        };
+
+        match self {
+            RiskRule::Velocity {
+                scope,
+                max,
+                window,
+                decision,
+            } => {
+                let key = scope.key(transaction);
+                let seen = recent(history, scope, &key, now - *window).count();
+                match seen + 1 > *max {
+                    true => triggered(
+                        *decision,
+                        format!(
+                            "{} transactions for {} within {} minutes",
+                            seen + 1,
+                            key,
+                            window.num_minutes()
+                        ),
+                    ),
+                    false => None,
+                }
+            }
+            RiskRule::AmountThreshold {
+                method,
+                limit,
+                decision,
+            } => {
+                let over = transaction.method == *method
+                    && transaction.currency() == limit.currency
+                    && transaction.amount.minor > limit.minor;
+                match over {
+                    true => triggered(
+                        *decision,
+                        format!(
+                            "{} is over the {} limit of {}",
+                            transaction.amount, method, limit
+                        ),
+                    ),
+                    false => None,
+                }
+            }
+            RiskRule::DuplicateAmountBurst {
+                scope,
+                max,
+                window,
+                decision,
+            } => {
+                let key = scope.key(transaction);
+                let seen = recent(history, scope, &key, now - *window)
+                    .filter(|attempt| attempt.amount == transaction.amount)
+                    .count();
+                match seen + 1 > *max {
+                    true => triggered(
+                        *decision,
+                        format!(
+                            "{} charged {} times for {}",
+                            transaction.amount,
+                            seen + 1,
+                            key
+                        ),
+                    ),
+                    false => None,
+                }
+            }
+            RiskRule::Blocklist { scope, entries } => {
+                let key = scope.key(transaction);
+                match entries.contains(&key) {
+                    true => triggered(RiskDecision::Deny, format!("{} is blocklisted", key)),
+                    false => None,
+                }
+            }
+        }
+    }
+}
+
+/// TriggeredRule struct
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriggeredRule {
+    pub rule: &'static str,
+    pub decision: RiskDecision,
+    pub reason: String,
+}
+
+/// RiskAssessment struct, the strictest decision of the triggered rules
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskAssessment {
+    pub decision: RiskDecision,
+    pub triggered: Vec<TriggeredRule>,
+}
+
+impl RiskAssessment {
+    pub fn rule_names(&self) -> Vec<String> {
+        self.triggered
+            .iter()
+            .map(|triggered| triggered.rule.to_string())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Attempt {
+    amount: Money,
+    at: DateTime<Utc>,
+}
+
+/// Attempts per scope and key, oldest first
+type History = HashMap<(RiskScope, String), VecDeque<Attempt>>;
+
+fn recent<'a>(
+    history: &'a History,
+    scope: &RiskScope,
+    key: &str,
+    since: DateTime<Utc>,
+) -> impl Iterator<Item = &'a Attempt> {
+    history
+        .get(&(scope.clone(), key.to_string()))
+        .into_iter()
+        .flatten()
+        .filter(move |attempt| attempt.at > since)
+}
+
+/// Drops the attempts that are out of every window of their scope
+fn evict(attempts: &mut VecDeque<Attempt>, window: Duration, now: DateTime<Utc>) {
+    while attempts
+        .front()
+        .is_some_and(|attempt| attempt.at <= now - window)
+    {
+        attempts.pop_front();
+    }
+}
+
+/// RiskEngine struct, screens transactions before they are processed
+///
+/// Every screened transaction counts towards the velocity and burst rules,
+/// denied ones included. Attempts are remembered per scope key, and only for
+/// as long as the largest window of their scope.
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_04::*;
+/// use chrono::{Duration, Utc};
+///
+/// let mut engine = RiskEngine::new(vec![
+///     RiskRule::Velocity {
+///         scope: RiskScope::IdPrefix('-'),
+///         max: 2,
+///         window: Duration::minutes(10),
+///         decision: RiskDecision::Review,
+///     },
+///     RiskRule::Blocklist { scope: RiskScope::IdPrefix('-'), entries: vec![String::from("mallory")] },
+/// ]);
+/// let now = Utc::now();
+/// let payment = |id: &str| Transaction::new(
+///    String::from(id),
+///    TransactionType::Payment,
+///    TransactionStatus::Pending,
+///    PaymentMethod::CreditCard,
+///    Money::new(2399, Currency::USD),
+/// );
+///
+/// assert_eq!(engine.assess(&payment("alice-1"), now).decision, RiskDecision::Allow);
+/// assert_eq!(engine.assess(&payment("alice-2"), now).decision, RiskDecision::Allow);
+/// assert_eq!(engine.assess(&payment("alice-3"), now).decision, RiskDecision::Review);
+///
+/// let mut blocked = payment("mallory-1");
+/// assert_eq!(
+///     engine.process(&mut blocked, now),
+///     Err(TransactionError::RiskDenied { id: String::from("mallory-1"), rules: vec![String::from("blocklist")] })
+/// );
+/// assert_eq!(blocked.status(), &TransactionStatus::Pending);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RiskEngine {
+    rules: Vec<RiskRule>,
+    history: History,
+    next_sweep: usize,
+}
+
+impl RiskEngine {
+    pub fn new(rules: Vec<RiskRule>) -> Self {
+        RiskEngine {
+            rules,
+            ..RiskEngine::default()
+        }
+    }
+
+    /// How many attempts are still remembered
+    pub fn remembered(&self) -> usize {
+        self.history.values().map(VecDeque::len).sum()
+    }
+
+    /// The largest window of every scope the rules look back at
+    fn windows(&self) -> HashMap<RiskScope, Duration> {
+        let mut windows: HashMap<RiskScope, Duration> = HashMap::new();
+        for (scope, window) in self.rules.iter().filter_map(RiskRule::lookback) {
+            let largest = windows.entry(scope.clone()).or_insert(window);
+            *largest = (*largest).max(window);
+        }
+        windows
+    }
+
+    fn remember(&mut self, transaction: &Transaction, now: DateTime<Utc>) {
+        let windows = self.windows();
+        for (scope, window) in &windows {
+            let key = (scope.clone(), scope.key(transaction));
+            let attempts = self.history.entry(key).or_default();
+            attempts.push_back(Attempt {
+                amount: transaction.amount,
+                at: now,
+            });
+            evict(attempts, *window, now);
+        }
+
+        if self.history.len() >= self.next_sweep.max(MIN_KEYS_TO_SWEEP) {
+            self.history.retain(|(scope, _), attempts| {
+                if let Some(window) = windows.get(scope) {
+                    evict(attempts, *window, now);
+                }
+                !attempts.is_empty()
+            });
+            self.next_sweep = self.history.len() * 2;
+        }
+    }
+
+    pub fn add_rule(&mut self, rule: RiskRule) {
+        self.rules.push(rule);
+    }
+
+    /// Evaluates every rule and remembers the transaction
+    pub fn assess(&mut self, transaction: &Transaction, now: DateTime<Utc>) -> RiskAssessment {
+        let triggered: Vec<TriggeredRule> = self
+            .rules
+            .iter()
+            .filter_map(|rule| rule.evaluate(transaction, &self.history, now))
+            .collect();
+        let decision = triggered
+            .iter()
+            .map(|triggered| triggered.decision)
+            .max()
+            .unwrap_or(RiskDecision::Allow);
+
+        self.remember(transaction, now);
+        RiskAssessment {
+            decision,
+            triggered,
+        }
+    }
+
+    /// Assesses the transaction, denials become an error
+    pub fn screen(
+        &mut self,
+        transaction: &Transaction,
+        now: DateTime<Utc>,
+    ) -> Result<RiskAssessment, TransactionError> {
+        let assessment = self.assess(transaction, now);
+        match assessment.decision {
+            RiskDecision::Deny => Err(TransactionError::RiskDenied {
+                id: transaction.id.clone(),
+                rules: assessment.rule_names(),
+            }),
+            _ => Ok(assessment),
+        }
+    }
+
+    /// Screens the transaction and processes it unless it's denied
+    ///
+    /// Only pending transactions are screened, so a payment taken through
+    /// every step counts as one attempt and later steps have no assessment.
+    /// Transactions sent to review are processed too, the assessment tells
+    /// which ones need a look.
+    pub fn process(
+        &mut self,
+        transaction: &mut Transaction,
+        now: DateTime<Utc>,
+    ) -> Result<(Option<RiskAssessment>, String), TransactionError> {
+        let assessment = match transaction.status() {
+            TransactionStatus::Pending => Some(self.screen(transaction, now)?),
+            _ => None,
+        };
+        let message = transaction.process()?;
+        Ok((assessment, message))
+    }
+}
@@ -1,4 +1,10 @@
 pub mod enums;
 pub use enums::*;
+pub mod pricing;
+pub use pricing::*;
 pub mod structs;
 pub use structs::*;
+pub mod tracking;
+pub use tracking::*;
+pub mod tracking_number;
+pub use tracking_number::*;
@@ -11,7 +11,7 @@ use crate::exercises::exercise_05::enums::delivery_action::DeliveryAction;
 ///
 /// assert_eq!(delivery_type, DeliveryType::Express);
 /// ```
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum DeliveryType {
     Express,
     Insured,
@@ -63,3 +63,13 @@ impl DeliveryType {
         }
     }
 }
+
+impl std::fmt::Display for DeliveryType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DeliveryType::Express => write!(f, "express"),
+            DeliveryType::Insured => write!(f, "insured"),
+            DeliveryType::Standard => write!(f, "standard"),
+        }
+    }
+}
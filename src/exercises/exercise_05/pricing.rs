@@ -0,0 +1,368 @@
+use crate::error::ErrorCode;
+use crate::exercises::exercise_01::Rectangle;
+use crate::exercises::exercise_04::{Currency, Money, MoneyError, RoundingMode};
+use crate::exercises::exercise_05::DeliveryType;
+use std::collections::HashMap;
+
+/// Zone enum, how far a package travels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Zone {
+    /// Up to 50 km
+    Local,
+    /// Up to 300 km
+    Regional,
+    /// Up to 1000 km
+    National,
+    International,
+}
+
+impl Zone {
+    pub fn for_distance(kilometers: f64) -> Zone {
+        match kilometers {
+            d if d <= 50.0 => Zone::Local,
+            d if d <= 300.0 => Zone::Regional,
+            d if d <= 1000.0 => Zone::National,
+            _ => Zone::International,
+        }
+    }
+}
+
+impl std::fmt::Display for Zone {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Zone::Local => write!(f, "local"),
+            Zone::Regional => write!(f, "regional"),
+            Zone::National => write!(f, "national"),
+            Zone::International => write!(f, "international"),
+        }
+    }
+}
+
+/// PricingError enum
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_05::*;
+///
+/// let error = PricingError::NoRate { delivery_type: DeliveryType::Express, zone: Zone::International };
+///
+/// assert_eq!(format!("{}", error), "There is no express rate for the international zone");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum PricingError {
+    InvalidDimensions,
+    InvalidWeight,
+    Overweight {
+        weight_kg: f64,
+        max_kg: f64,
+    },
+    NoRate {
+        delivery_type: DeliveryType,
+        zone: Zone,
+    },
+    MissingDeclaredValue,
+    Amount(MoneyError),
+}
+
+impl std::fmt::Display for PricingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PricingError::InvalidDimensions => write!(f, "Package dimensions must be positive"),
+            PricingError::InvalidWeight => write!(f, "Package weight must be positive"),
+            PricingError::Overweight { weight_kg, max_kg } => write!(
+                f,
+                "Package weighs {} kg, the most we ship is {} kg",
+                weight_kg, max_kg
+            ),
+            PricingError::NoRate {
+                delivery_type,
+                zone,
+            } => write!(
+                f,
+                "There is no {} rate for the {} zone",
+                delivery_type, zone
+            ),
+            PricingError::MissingDeclaredValue => {
+                write!(f, "Insured deliveries need a declared value")
+            }
+            PricingError::Amount(error) => write!(f, "Invalid shipping amount: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for PricingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PricingError::Amount(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl ErrorCode for PricingError {
+    fn code(&self) -> &'static str {
+        match self {
+            PricingError::InvalidDimensions => "pricing.invalid_dimensions",
+            PricingError::InvalidWeight => "pricing.invalid_weight",
+            PricingError::Overweight { .. } => "pricing.overweight",
+            PricingError::NoRate { .. } => "pricing.no_rate",
+            PricingError::MissingDeclaredValue => "pricing.missing_declared_value",
+            PricingError::Amount(_) => "pricing.amount",
+        }
+    }
+}
+
+impl From<MoneyError> for PricingError {
+    fn from(error: MoneyError) -> Self {
+        PricingError::Amount(error)
+    }
+}
+
+/// Package struct, a box with its footprint in centimeters and weight in kilograms
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_01::{Point, Rectangle};
+/// use clean_code_notes_exercises::exercises::exercise_05::Package;
+///
+/// let package = Package::new(Rectangle::new(Point::new(0.0, 0.0), 40.0, 30.0), 20.0, 2.0);
+///
+/// assert_eq!(package.volume(), 24000.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub footprint: Rectangle,
+    pub height: f64,
+    pub weight_kg: f64,
+    /// What the content is worth, insured deliveries pay a premium on it
+    pub declared_value: Option<Money>,
+}
+
+impl Package {
+    pub fn new(footprint: Rectangle, height: f64, weight_kg: f64) -> Self {
+        Package {
+            footprint,
+            height,
+            weight_kg,
+            declared_value: None,
+        }
+    }
+
+    pub fn with_declared_value(mut self, declared_value: Money) -> Self {
+        self.declared_value = Some(declared_value);
+        self
+    }
+
+    /// Volume in cubic centimeters
+    pub fn volume(&self) -> f64 {
+        self.footprint.get_area() * self.height
+    }
+
+    fn validate(&self) -> Result<(), PricingError> {
+        let positive = |value: f64| value.is_finite() && value > 0.0;
+        if !positive(self.footprint.width) || !positive(self.footprint.height) {
+            return Err(PricingError::InvalidDimensions);
+        }
+        if !positive(self.height) {
+            return Err(PricingError::InvalidDimensions);
+        }
+        if !positive(self.weight_kg) {
+            return Err(PricingError::InvalidWeight);
+        }
+        Ok(())
+    }
+}
+
+/// Rate struct, a fixed price plus a price per billable kilogram
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate {
+    pub base: Money,
+    pub per_kg: Money,
+}
+
+/// RateTable struct, the rates per delivery type and zone
+///
+/// Light but bulky packages are billed by their volumetric weight, the
+/// volume in cubic centimeters divided by `volumetric_divisor`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateTable {
+    pub currency: Currency,
+    pub volumetric_divisor: f64,
+    pub max_weight_kg: f64,
+    /// Insurance premium on the declared value in basis points, 150 is 1.5%
+    pub insurance_basis_points: u32,
+    pub minimum_insurance: Money,
+    rates: HashMap<(DeliveryType, Zone), Rate>,
+}
+
+impl RateTable {
+    pub fn new(currency: Currency) -> Self {
+        RateTable {
+            currency,
+            volumetric_divisor: 5000.0,
+            max_weight_kg: 30.0,
+            insurance_basis_points: 100,
+            minimum_insurance: Money::zero(currency),
+            rates: HashMap::new(),
+        }
+    }
+
+    /// Sets the rate of a delivery type in a zone, in the currency of the table
+    pub fn with_rate(
+        mut self,
+        delivery_type: DeliveryType,
+        zone: Zone,
+        base: Money,
+        per_kg: Money,
+    ) -> Result<Self, PricingError> {
+        for amount in [base, per_kg] {
+            if amount.currency != self.currency {
+                return Err(MoneyError::CurrencyMismatch(self.currency, amount.currency).into());
+            }
+        }
+        self.rates
+            .insert((delivery_type, zone), Rate { base, per_kg });
+        Ok(self)
+    }
+
+    pub fn with_insurance(mut self, basis_points: u32, minimum: Money) -> Self {
+        self.insurance_basis_points = basis_points;
+        self.minimum_insurance = minimum;
+        self
+    }
+
+    pub fn with_volumetric_divisor(mut self, divisor: f64) -> Self {
+        self.volumetric_divisor = divisor;
+        self
+    }
+
+    pub fn with_max_weight(mut self, max_weight_kg: f64) -> Self {
+        self.max_weight_kg = max_weight_kg;
+        self
+    }
+
+    pub fn rate(&self, delivery_type: &DeliveryType, zone: Zone) -> Option<&Rate> {
+        self.rates.get(&(delivery_type.clone(), zone))
+    }
+}
+
+/// ShippingQuote struct, what a delivery costs and why
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShippingQuote {
+    pub delivery_type: DeliveryType,
+    pub zone: Zone,
+    /// The actual or the volumetric weight, whichever is higher, in whole grams
+    pub billable_grams: i64,
+    pub base: Money,
+    pub weight_charge: Money,
+    pub insurance: Money,
+    pub total: Money,
+}
+
+/// PricingEngine struct, prices deliveries with a rate table
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_01::{Point, Rectangle};
+/// use clean_code_notes_exercises::exercises::exercise_04::{Currency, Money};
+/// use clean_code_notes_exercises::exercises::exercise_05::*;
+///
+/// let euros = |minor| Money::new(minor, Currency::EUR);
+/// let rates = RateTable::new(Currency::EUR)
+///     .with_rate(DeliveryType::Standard, Zone::Regional, euros(450), euros(120))
+///     .unwrap()
+///     .with_rate(DeliveryType::Insured, Zone::Regional, euros(650), euros(120))
+///     .unwrap()
+///     .with_insurance(150, euros(200));
+/// let engine = PricingEngine::new(rates);
+/// let package = Package::new(Rectangle::new(Point::new(0.0, 0.0), 40.0, 30.0), 20.0, 2.0);
+///
+/// // 24000 cm³ weigh 4.8 kg by volume, more than the actual 2 kg
+/// let quote = engine.quote(&package, Zone::for_distance(120.0), &DeliveryType::Standard).unwrap();
+/// assert_eq!(quote.billable_grams, 4800);
+/// assert_eq!(quote.total, Money::new(450 + 576, Currency::EUR));
+///
+/// let insured = package.with_declared_value(Money::new(50000, Currency::EUR));
+/// let quote = engine.quote(&insured, Zone::Regional, &DeliveryType::Insured).unwrap();
+/// assert_eq!(quote.insurance, Money::new(750, Currency::EUR));
+/// assert_eq!(quote.total, Money::new(650 + 576 + 750, Currency::EUR));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PricingEngine {
+    pub rates: RateTable,
+}
+
+impl PricingEngine {
+    pub fn new(rates: RateTable) -> Self {
+        PricingEngine { rates }
+    }
+
+    pub fn quote(
+        &self,
+        package: &Package,
+        zone: Zone,
+        delivery_type: &DeliveryType,
+    ) -> Result<ShippingQuote, PricingError> {
+        package.validate()?;
+        if package.weight_kg > self.rates.max_weight_kg {
+            return Err(PricingError::Overweight {
+                weight_kg: package.weight_kg,
+                max_kg: self.rates.max_weight_kg,
+            });
+        }
+        let rate = self
+            .rates
+            .rate(delivery_type, zone)
+            .ok_or(PricingError::NoRate {
+                delivery_type: delivery_type.clone(),
+                zone,
+            })?;
+
+        let volumetric_kg = package.volume() / self.rates.volumetric_divisor;
+        let billable_grams = (package.weight_kg.max(volumetric_kg) * 1000.0).ceil() as i64;
+        let weight_charge = rate
+            .per_kg
+            .mul_ratio(billable_grams, 1000, RoundingMode::Up)?;
+        let insurance = match delivery_type {
+            DeliveryType::Insured => self.insurance(package)?,
+            _ => Money::zero(self.rates.currency),
+        };
+        let total = rate
+            .base
+            .checked_add(&weight_charge)?
+            .checked_add(&insurance)?;
+
+        Ok(ShippingQuote {
+            delivery_type: delivery_type.clone(),
+            zone,
+            billable_grams,
+            base: rate.base,
+            weight_charge,
+            insurance,
+            total,
+        })
+    }
+
+    fn insurance(&self, package: &Package) -> Result<Money, PricingError> {
+        let declared_value = package
+            .declared_value
+            .ok_or(PricingError::MissingDeclaredValue)?;
+        if declared_value.currency != self.rates.currency {
+            return Err(
+                MoneyError::CurrencyMismatch(self.rates.currency, declared_value.currency).into(),
+            );
+        }
+        let premium = declared_value.mul_ratio(
+            self.rates.insurance_basis_points as i64,
+            10_000,
+            RoundingMode::Up,
+        )?;
+        Ok(match premium.minor < self.rates.minimum_insurance.minor {
+            true => self.rates.minimum_insurance,
+            false => premium,
+        })
+    }
+}
@@ -1,10 +1,61 @@
+use crate::exercises::exercise_05::{Shipment, TrackingNumberError, TrackingNumberGenerator};
 use crate::exercises::{delivery_type::DeliveryType, DeliveryAction};
+use chrono::{DateTime, Utc};
 
 pub trait DeliveryTrait {
     fn deliver_product(&self) -> String;
+    /// The tracking message of the delivery, followed by where its shipment
+    /// is when it has one
     fn track_product(&self) -> String;
 }
 
+/// Deliveries that can be shipped and tracked through a `Shipment`
+///
+/// Kept apart from `DeliveryTrait` so its implementors don't have to
+/// provide the delivery type and product.
+pub trait Shippable: DeliveryTrait {
+    fn delivery_type(&self) -> DeliveryType;
+    fn product(&self) -> &str;
+
+    /// Creates the label of a shipment for the product
+    fn ship_product(&self, tracking_number: &str, now: DateTime<Utc>) -> Shipment {
+        Shipment::new(tracking_number, self.product(), self.delivery_type(), now)
+    }
+
+    /// Creates the label of a shipment with the next tracking number of `generator`
+    fn ship_with(
+        &self,
+        generator: &mut TrackingNumberGenerator,
+        now: DateTime<Utc>,
+    ) -> Result<Shipment, TrackingNumberError> {
+        let tracking_number = generator.next_for(&self.delivery_type())?;
+        Ok(self.ship_product(&tracking_number.to_string(), now))
+    }
+
+    /// The tracking message followed by where the shipment is
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clean_code_notes_exercises::exercises::delivery_structs::{Delivery, DeliveryExpress, Shippable};
+    /// use chrono::{TimeZone, Utc};
+    ///
+    /// let delivery = DeliveryExpress { base: Delivery::new("A product".to_string()) };
+    /// let shipment = delivery.ship_product("TRK1", Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap());
+    ///
+    /// assert_eq!(
+    ///     delivery.track_shipment(&shipment),
+    ///     "Tracking express delivery for A product: TRK1: label created, expected on 2024-03-04"
+    /// );
+    /// ```
+    fn track_shipment(&self, shipment: &Shipment) -> String {
+        let message = self
+            .delivery_type()
+            .get_action_message(DeliveryAction::Track, self.product().to_string());
+        format!("{}: {}", message, shipment.summary())
+    }
+}
+
 /// A delivery struct.
 ///
 /// # Example
@@ -14,15 +65,54 @@ pub trait DeliveryTrait {
 ///
 /// let delivery = Delivery::new("A product".to_string());
 /// assert_eq!(delivery.product, "A product");
+/// assert_eq!(delivery.shipment, None);
 /// ```
 #[derive(Debug)]
 pub struct Delivery {
     pub product: String,
+    /// The shipment once the product has been shipped
+    pub shipment: Option<Shipment>,
 }
 
 impl Delivery {
     pub fn new(product: String) -> Self {
-        Self { product }
+        Self {
+            product,
+            shipment: None,
+        }
+    }
+
+    pub fn with_shipment(mut self, shipment: Shipment) -> Self {
+        self.shipment = Some(shipment);
+        self
+    }
+
+    /// The tracking message of `delivery_type`, followed by where the shipment
+    /// is when there is one
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clean_code_notes_exercises::exercises::delivery_structs::{Delivery, DeliveryStandard, DeliveryTrait, Shippable};
+    /// use chrono::{TimeZone, Utc};
+    ///
+    /// let mut delivery = DeliveryStandard { base: Delivery::new("A product".to_string()) };
+    /// assert_eq!(delivery.track_product(), "Tracking standard delivery for A product");
+    ///
+    /// let shipment = delivery.ship_product("TRK1", Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap());
+    /// delivery.base.shipment = Some(shipment);
+    /// assert_eq!(
+    ///     delivery.track_product(),
+    ///     "Tracking standard delivery for A product: TRK1: label created, expected on 2024-03-08"
+    /// );
+    /// ```
+    pub fn track(&self, delivery_type: DeliveryType) -> String {
+        let message =
+            delivery_type.get_action_message(DeliveryAction::Track, self.product.to_string());
+        match &self.shipment {
+            Some(shipment) => format!("{}: {}", message, shipment.summary()),
+            None => message,
+        }
     }
 }
 
@@ -51,8 +141,17 @@ impl DeliveryTrait for DeliveryExpress {
     }
 
     fn track_product(&self) -> String {
+        self.base.track(DeliveryType::Express)
+    }
+}
+
+impl Shippable for DeliveryExpress {
+    fn delivery_type(&self) -> DeliveryType {
         DeliveryType::Express
-            .get_action_message(DeliveryAction::Track, self.base.product.to_string())
+    }
+
+    fn product(&self) -> &str {
+        &self.base.product
     }
 }
 
@@ -82,8 +181,17 @@ impl DeliveryTrait for DeliveryInsured {
     }
 
     fn track_product(&self) -> String {
+        self.base.track(DeliveryType::Insured)
+    }
+}
+
+impl Shippable for DeliveryInsured {
+    fn delivery_type(&self) -> DeliveryType {
         DeliveryType::Insured
-            .get_action_message(DeliveryAction::Track, self.base.product.to_string())
+    }
+
+    fn product(&self) -> &str {
+        &self.base.product
     }
 }
 
@@ -113,7 +221,16 @@ impl DeliveryTrait for DeliveryStandard {
     }
 
     fn track_product(&self) -> String {
+        self.base.track(DeliveryType::Standard)
+    }
+}
+
+impl Shippable for DeliveryStandard {
+    fn delivery_type(&self) -> DeliveryType {
         DeliveryType::Standard
-            .get_action_message(DeliveryAction::Track, self.base.product.to_string())
+    }
+
+    fn product(&self) -> &str {
+        &self.base.product
     }
 }
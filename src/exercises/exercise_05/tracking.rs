@@ -0,0 +1,270 @@
+use crate::error::ErrorCode;
+use crate::exercises::exercise_05::DeliveryType;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc, Weekday};
+
+/// TrackingStatus enum, the steps a shipment goes through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrackingStatus {
+    LabelCreated,
+    PickedUp,
+    InTransit,
+    OutForDelivery,
+    Delivered,
+    /// Something went wrong on the way, like a failed delivery attempt or damage
+    Exception,
+}
+
+impl std::fmt::Display for TrackingStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TrackingStatus::LabelCreated => write!(f, "label created"),
+            TrackingStatus::PickedUp => write!(f, "picked up"),
+            TrackingStatus::InTransit => write!(f, "in transit"),
+            TrackingStatus::OutForDelivery => write!(f, "out for delivery"),
+            TrackingStatus::Delivered => write!(f, "delivered"),
+            TrackingStatus::Exception => write!(f, "exception"),
+        }
+    }
+}
+
+/// TrackingEvent struct, one scan of a shipment
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackingEvent {
+    pub status: TrackingStatus,
+    pub at: DateTime<Utc>,
+    pub location: Option<String>,
+    pub note: String,
+}
+
+impl TrackingEvent {
+    pub fn new(status: TrackingStatus, at: DateTime<Utc>) -> Self {
+        TrackingEvent {
+            status,
+            at,
+            location: None,
+            note: String::new(),
+        }
+    }
+
+    pub fn with_location(mut self, location: &str) -> Self {
+        self.location = Some(location.to_string());
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.note = note.to_string();
+        self
+    }
+}
+
+/// TrackingError enum
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrackingError {
+    /// The event happened before the label was created
+    BeforeLabelCreated {
+        at: DateTime<Utc>,
+        created_at: DateTime<Utc>,
+    },
+    /// The shipment was delivered before the event happened
+    AlreadyDelivered { delivered_at: DateTime<Utc> },
+    /// Only the shipment itself creates the label
+    DuplicateLabel,
+}
+
+impl std::fmt::Display for TrackingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TrackingError::BeforeLabelCreated { at, created_at } => write!(
+                f,
+                "Event at {} is before the label was created at {}",
+                at, created_at
+            ),
+            TrackingError::AlreadyDelivered { delivered_at } => {
+                write!(f, "Shipment was already delivered at {}", delivered_at)
+            }
+            TrackingError::DuplicateLabel => write!(f, "Shipment already has a label"),
+        }
+    }
+}
+
+impl std::error::Error for TrackingError {}
+
+impl ErrorCode for TrackingError {
+    fn code(&self) -> &'static str {
+        match self {
+            TrackingError::BeforeLabelCreated { .. } => "tracking.before_label_created",
+            TrackingError::AlreadyDelivered { .. } => "tracking.already_delivered",
+            TrackingError::DuplicateLabel => "tracking.duplicate_label",
+        }
+    }
+}
+
+impl DeliveryType {
+    /// Business days from pick up to delivery
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use clean_code_notes_exercises::exercises::delivery_type::DeliveryType;
+    ///
+    /// assert_eq!(DeliveryType::Express.transit_days(), 1);
+    /// assert_eq!(DeliveryType::Standard.transit_days(), 5);
+    /// ```
+    pub fn transit_days(&self) -> u32 {
+        match self {
+            DeliveryType::Express => 1,
+            DeliveryType::Insured => 3,
+            DeliveryType::Standard => 5,
+        }
+    }
+}
+
+/// Adds business days to a date, weekends are skipped
+pub fn add_business_days(date: NaiveDate, days: u32) -> NaiveDate {
+    let mut date = date;
+    let mut remaining = days;
+    while remaining > 0 {
+        date += Duration::days(1);
+        if !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) {
+            remaining -= 1;
+        }
+    }
+    date
+}
+
+/// Shipment struct, a delivery with its tracking number and event timeline
+///
+/// Events are kept in the order they happened, carriers may report them late.
+/// The status is the one of the latest event.
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_05::*;
+/// use chrono::{NaiveDate, TimeZone, Utc};
+///
+/// // Friday
+/// let created_at = Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap();
+/// let mut shipment = Shipment::new("TRK1", "A product", DeliveryType::Standard, created_at);
+///
+/// assert_eq!(shipment.status(), TrackingStatus::LabelCreated);
+/// assert_eq!(shipment.estimated_delivery(), NaiveDate::from_ymd_opt(2024, 3, 8).unwrap());
+///
+/// let picked_up_at = Utc.with_ymd_and_hms(2024, 3, 4, 15, 0, 0).unwrap();
+/// shipment.record(TrackingEvent::new(TrackingStatus::PickedUp, picked_up_at)).unwrap();
+///
+/// assert_eq!(shipment.status(), TrackingStatus::PickedUp);
+/// assert_eq!(shipment.estimated_delivery(), NaiveDate::from_ymd_opt(2024, 3, 11).unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shipment {
+    pub tracking_number: String,
+    pub product: String,
+    pub delivery_type: DeliveryType,
+    events: Vec<TrackingEvent>,
+}
+
+impl Shipment {
+    pub fn new(
+        tracking_number: &str,
+        product: &str,
+        delivery_type: DeliveryType,
+        created_at: DateTime<Utc>,
+    ) -> Self {
+        Shipment {
+            tracking_number: tracking_number.to_string(),
+            product: product.to_string(),
+            delivery_type,
+            events: vec![TrackingEvent::new(TrackingStatus::LabelCreated, created_at)],
+        }
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.events[0].at
+    }
+
+    /// Every event, oldest first
+    pub fn events(&self) -> &[TrackingEvent] {
+        &self.events
+    }
+
+    pub fn latest_event(&self) -> &TrackingEvent {
+        &self.events[self.events.len() - 1]
+    }
+
+    pub fn status(&self) -> TrackingStatus {
+        self.latest_event().status
+    }
+
+    pub fn delivered_at(&self) -> Option<DateTime<Utc>> {
+        self.events
+            .iter()
+            .find(|event| event.status == TrackingStatus::Delivered)
+            .map(|event| event.at)
+    }
+
+    pub fn is_delivered(&self) -> bool {
+        self.delivered_at().is_some()
+    }
+
+    /// Adds an event in the timeline where it happened
+    pub fn record(&mut self, event: TrackingEvent) -> Result<(), TrackingError> {
+        if event.status == TrackingStatus::LabelCreated {
+            return Err(TrackingError::DuplicateLabel);
+        }
+        if event.at < self.created_at() {
+            return Err(TrackingError::BeforeLabelCreated {
+                at: event.at,
+                created_at: self.created_at(),
+            });
+        }
+        if let Some(delivered_at) = self.delivered_at() {
+            if event.at >= delivered_at || event.status == TrackingStatus::Delivered {
+                return Err(TrackingError::AlreadyDelivered { delivered_at });
+            }
+        }
+
+        let position = self.events.partition_point(|other| other.at <= event.at);
+        self.events.insert(position, event);
+        Ok(())
+    }
+
+    /// The delivery date, or the day it's expected: the transit days of the
+    /// delivery type counted from the pick up, or from the label if the
+    /// shipment wasn't picked up yet
+    pub fn estimated_delivery(&self) -> NaiveDate {
+        if let Some(delivered_at) = self.delivered_at() {
+            return delivered_at.date_naive();
+        }
+        let start = self
+            .events
+            .iter()
+            .find(|event| event.status == TrackingStatus::PickedUp)
+            .unwrap_or(&self.events[0]);
+        add_business_days(start.at.date_naive(), self.delivery_type.transit_days())
+    }
+
+    /// A one line summary for customers
+    pub fn summary(&self) -> String {
+        let latest = self.latest_event();
+        let location = match &latest.location {
+            Some(location) => format!(" in {}", location),
+            None => String::new(),
+        };
+        match self.delivered_at() {
+            Some(delivered_at) => format!(
+                "{}: delivered on {}{}",
+                self.tracking_number,
+                delivered_at.date_naive(),
+                location
+            ),
+            None => format!(
+                "{}: {}{}, expected on {}",
+                self.tracking_number,
+                latest.status,
+                location,
+                self.estimated_delivery()
+            ),
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use crate::error::ErrorCode;
+use crate::exercises::exercise_05::DeliveryType;
+use std::str::FromStr;
+
+/// Weights of the serial digits in the mod-11 check digit
+const CHECK_WEIGHTS: [u32; 8] = [8, 6, 4, 2, 3, 5, 9, 7];
+
+/// Serials have eight digits
+pub const MAX_SERIAL: u64 = 99_999_999;
+
+impl DeliveryType {
+    /// The two letters tracking numbers of the delivery type start with
+    pub fn tracking_prefix(&self) -> &'static str {
+        match self {
+            DeliveryType::Express => "EX",
+            DeliveryType::Insured => "IN",
+            DeliveryType::Standard => "ST",
+        }
+    }
+
+    pub fn from_tracking_prefix(prefix: &str) -> Option<DeliveryType> {
+        match prefix {
+            "EX" => Some(DeliveryType::Express),
+            "IN" => Some(DeliveryType::Insured),
+            "ST" => Some(DeliveryType::Standard),
+            _ => None,
+        }
+    }
+}
+
+/// TrackingNumberError enum
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrackingNumberError {
+    InvalidLength(usize),
+    UnknownPrefix(String),
+    InvalidSerial(String),
+    SerialTooLarge(u64),
+    InvalidCheckDigit { expected: u32, found: u32 },
+}
+
+impl std::fmt::Display for TrackingNumberError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TrackingNumberError::InvalidLength(length) => write!(
+                f,
+                "Tracking numbers have {} characters, not {}",
+                TrackingNumber::LENGTH,
+                length
+            ),
+            TrackingNumberError::UnknownPrefix(prefix) => {
+                write!(f, "Unknown tracking number prefix: {}", prefix)
+            }
+            TrackingNumberError::InvalidSerial(serial) => {
+                write!(f, "Invalid tracking number serial: {}", serial)
+            }
+            TrackingNumberError::SerialTooLarge(serial) => {
+                write!(f, "Serial {} is larger than {}", serial, MAX_SERIAL)
+            }
+            TrackingNumberError::InvalidCheckDigit { expected, found } => write!(
+                f,
+                "Invalid check digit: expected {}, found {}",
+                expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TrackingNumberError {}
+
+impl ErrorCode for TrackingNumberError {
+    fn code(&self) -> &'static str {
+        match self {
+            TrackingNumberError::InvalidLength(_) => "tracking_number.invalid_length",
+            TrackingNumberError::UnknownPrefix(_) => "tracking_number.unknown_prefix",
+            TrackingNumberError::InvalidSerial(_) => "tracking_number.invalid_serial",
+            TrackingNumberError::SerialTooLarge(_) => "tracking_number.serial_too_large",
+            TrackingNumberError::InvalidCheckDigit { .. } => "tracking_number.invalid_check_digit",
+        }
+    }
+}
+
+/// TrackingNumber struct
+///
+/// Written as the prefix of the delivery type, an eight digit serial and a
+/// mod-11 check digit, like postal S10 identifiers: `EX000000014`.
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_05::*;
+///
+/// let number = TrackingNumber::new(DeliveryType::Express, 1).unwrap();
+/// assert_eq!(number.to_string(), "EX000000014");
+///
+/// let parsed: TrackingNumber = "ex 0000 0001 4".parse().unwrap();
+/// assert_eq!(parsed, number);
+/// assert_eq!(parsed.delivery_type, DeliveryType::Express);
+///
+/// assert_eq!(
+///     "EX000000015".parse::<TrackingNumber>(),
+///     Err(TrackingNumberError::InvalidCheckDigit { expected: 4, found: 5 })
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TrackingNumber {
+    pub delivery_type: DeliveryType,
+    pub serial: u64,
+}
+
+impl TrackingNumber {
+    pub const LENGTH: usize = 11;
+
+    pub fn new(delivery_type: DeliveryType, serial: u64) -> Result<Self, TrackingNumberError> {
+        if serial > MAX_SERIAL {
+            return Err(TrackingNumberError::SerialTooLarge(serial));
+        }
+        Ok(TrackingNumber {
+            delivery_type,
+            serial,
+        })
+    }
+
+    pub fn check_digit(&self) -> u32 {
+        check_digit(self.serial)
+    }
+}
+
+/// The mod-11 check digit of a serial, a remainder of 10 gives 0 and 11 gives 5
+pub fn check_digit(serial: u64) -> u32 {
+    let digits = format!("{:08}", serial);
+    let sum: u32 = digits
+        .chars()
+        .zip(CHECK_WEIGHTS)
+        .map(|(digit, weight)| digit.to_digit(10).unwrap_or(0) * weight)
+        .sum();
+    match 11 - sum % 11 {
+        10 => 0,
+        11 => 5,
+        digit => digit,
+    }
+}
+
+impl std::fmt::Display for TrackingNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{:08}{}",
+            self.delivery_type.tracking_prefix(),
+            self.serial,
+            self.check_digit()
+        )
+    }
+}
+
+impl FromStr for TrackingNumber {
+    type Err = TrackingNumberError;
+
+    /// Spaces and dashes are ignored, letters may be lowercase
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let compact: String = value
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .collect::<String>()
+            .to_uppercase();
+        if compact.chars().count() != Self::LENGTH {
+            return Err(TrackingNumberError::InvalidLength(compact.chars().count()));
+        }
+        if !compact.is_ascii() {
+            return Err(TrackingNumberError::InvalidSerial(compact));
+        }
+
+        let (prefix, rest) = compact.split_at(2);
+        let delivery_type = DeliveryType::from_tracking_prefix(prefix)
+            .ok_or_else(|| TrackingNumberError::UnknownPrefix(prefix.to_string()))?;
+        if !rest.chars().all(|c| c.is_ascii_digit()) {
+            return Err(TrackingNumberError::InvalidSerial(rest.to_string()));
+        }
+
+        let (serial, check) = rest.split_at(8);
+        let serial: u64 = serial
+            .parse()
+            .map_err(|_| TrackingNumberError::InvalidSerial(serial.to_string()))?;
+        let found = check.parse().unwrap_or_default();
+        let expected = check_digit(serial);
+        if found != expected {
+            return Err(TrackingNumberError::InvalidCheckDigit { expected, found });
+        }
+        TrackingNumber::new(delivery_type, serial)
+    }
+}
+
+/// TrackingNumberGenerator struct, hands out tracking numbers with increasing serials
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_05::*;
+///
+/// let mut generator = TrackingNumberGenerator::new(41);
+///
+/// let first = generator.next_for(&DeliveryType::Standard).unwrap();
+/// let second = generator.next_for(&DeliveryType::Insured).unwrap();
+///
+/// assert_eq!(first.to_string(), "ST000000411");
+/// assert_eq!(second.to_string(), "IN000000425");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackingNumberGenerator {
+    next_serial: u64,
+}
+
+impl TrackingNumberGenerator {
+    pub fn new(first_serial: u64) -> Self {
+        TrackingNumberGenerator {
+            next_serial: first_serial,
+        }
+    }
+
+    pub fn next_for(
+        &mut self,
+        delivery_type: &DeliveryType,
+    ) -> Result<TrackingNumber, TrackingNumberError> {
+        let number = TrackingNumber::new(delivery_type.clone(), self.next_serial)?;
+        self.next_serial += 1;
+        Ok(number)
+    }
+}
@@ -13,3 +13,9 @@ pub use isp::*;
 /// Dependency Inversion Principle (DIP)
 pub mod dip;
 pub use dip::*;
+/// Authentication service, credentials kept apart from the member profile (SRP)
+pub mod auth;
+pub use auth::*;
+/// Role-based access control for members
+pub mod rbac;
+pub use rbac::*;
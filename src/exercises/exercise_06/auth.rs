@@ -0,0 +1,398 @@
+use crate::error::ErrorCode;
+use crate::exercises::exercise_06::{rbac::Role, srp::Member};
+use chrono::{DateTime, Duration, Utc};
+use rand::{distr::Alphanumeric, RngExt};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+const SESSION_TOKEN_LENGTH: usize = 48;
+
+/// Login attempts are pruned whenever their count reaches this, or twice
+/// what was left after the last pruning
+const MIN_ATTEMPTS_TO_PRUNE: usize = 64;
+
+/// Checked against when the email is unknown, so unknown emails take as long as wrong passwords
+const DUMMY_PASSWORD: &str = "not the password of anyone";
+
+/// AuthError enum
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_06::auth::AuthError;
+///
+/// assert_eq!(format!("{}", AuthError::InvalidCredentials), "Invalid email or password");
+/// assert_eq!(format!("{}", AuthError::MemberExists("test@test.com".to_string())), "Member test@test.com already exists");
+/// ```
+#[derive(Debug, Clone)]
+pub enum AuthError {
+    MemberExists(String),
+    InvalidCredentials,
+    LockedOut { until: DateTime<Utc> },
+    RateLimited { retry_at: DateTime<Utc> },
+    SessionNotFound,
+    SessionExpired,
+    Hashing(Arc<bcrypt::BcryptError>),
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AuthError::MemberExists(email) => write!(f, "Member {} already exists", email),
+            AuthError::InvalidCredentials => write!(f, "Invalid email or password"),
+            AuthError::LockedOut { until } => write!(f, "Account locked until {}", until),
+            AuthError::RateLimited { retry_at } => {
+                write!(f, "Too many login attempts, retry at {}", retry_at)
+            }
+            AuthError::SessionNotFound => write!(f, "Session not found"),
+            AuthError::SessionExpired => write!(f, "Session has expired"),
+            AuthError::Hashing(error) => write!(f, "Could not hash password: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AuthError::Hashing(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Hashing errors aren't comparable, they are equal when their messages are
+impl PartialEq for AuthError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (AuthError::MemberExists(a), AuthError::MemberExists(b)) => a == b,
+            (AuthError::InvalidCredentials, AuthError::InvalidCredentials) => true,
+            (AuthError::LockedOut { until }, AuthError::LockedOut { until: other_until }) => {
+                until == other_until
+            }
+            (
+                AuthError::RateLimited { retry_at },
+                AuthError::RateLimited {
+                    retry_at: other_retry_at,
+                },
+            ) => retry_at == other_retry_at,
+            (AuthError::SessionNotFound, AuthError::SessionNotFound) => true,
+            (AuthError::SessionExpired, AuthError::SessionExpired) => true,
+            (AuthError::Hashing(a), AuthError::Hashing(b)) => a.to_string() == b.to_string(),
+            _ => false,
+        }
+    }
+}
+
+impl ErrorCode for AuthError {
+    fn code(&self) -> &'static str {
+        match self {
+            AuthError::MemberExists(_) => "auth.member_exists",
+            AuthError::InvalidCredentials => "auth.invalid_credentials",
+            AuthError::LockedOut { .. } => "auth.locked_out",
+            AuthError::RateLimited { .. } => "auth.rate_limited",
+            AuthError::SessionNotFound => "auth.session_not_found",
+            AuthError::SessionExpired => "auth.session_expired",
+            AuthError::Hashing(_) => "auth.hashing",
+        }
+    }
+}
+
+/// AuthConfig struct
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_06::auth::AuthConfig;
+/// use chrono::Duration;
+///
+/// let config = AuthConfig::default();
+///
+/// assert_eq!(config.session_ttl, Duration::hours(1));
+/// assert_eq!(config.max_failed_logins, 5);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthConfig {
+    pub session_ttl: Duration,
+    pub max_failed_logins: u32,
+    pub lockout_duration: Duration,
+    pub max_attempts_per_window: usize,
+    pub attempts_window: Duration,
+    pub hash_cost: u32,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            session_ttl: Duration::hours(1),
+            max_failed_logins: 5,
+            lockout_duration: Duration::minutes(15),
+            max_attempts_per_window: 10,
+            attempts_window: Duration::minutes(1),
+            hash_cost: bcrypt::DEFAULT_COST,
+        }
+    }
+}
+
+/// MemberProfile struct, everything about a member but its credentials
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemberProfile {
+    pub email: String,
+    pub role: Role,
+}
+
+impl From<&Member> for MemberProfile {
+    fn from(member: &Member) -> Self {
+        MemberProfile {
+            email: member.email().to_string(),
+            role: *member.role(),
+        }
+    }
+}
+
+/// Credentials struct, only a bcrypt hash of the password is kept
+#[derive(Debug, Clone, PartialEq)]
+pub struct Credentials {
+    pub email: String,
+    password_hash: String,
+}
+
+impl Credentials {
+    pub fn new(email: &str, password: &str, cost: u32) -> Result<Self, AuthError> {
+        let password_hash =
+            bcrypt::hash(password, cost).map_err(|e| AuthError::Hashing(Arc::new(e)))?;
+        Ok(Credentials {
+            email: email.to_string(),
+            password_hash,
+        })
+    }
+
+    pub fn verify(&self, password: &str) -> bool {
+        bcrypt::verify(password, &self.password_hash).unwrap_or(false)
+    }
+}
+
+/// Session struct
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub token: String,
+    pub email: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Session {
+    fn issue(email: &str, now: DateTime<Utc>, ttl: Duration) -> Session {
+        let token = rand::rng()
+            .sample_iter(Alphanumeric)
+            .take(SESSION_TOKEN_LENGTH)
+            .map(char::from)
+            .collect();
+
+        Session {
+            token,
+            email: email.to_string(),
+            expires_at: now + ttl,
+        }
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now >= self.expires_at
+    }
+}
+
+#[derive(Debug, Default)]
+struct LoginAttempts {
+    consecutive_failures: u32,
+    locked_until: Option<DateTime<Utc>>,
+    recent: VecDeque<DateTime<Utc>>,
+}
+
+impl LoginAttempts {
+    /// Not locked out and quiet for longer than both the rate limit window
+    /// and the lockout, so forgetting the failures can't shorten either
+    fn is_stale(&self, now: DateTime<Utc>, config: &AuthConfig) -> bool {
+        let is_locked = self.locked_until.is_some_and(|until| now < until);
+        let quiet_for = config.attempts_window.max(config.lockout_duration);
+        !is_locked
+            && self
+                .recent
+                .back()
+                .is_none_or(|last| now - *last >= quiet_for)
+    }
+}
+
+/// AuthService struct, keeps credentials, profiles and sessions apart
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_06::auth::*;
+/// use chrono::Utc;
+///
+/// let config = AuthConfig { hash_cost: 4, ..AuthConfig::default() };
+/// let mut auth = AuthService::new(config);
+/// let now = Utc::now();
+///
+/// assert_eq!(auth.signup("test@test.com", "password").is_ok(), true);
+/// assert_eq!(auth.signup("test@test.com", "other"), Err(AuthError::MemberExists("test@test.com".to_string())));
+///
+/// assert_eq!(auth.login("test@test.com", "wrong", now), Err(AuthError::InvalidCredentials));
+/// let token = auth.login("test@test.com", "password", now).unwrap().token;
+///
+/// assert_eq!(auth.validate_session(&token, now).unwrap().email, "test@test.com");
+/// assert_eq!(auth.revoke_session(&token), Ok(()));
+/// assert_eq!(auth.validate_session(&token, now), Err(AuthError::SessionNotFound));
+/// ```
+#[derive(Debug, Default)]
+pub struct AuthService {
+    pub config: AuthConfig,
+    credentials: HashMap<String, Credentials>,
+    profiles: HashMap<String, MemberProfile>,
+    sessions: HashMap<String, Session>,
+    attempts: HashMap<String, LoginAttempts>,
+    next_attempts_prune: usize,
+    dummy_credentials: Option<Credentials>,
+}
+
+impl AuthService {
+    pub fn new(config: AuthConfig) -> Self {
+        AuthService {
+            config,
+            ..AuthService::default()
+        }
+    }
+
+    pub fn signup(&mut self, email: &str, password: &str) -> Result<&MemberProfile, AuthError> {
+        let profile = MemberProfile {
+            email: email.to_string(),
+            role: Role::User,
+        };
+        self.register(profile, password)
+    }
+
+    /// Moves a `srp::Member` into the service, hashing its password
+    pub fn import_member(&mut self, member: &Member) -> Result<&MemberProfile, AuthError> {
+        self.register(MemberProfile::from(member), member.password())
+    }
+
+    fn register(
+        &mut self,
+        profile: MemberProfile,
+        password: &str,
+    ) -> Result<&MemberProfile, AuthError> {
+        let email = profile.email.clone();
+        if self.credentials.contains_key(&email) {
+            return Err(AuthError::MemberExists(email));
+        }
+
+        let credentials = Credentials::new(&email, password, self.config.hash_cost)?;
+        self.credentials.insert(email.clone(), credentials);
+        Ok(self.profiles.entry(email).or_insert(profile))
+    }
+
+    pub fn profile(&self, email: &str) -> Option<&MemberProfile> {
+        self.profiles.get(email)
+    }
+
+    pub fn login(
+        &mut self,
+        email: &str,
+        password: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Session, AuthError> {
+        let config = self.config.clone();
+        if self.attempts.len() >= self.next_attempts_prune.max(MIN_ATTEMPTS_TO_PRUNE) {
+            self.purge_expired_attempts(now);
+            self.next_attempts_prune = self.attempts.len() * 2;
+        }
+        if self.dummy_credentials.is_none() {
+            self.dummy_credentials = Some(Credentials::new("", DUMMY_PASSWORD, config.hash_cost)?);
+        }
+        let attempts = self.attempts.entry(email.to_string()).or_default();
+
+        while attempts
+            .recent
+            .front()
+            .is_some_and(|attempt| now - *attempt >= config.attempts_window)
+        {
+            attempts.recent.pop_front();
+        }
+        if attempts.recent.len() >= config.max_attempts_per_window {
+            let oldest = attempts.recent[0];
+            return Err(AuthError::RateLimited {
+                retry_at: oldest + config.attempts_window,
+            });
+        }
+        attempts.recent.push_back(now);
+
+        match attempts.locked_until {
+            Some(until) if now < until => return Err(AuthError::LockedOut { until }),
+            Some(_) => {
+                attempts.locked_until = None;
+                attempts.consecutive_failures = 0;
+            }
+            None => {}
+        }
+
+        let is_valid = match self.credentials.get(email) {
+            Some(credentials) => credentials.verify(password),
+            None => {
+                // Same bcrypt work as a wrong password, the outcome is ignored
+                if let Some(dummy) = &self.dummy_credentials {
+                    dummy.verify(password);
+                }
+                false
+            }
+        };
+        if !is_valid {
+            attempts.consecutive_failures += 1;
+            if attempts.consecutive_failures >= config.max_failed_logins {
+                attempts.locked_until = Some(now + config.lockout_duration);
+            }
+            return Err(AuthError::InvalidCredentials);
+        }
+
+        attempts.consecutive_failures = 0;
+        let session = Session::issue(email, now, config.session_ttl);
+        self.sessions.insert(session.token.clone(), session.clone());
+        Ok(session)
+    }
+
+    pub fn validate_session(&self, token: &str, now: DateTime<Utc>) -> Result<&Session, AuthError> {
+        let session = self.sessions.get(token).ok_or(AuthError::SessionNotFound)?;
+        if session.is_expired(now) {
+            return Err(AuthError::SessionExpired);
+        }
+        Ok(session)
+    }
+
+    pub fn revoke_session(&mut self, token: &str) -> Result<(), AuthError> {
+        self.sessions
+            .remove(token)
+            .map(|_| ())
+            .ok_or(AuthError::SessionNotFound)
+    }
+
+    /// Revokes every session of a member, returns how many were revoked
+    pub fn revoke_member_sessions(&mut self, email: &str) -> usize {
+        let before = self.sessions.len();
+        self.sessions.retain(|_, session| session.email != email);
+        before - self.sessions.len()
+    }
+
+    /// Forgets the login attempts of emails that are neither locked out nor
+    /// rate limited anymore, returns how many were forgotten
+    pub fn purge_expired_attempts(&mut self, now: DateTime<Utc>) -> usize {
+        let before = self.attempts.len();
+        let config = &self.config;
+        self.attempts
+            .retain(|_, attempts| !attempts.is_stale(now, config));
+        before - self.attempts.len()
+    }
+
+    /// Drops expired sessions, returns how many were dropped
+    pub fn purge_expired_sessions(&mut self, now: DateTime<Utc>) -> usize {
+        let before = self.sessions.len();
+        self.sessions.retain(|_, session| !session.is_expired(now));
+        before - self.sessions.len()
+    }
+}
@@ -1,9 +1,43 @@
+use crate::error::ErrorCode;
+
+/// PrintError enum
+///
+/// # Example
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_06::ocp::*;
+///
+/// assert_eq!(format!("{}", PrintError::EmptyData), "Data is empty");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrintError {
+    EmptyData,
+}
+
+impl std::fmt::Display for PrintError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PrintError::EmptyData => write!(f, "Data is empty"),
+        }
+    }
+}
+
+impl std::error::Error for PrintError {}
+
+impl ErrorCode for PrintError {
+    fn code(&self) -> &'static str {
+        match self {
+            PrintError::EmptyData => "print.empty_data",
+        }
+    }
+}
+
 pub trait Printer {
-    fn print(&self, data: &str) -> Result<String, &str>;
+    fn print(&self, data: &str) -> Result<String, PrintError>;
 
-    fn verify_data<'a>(&self, data: &'a str) -> Result<&'a str, &str> {
+    fn verify_data<'a>(&self, data: &'a str) -> Result<&'a str, PrintError> {
         if data.is_empty() {
-            return Err("Data is empty");
+            return Err(PrintError::EmptyData);
         }
         Ok(data)
     }
@@ -18,12 +52,12 @@ pub trait Printer {
 ///
 /// let web_printer = WebPrinter;
 /// assert_eq!(web_printer.print("Some data"), Ok("Web Printer Printing: Some data".to_string()));
-/// assert_eq!(web_printer.print(""), Err("Data is empty"));
+/// assert_eq!(web_printer.print(""), Err(PrintError::EmptyData));
 /// ```
 pub struct WebPrinter;
 
 impl Printer for WebPrinter {
-    fn print(&self, data: &str) -> Result<String, &str> {
+    fn print(&self, data: &str) -> Result<String, PrintError> {
         let valid_data = self.verify_data(data)?;
         Ok(format!("Web Printer Printing: {}", valid_data))
     }
@@ -38,12 +72,12 @@ impl Printer for WebPrinter {
 ///
 /// let pdf_printer = PDFPrinter;
 /// assert_eq!(pdf_printer.print("Some data"), Ok("PDF Printer Printing: Some data".to_string()));
-/// assert_eq!(pdf_printer.print(""), Err("Data is empty"));
+/// assert_eq!(pdf_printer.print(""), Err(PrintError::EmptyData));
 /// ```
 pub struct PDFPrinter;
 
 impl Printer for PDFPrinter {
-    fn print(&self, data: &str) -> Result<String, &str> {
+    fn print(&self, data: &str) -> Result<String, PrintError> {
         let valid_data = self.verify_data(data)?;
         Ok(format!("PDF Printer Printing: {}", valid_data))
     }
@@ -58,12 +92,12 @@ impl Printer for PDFPrinter {
 ///
 /// let page_printer = PagePrinter;
 /// assert_eq!(page_printer.print("Some data"), Ok("Page Printer Printing: Some data".to_string()));
-/// assert_eq!(page_printer.print(""), Err("Data is empty"));
+/// assert_eq!(page_printer.print(""), Err(PrintError::EmptyData));
 /// ```
 pub struct PagePrinter;
 
 impl Printer for PagePrinter {
-    fn print(&self, data: &str) -> Result<String, &str> {
+    fn print(&self, data: &str) -> Result<String, PrintError> {
         let valid_data = self.verify_data(data)?;
         Ok(format!("Page Printer Printing: {}", valid_data))
     }
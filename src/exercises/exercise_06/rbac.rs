@@ -0,0 +1,346 @@
+use crate::error::ErrorCode;
+use crate::exercises::exercise_06::srp::Member;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+/// Who the first admin is assigned by
+pub const SYSTEM_ASSIGNER: &str = "system";
+
+/// Role enum, every role inherits the permissions of the one below it
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_06::rbac::Role;
+///
+/// assert_eq!(format!("{}", Role::Admin), "admin");
+/// assert_eq!("moderator".parse::<Role>(), Ok(Role::Moderator));
+/// assert_eq!(Role::Admin.inherits(&Role::User), true);
+/// assert_eq!(Role::User.inherits(&Role::Admin), false);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Role {
+    Guest,
+    User,
+    Moderator,
+    Admin,
+}
+
+impl Role {
+    pub fn parent(&self) -> Option<Role> {
+        match self {
+            Role::Guest => None,
+            Role::User => Some(Role::Guest),
+            Role::Moderator => Some(Role::User),
+            Role::Admin => Some(Role::Moderator),
+        }
+    }
+
+    /// Returns the role itself followed by every role it inherits from
+    pub fn lineage(&self) -> Vec<Role> {
+        std::iter::successors(Some(*self), Role::parent).collect()
+    }
+
+    pub fn inherits(&self, other: &Role) -> bool {
+        self.lineage().contains(other)
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Role::Guest => write!(f, "guest"),
+            Role::User => write!(f, "user"),
+            Role::Moderator => write!(f, "moderator"),
+            Role::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = AccessError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "guest" => Ok(Role::Guest),
+            "user" => Ok(Role::User),
+            "moderator" => Ok(Role::Moderator),
+            "admin" => Ok(Role::Admin),
+            _ => Err(AccessError::UnknownRole(s.to_string())),
+        }
+    }
+}
+
+/// Action enum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Read,
+    Create,
+    Update,
+    Delete,
+    AssignRole,
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Action::Read => write!(f, "read"),
+            Action::Create => write!(f, "create"),
+            Action::Update => write!(f, "update"),
+            Action::Delete => write!(f, "delete"),
+            Action::AssignRole => write!(f, "assign role"),
+        }
+    }
+}
+
+/// Resource enum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Resource {
+    Profile,
+    Content,
+    Member,
+    Settings,
+}
+
+impl std::fmt::Display for Resource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Resource::Profile => write!(f, "profile"),
+            Resource::Content => write!(f, "content"),
+            Resource::Member => write!(f, "member"),
+            Resource::Settings => write!(f, "settings"),
+        }
+    }
+}
+
+/// AccessError enum
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_06::rbac::*;
+///
+/// let error = AccessError::Forbidden {
+///     role: Role::User,
+///     action: Action::Delete,
+///     resource: Resource::Member,
+/// };
+///
+/// assert_eq!(format!("{}", error), "Role user cannot delete member");
+/// assert_eq!(format!("{}", AccessError::UnknownRole("owner".to_string())), "Unknown role: owner");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccessError {
+    UnknownRole(String),
+    Forbidden {
+        role: Role,
+        action: Action,
+        resource: Resource,
+    },
+    RoleAboveOwn {
+        role: Role,
+        requested: Role,
+    },
+    /// The first admin was already assigned
+    AdminExists,
+}
+
+impl std::fmt::Display for AccessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AccessError::UnknownRole(role) => write!(f, "Unknown role: {}", role),
+            AccessError::Forbidden {
+                role,
+                action,
+                resource,
+            } => write!(f, "Role {} cannot {} {}", role, action, resource),
+            AccessError::RoleAboveOwn { role, requested } => {
+                write!(f, "Role {} cannot assign role {}", role, requested)
+            }
+            AccessError::AdminExists => write!(f, "An admin was already assigned"),
+        }
+    }
+}
+
+impl std::error::Error for AccessError {}
+
+impl ErrorCode for AccessError {
+    fn code(&self) -> &'static str {
+        match self {
+            AccessError::UnknownRole(_) => "access.unknown_role",
+            AccessError::Forbidden { .. } => "access.forbidden",
+            AccessError::RoleAboveOwn { .. } => "access.role_above_own",
+            AccessError::AdminExists => "access.admin_exists",
+        }
+    }
+}
+
+/// RoleAssignment struct, an entry of the role audit log
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoleAssignment {
+    pub member: String,
+    pub previous_role: Role,
+    pub role: Role,
+    pub assigned_by: String,
+    pub assigned_at: DateTime<Utc>,
+}
+
+/// AccessControl struct, the policy engine deciding what each role can do
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::exercises::exercise_06::rbac::*;
+/// use clean_code_notes_exercises::exercises::exercise_06::srp::Member;
+/// use chrono::Utc;
+///
+/// let mut access = AccessControl::default();
+/// let mut admin = Member::new("admin@test.com", "password");
+/// let mut member = Member::new("test@test.com", "password");
+/// let now = Utc::now();
+/// access.bootstrap_admin(&mut admin, now).unwrap();
+///
+/// assert_eq!(access.can(&member, Action::Read, Resource::Content), true);
+/// assert_eq!(access.can(&member, Action::Delete, Resource::Content), false);
+///
+/// assert_eq!(access.assign_role(&admin, &mut member, Role::Moderator, now).is_ok(), true);
+/// assert_eq!(access.can(&member, Action::Delete, Resource::Content), true);
+/// assert_eq!(access.audit_log()[0].assigned_by, SYSTEM_ASSIGNER);
+/// assert_eq!(access.audit_log()[1].assigned_by, "admin@test.com");
+/// ```
+#[derive(Debug, Clone)]
+pub struct AccessControl {
+    grants: HashMap<Role, Vec<(Action, Resource)>>,
+    audit_log: Vec<RoleAssignment>,
+}
+
+impl Default for AccessControl {
+    fn default() -> Self {
+        let mut access = AccessControl::new();
+        access.grant(Role::Guest, Action::Read, Resource::Content);
+        access.grant(Role::User, Action::Read, Resource::Profile);
+        access.grant(Role::User, Action::Update, Resource::Profile);
+        access.grant(Role::User, Action::Create, Resource::Content);
+        access.grant(Role::Moderator, Action::Update, Resource::Content);
+        access.grant(Role::Moderator, Action::Delete, Resource::Content);
+        access.grant(Role::Moderator, Action::Read, Resource::Member);
+        access.grant(Role::Admin, Action::Update, Resource::Member);
+        access.grant(Role::Admin, Action::Delete, Resource::Member);
+        access.grant(Role::Admin, Action::AssignRole, Resource::Member);
+        access.grant(Role::Admin, Action::Read, Resource::Settings);
+        access.grant(Role::Admin, Action::Update, Resource::Settings);
+        access
+    }
+}
+
+impl AccessControl {
+    /// Creates a policy without any grants
+    pub fn new() -> Self {
+        AccessControl {
+            grants: HashMap::new(),
+            audit_log: vec![],
+        }
+    }
+
+    pub fn grant(&mut self, role: Role, action: Action, resource: Resource) {
+        let grants = self.grants.entry(role).or_default();
+        if !grants.contains(&(action, resource)) {
+            grants.push((action, resource));
+        }
+    }
+
+    pub fn role_can(&self, role: &Role, action: Action, resource: Resource) -> bool {
+        role.lineage().iter().any(|role| {
+            self.grants
+                .get(role)
+                .is_some_and(|grants| grants.contains(&(action, resource)))
+        })
+    }
+
+    pub fn can(&self, member: &Member, action: Action, resource: Resource) -> bool {
+        self.role_can(member.role(), action, resource)
+    }
+
+    pub fn authorize(
+        &self,
+        member: &Member,
+        action: Action,
+        resource: Resource,
+    ) -> Result<(), AccessError> {
+        match self.can(member, action, resource) {
+            true => Ok(()),
+            false => Err(AccessError::Forbidden {
+                role: *member.role(),
+                action,
+                resource,
+            }),
+        }
+    }
+
+    /// Assigns a role on behalf of `actor`, who can't hand out a role above
+    /// their own, and records it in the audit log
+    pub fn assign_role(
+        &mut self,
+        actor: &Member,
+        member: &mut Member,
+        role: Role,
+        now: DateTime<Utc>,
+    ) -> Result<&RoleAssignment, AccessError> {
+        self.authorize(actor, Action::AssignRole, Resource::Member)?;
+        if !actor.role().inherits(&role) {
+            return Err(AccessError::RoleAboveOwn {
+                role: *actor.role(),
+                requested: role,
+            });
+        }
+
+        Ok(self.record_assignment(actor.email(), member, role, now))
+    }
+
+    /// Makes the first admin, there is nobody yet to assign it, so it's
+    /// recorded as assigned by `SYSTEM_ASSIGNER` and only works once
+    pub fn bootstrap_admin(
+        &mut self,
+        member: &mut Member,
+        now: DateTime<Utc>,
+    ) -> Result<&RoleAssignment, AccessError> {
+        if self
+            .audit_log
+            .iter()
+            .any(|assignment| assignment.assigned_by == SYSTEM_ASSIGNER)
+        {
+            return Err(AccessError::AdminExists);
+        }
+        Ok(self.record_assignment(SYSTEM_ASSIGNER, member, Role::Admin, now))
+    }
+
+    fn record_assignment(
+        &mut self,
+        assigned_by: &str,
+        member: &mut Member,
+        role: Role,
+        now: DateTime<Utc>,
+    ) -> &RoleAssignment {
+        let previous_role = *member.role();
+        member.assign_role(role);
+        self.audit_log.push(RoleAssignment {
+            member: member.email().to_string(),
+            previous_role,
+            role,
+            assigned_by: assigned_by.to_string(),
+            assigned_at: now,
+        });
+        &self.audit_log[self.audit_log.len() - 1]
+    }
+
+    pub fn audit_log(&self) -> &[RoleAssignment] {
+        &self.audit_log
+    }
+
+    pub fn assignments_for(&self, email: &str) -> Vec<&RoleAssignment> {
+        self.audit_log
+            .iter()
+            .filter(|assignment| assignment.member == email)
+            .collect()
+    }
+}
@@ -1,19 +1,24 @@
-/// User struct with login, signup and assign_role methods
+use crate::exercises::exercise_06::rbac::Role;
+
+/// User struct with login and signup methods
+///
+/// Roles are changed through `rbac::AccessControl`, which checks the
+/// permission and records the change.
 ///
 /// # Example
 ///
 /// ```
-/// use clean_code_notes_exercises::exercises::exercise_06::srp::*;
+/// use clean_code_notes_exercises::exercises::exercise_06::{rbac::Role, srp::*};
 ///
 /// let mut member = Member::new("test@test.com", "password");
 /// assert_eq!(member.signup("test@test.com", "password"), "Member test@test.com signed up");
-/// assert_eq!(member.assign_role("admin"), "Member test@test.com assigned role admin");
+/// assert_eq!(member.role(), &Role::User);
 /// assert_eq!(member.login("test@test.com", "password"), true);
 /// ```
 pub struct Member {
     email: String,
     password: String,
-    role: String,
+    role: Role,
 }
 
 impl Member {
@@ -21,10 +26,22 @@ impl Member {
         Member {
             email: email.to_string(),
             password: password.to_string(),
-            role: "user".to_string(),
+            role: Role::User,
         }
     }
 
+    pub fn email(&self) -> &str {
+        &self.email
+    }
+
+    pub fn role(&self) -> &Role {
+        &self.role
+    }
+
+    pub(crate) fn password(&self) -> &str {
+        &self.password
+    }
+
     pub fn login(&self, email: &str, password: &str) -> bool {
         if self.email.is_empty() || self.password.is_empty() {
             return false;
@@ -38,8 +55,7 @@ impl Member {
         format!("Member {} signed up", email)
     }
 
-    pub fn assign_role(&mut self, role: &str) -> String {
-        self.role = role.to_string();
-        format!("Member {} assigned role {}", self.email, role)
+    pub(crate) fn assign_role(&mut self, role: Role) {
+        self.role = role;
     }
 }
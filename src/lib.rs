@@ -1 +1,3 @@
+pub mod error;
 pub mod exercises;
+pub mod retry;
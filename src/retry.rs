@@ -0,0 +1,353 @@
+use crate::error::ErrorCode;
+use chrono::{DateTime, Duration, Utc};
+use rand::rngs::StdRng;
+use rand::{RngExt, SeedableRng};
+use std::cell::{Cell, RefCell};
+
+/// Clock trait, where retries read the time and wait between attempts
+pub trait Clock {
+    fn now(&self) -> DateTime<Utc>;
+    fn sleep(&self, duration: Duration);
+}
+
+/// SystemClock struct, the wall clock, sleeps block the thread
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration.to_std().unwrap_or_default());
+    }
+}
+
+/// ManualClock struct, a clock for tests that only moves when slept on
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::retry::*;
+/// use chrono::{Duration, TimeZone, Utc};
+///
+/// let start = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+/// let clock = ManualClock::new(start);
+///
+/// clock.sleep(Duration::seconds(2));
+/// clock.advance(Duration::seconds(1));
+///
+/// assert_eq!(clock.now(), start + Duration::seconds(3));
+/// assert_eq!(clock.sleeps(), vec![Duration::seconds(2)]);
+/// ```
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Cell<DateTime<Utc>>,
+    sleeps: RefCell<Vec<Duration>>,
+}
+
+impl ManualClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        ManualClock {
+            now: Cell::new(start),
+            sleeps: RefCell::new(vec![]),
+        }
+    }
+
+    /// Moves the time forward without counting it as a sleep
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+
+    /// Every sleep so far, in order
+    pub fn sleeps(&self) -> Vec<Duration> {
+        self.sleeps.borrow().clone()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.now.get()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+        self.sleeps.borrow_mut().push(duration);
+    }
+}
+
+/// Jitter enum, how much randomness goes into each delay
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Jitter {
+    /// The exact backoff
+    None,
+    /// Anything between zero and the backoff
+    Full,
+    /// Half the backoff plus anything up to the other half
+    Equal,
+}
+
+/// RetryPolicy struct
+///
+/// The delay before retry `n` is `initial_delay * multiplier^(n - 1)`, capped
+/// at `max_delay` and then jittered. With a `deadline` no retry is started
+/// once waiting for it would end past the deadline.
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::retry::*;
+/// use chrono::Duration;
+///
+/// let policy = RetryPolicy::new(5)
+///     .with_backoff(Duration::milliseconds(100), 3)
+///     .with_max_delay(Duration::seconds(1));
+///
+/// assert_eq!(policy.backoff(1), Duration::milliseconds(100));
+/// assert_eq!(policy.backoff(2), Duration::milliseconds(300));
+/// assert_eq!(policy.backoff(3), Duration::milliseconds(900));
+/// assert_eq!(policy.backoff(4), Duration::seconds(1));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts including the first one, at least one attempt is always made
+    pub max_attempts: u32,
+    pub initial_delay: Duration,
+    pub multiplier: u32,
+    pub max_delay: Duration,
+    pub jitter: Jitter,
+    /// Longest time from the first attempt to the start of the last one
+    pub deadline: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::new(3)
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32) -> Self {
+        RetryPolicy {
+            max_attempts,
+            initial_delay: Duration::milliseconds(100),
+            multiplier: 2,
+            max_delay: Duration::seconds(10),
+            jitter: Jitter::Full,
+            deadline: None,
+        }
+    }
+
+    pub fn with_backoff(mut self, initial_delay: Duration, multiplier: u32) -> Self {
+        self.initial_delay = initial_delay;
+        self.multiplier = multiplier;
+        self
+    }
+
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: Jitter) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Delay before retry `retry`, the first retry is 1, without jitter
+    ///
+    /// Negative delays are clamped to zero, delays too long for a `Duration`
+    /// to the maximum delay.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = (self.multiplier as i64).saturating_pow(retry.saturating_sub(1));
+        let delay = self
+            .initial_delay
+            .num_milliseconds()
+            .max(0)
+            .saturating_mul(factor);
+        Duration::try_milliseconds(delay)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+            .max(Duration::zero())
+    }
+
+    fn jittered(&self, delay: Duration, rng: &mut StdRng) -> Duration {
+        let millis = delay.num_milliseconds().max(0);
+        match self.jitter {
+            Jitter::None => delay,
+            Jitter::Full => Duration::milliseconds(rng.random_range(0..=millis)),
+            Jitter::Equal => {
+                let half = millis / 2;
+                Duration::milliseconds(half + rng.random_range(0..=millis - half))
+            }
+        }
+    }
+}
+
+/// RetryError enum, why retrying stopped and the last error
+#[derive(Debug, Clone, PartialEq)]
+pub enum RetryError<E> {
+    /// The classifier said retrying won't help
+    Permanent {
+        attempts: u32,
+        error: E,
+    },
+    Exhausted {
+        attempts: u32,
+        error: E,
+    },
+    DeadlineExceeded {
+        attempts: u32,
+        error: E,
+    },
+}
+
+impl<E> RetryError<E> {
+    pub fn attempts(&self) -> u32 {
+        match self {
+            RetryError::Permanent { attempts, .. } => *attempts,
+            RetryError::Exhausted { attempts, .. } => *attempts,
+            RetryError::DeadlineExceeded { attempts, .. } => *attempts,
+        }
+    }
+
+    pub fn error(&self) -> &E {
+        match self {
+            RetryError::Permanent { error, .. } => error,
+            RetryError::Exhausted { error, .. } => error,
+            RetryError::DeadlineExceeded { error, .. } => error,
+        }
+    }
+
+    pub fn into_inner(self) -> E {
+        match self {
+            RetryError::Permanent { error, .. } => error,
+            RetryError::Exhausted { error, .. } => error,
+            RetryError::DeadlineExceeded { error, .. } => error,
+        }
+    }
+}
+
+impl<E> std::fmt::Display for RetryError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RetryError::Permanent { attempts, .. } => {
+                write!(f, "Failed for good after {} attempts", attempts)
+            }
+            RetryError::Exhausted { attempts, .. } => {
+                write!(f, "Still failing after {} attempts", attempts)
+            }
+            RetryError::DeadlineExceeded { attempts, .. } => {
+                write!(f, "Retry deadline passed after {} attempts", attempts)
+            }
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for RetryError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.error())
+    }
+}
+
+impl<E> ErrorCode for RetryError<E> {
+    fn code(&self) -> &'static str {
+        match self {
+            RetryError::Permanent { .. } => "retry.permanent",
+            RetryError::Exhausted { .. } => "retry.exhausted",
+            RetryError::DeadlineExceeded { .. } => "retry.deadline_exceeded",
+        }
+    }
+}
+
+/// Retry struct, runs fallible operations again following a policy
+///
+/// # Examples
+///
+/// ```
+/// use clean_code_notes_exercises::retry::*;
+/// use chrono::{Duration, Utc};
+///
+/// let clock = ManualClock::new(Utc::now());
+/// let policy = RetryPolicy::new(4)
+///     .with_backoff(Duration::milliseconds(100), 2)
+///     .with_jitter(Jitter::None);
+/// let mut retry = Retry::new(policy, &clock);
+///
+/// let result = retry.run(
+///     |error: &&str| *error == "busy",
+///     |attempt| if attempt < 3 { Err("busy") } else { Ok(attempt) },
+/// );
+///
+/// assert_eq!(result, Ok(3));
+/// assert_eq!(clock.sleeps(), vec![Duration::milliseconds(100), Duration::milliseconds(200)]);
+///
+/// let result: Result<(), _> = retry.run(|error: &&str| *error == "busy", |_| Err("broken"));
+/// assert_eq!(result, Err(RetryError::Permanent { attempts: 1, error: "broken" }));
+/// ```
+#[derive(Debug)]
+pub struct Retry<'c, C> {
+    policy: RetryPolicy,
+    clock: &'c C,
+    rng: StdRng,
+}
+
+impl<'c, C: Clock> Retry<'c, C> {
+    pub fn new(policy: RetryPolicy, clock: &'c C) -> Self {
+        Retry {
+            policy,
+            clock,
+            rng: rand::make_rng(),
+        }
+    }
+
+    /// Makes the jitter repeatable
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    /// Calls `operation` with the attempt number, starting at 1, until it
+    /// succeeds, fails with an error `is_retryable` rejects or the policy
+    /// runs out
+    pub fn run<T, E>(
+        &mut self,
+        is_retryable: impl Fn(&E) -> bool,
+        mut operation: impl FnMut(u32) -> Result<T, E>,
+    ) -> Result<T, RetryError<E>> {
+        let started = self.clock.now();
+        let mut attempts = 1;
+        loop {
+            let error = match operation(attempts) {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+            if !is_retryable(&error) {
+                return Err(RetryError::Permanent { attempts, error });
+            }
+            if attempts >= self.policy.max_attempts {
+                return Err(RetryError::Exhausted { attempts, error });
+            }
+
+            let delay = self
+                .policy
+                .jittered(self.policy.backoff(attempts), &mut self.rng);
+            if let Some(deadline) = self.policy.deadline {
+                if self.clock.now() + delay - started > deadline {
+                    return Err(RetryError::DeadlineExceeded { attempts, error });
+                }
+            }
+            self.clock.sleep(delay);
+            attempts += 1;
+        }
+    }
+}
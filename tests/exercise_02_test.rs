@@ -1,4 +1,6 @@
+use clean_code_notes_exercises::error::{Error, ErrorCode};
 use clean_code_notes_exercises::exercises::exercise_02::*;
+use clean_code_notes_exercises::retry::*;
 
 #[cfg(test)]
 mod exercise_02_test {
@@ -13,4 +15,71 @@ mod exercise_02_test {
         let file_content = std::fs::read_to_string(file_path).unwrap();
         assert_eq!(file_content, "Test");
     }
+
+    #[test]
+    fn test_disk_storage_errors_keep_the_io_error() {
+        let blocker = DiskStorage::new("logs_blocker");
+        std::fs::write(blocker.get_directory_path(), "Not a directory").unwrap();
+        let nested = DiskStorage::new("logs_blocker/nested");
+
+        let error = nested.create_directory().unwrap_err();
+
+        assert_eq!(error.code(), "storage.create_directory");
+        assert_eq!(
+            error.to_string(),
+            "Cannot create directory logs_blocker/nested"
+        );
+        assert!(std::error::Error::source(&error).is_some());
+
+        let error = Error::from(nested.insert_file("test.txt", "Test").unwrap_err());
+        assert_eq!(error.code(), "storage.write_file");
+        assert!(matches!(
+            error,
+            Error::Storage(StorageError::WriteFile { .. })
+        ));
+        assert!(std::error::Error::source(&error).is_some());
+
+        std::fs::remove_file(blocker.get_directory_path()).unwrap();
+    }
+
+    #[test]
+    fn test_disk_storage_only_retries_transient_errors() {
+        let clock = ManualClock::new(chrono::Utc::now());
+        let mut retry = Retry::new(RetryPolicy::new(3), &clock);
+        let storage = DiskStorage::new("logs_retry");
+        storage.create_directory().unwrap();
+
+        assert!(storage
+            .insert_file_with_retry("test.txt", "Test", &mut retry)
+            .is_ok());
+
+        // An interrupted write is tried again, after one backoff
+        let interrupted = StorageError::WriteFile {
+            path: storage.get_directory_path().join("retried.txt"),
+            source: std::io::Error::from(std::io::ErrorKind::Interrupted),
+        };
+        assert!(interrupted.is_transient());
+        let mut pending_failure = Some(interrupted);
+        let result = retry.run(StorageError::is_transient, |_| {
+            match pending_failure.take() {
+                Some(error) => Err(error),
+                None => storage.insert_file("retried.txt", "Retried"),
+            }
+        });
+        assert!(result.is_ok());
+        assert_eq!(clock.sleeps().len(), 1);
+        let retried = storage.get_directory_path().join("retried.txt");
+        assert_eq!(std::fs::read_to_string(retried).unwrap(), "Retried");
+
+        let missing = DiskStorage::new("logs_retry/missing");
+        let error = missing
+            .insert_file_with_retry("test.txt", "Test", &mut retry)
+            .unwrap_err();
+
+        assert!(matches!(error, RetryError::Permanent { attempts: 1, .. }));
+        assert!(!error.error().is_transient());
+        assert_eq!(clock.sleeps().len(), 1);
+
+        std::fs::remove_dir_all(storage.get_directory_path()).unwrap();
+    }
 }
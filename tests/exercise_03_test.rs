@@ -1,4 +1,7 @@
+use chrono::{Duration, Utc};
+use clean_code_notes_exercises::error::ErrorCode;
 use clean_code_notes_exercises::exercises::exercise_03::*;
+use unicode_script::Script;
 
 #[cfg(test)]
 mod exercise_03_test {
@@ -93,4 +96,204 @@ mod exercise_03_test {
             assert_eq!(result.unwrap_err(), expected_error);
         }
     }
+
+    #[test]
+    fn test_create_user_unicode_username() {
+        let usernames = vec!["José_Luis", "Дмитрий", "山田太郎さん", "ｕｓｅｒｎａｍｅ"];
+
+        for username in usernames {
+            let result = create_user(
+                username.to_string(),
+                VALID_EMAIL.to_string(),
+                VALID_PASSWORD.to_string(),
+            );
+            assert!(result.is_ok(), "{} should be a valid username", username);
+        }
+    }
+
+    #[test]
+    fn test_create_user_fail_mixed_script_username() {
+        let result = create_user(
+            "pаypal".to_string(),
+            VALID_EMAIL.to_string(),
+            VALID_PASSWORD.to_string(),
+        );
+        assert_eq!(result.unwrap_err(), CreateUserError::InvalidUsername);
+    }
+
+    #[test]
+    fn test_username_policy_allowed_scripts() {
+        let latin_only = UsernamePolicy::new(5, 32, ScriptPolicy::Allowed(vec![Script::Latin]));
+
+        assert_eq!(
+            latin_only.validate("José_Luis"),
+            Ok("José_Luis".to_string())
+        );
+        assert_eq!(
+            latin_only.validate("Дмитрий"),
+            Err(UsernameError::DisallowedScript(Script::Cyrillic))
+        );
+        assert_eq!(
+            UsernamePolicy::default().validate("a_very_long_username_that_goes_on"),
+            Err(UsernameError::TooLong(32))
+        );
+    }
+
+    #[test]
+    fn test_username_uniqueness_keys() {
+        assert_eq!(username_key("Username"), username_key("USERNAME"));
+        assert_eq!(username_key("ｕｓｅｒｎａｍｅ"), username_key("username"));
+        assert_eq!(username_key("Straße"), username_key("STRASSE"));
+        assert_eq!(username_key("ὈΔΥΣΣΕΎΣ"), username_key("ὀδυσσεύς"));
+        assert_eq!(username_key("ᾼ"), username_key("αι"));
+        assert_eq!(username_key("ꭰ"), username_key("Ꭰ"));
+        assert_eq!(username_key("user\u{AD}name"), "username");
+        assert_eq!(
+            find_confusable("раураl", vec!["username", "paypal"]),
+            Err(UsernameError::Confusable("paypal".to_string()))
+        );
+        assert_eq!(find_confusable("Дмитрий", vec!["paypal"]), Ok(()));
+    }
+
+    #[test]
+    fn test_user_account_lifecycle() {
+        let mut user = create_user(
+            VALID_USERNAME.to_string(),
+            VALID_EMAIL.to_string(),
+            VALID_PASSWORD.to_string(),
+        )
+        .unwrap();
+        assert_eq!(user.status(), &AccountStatus::PendingVerification);
+        assert_eq!(
+            user.lock(),
+            Err(AccountError::IllegalTransition {
+                from: AccountStatus::PendingVerification,
+                to: AccountStatus::Locked,
+            })
+        );
+
+        let token = user.verification_token().cloned().unwrap();
+        assert_eq!(user.verify_email(&token.token, Utc::now()), Ok(()));
+        assert_eq!(user.status(), &AccountStatus::Active);
+        assert_eq!(user.verification_token(), None);
+
+        assert_eq!(user.deactivate(), Ok(()));
+        assert_eq!(user.status(), &AccountStatus::Deactivated);
+        assert_eq!(user.reactivate(), Ok(()));
+        assert_eq!(user.lock(), Ok(()));
+        assert_eq!(user.unlock(), Ok(()));
+
+        assert_eq!(user.delete(), Ok(()));
+        assert_eq!(user.status(), &AccountStatus::Deleted);
+        assert_eq!(user.password, "");
+        assert_eq!(
+            user.reactivate(),
+            Err(AccountError::IllegalTransition {
+                from: AccountStatus::Deleted,
+                to: AccountStatus::Active,
+            })
+        );
+    }
+
+    #[test]
+    fn test_create_user_error_wraps_account_errors() {
+        let error = CreateUserError::from(AccountError::TokenMissing);
+
+        assert_eq!(error.code(), "user.account");
+        assert_eq!(
+            error.to_string(),
+            "Could not set up the account: No verification token has been issued"
+        );
+        assert!(std::error::Error::source(&error).is_some());
+    }
+
+    #[test]
+    fn test_user_verification_token_expiry() {
+        let issued_at = Utc::now();
+        let mut user = User::new(
+            VALID_USERNAME.to_string(),
+            VALID_EMAIL.to_string(),
+            VALID_PASSWORD.to_string(),
+        );
+        assert_eq!(
+            user.verify_email("token", issued_at),
+            Err(AccountError::TokenMissing)
+        );
+
+        let token = user
+            .issue_verification_token(issued_at)
+            .unwrap()
+            .token
+            .clone();
+        let expired_at = issued_at + Duration::hours(VERIFICATION_TOKEN_TTL_HOURS);
+        assert_eq!(
+            user.verify_email(&token, expired_at),
+            Err(AccountError::TokenExpired)
+        );
+
+        let new_token = user
+            .issue_verification_token(expired_at)
+            .unwrap()
+            .token
+            .clone();
+        assert_eq!(
+            user.verify_email(&token, expired_at),
+            Err(AccountError::TokenInvalid)
+        );
+        let prefix = &new_token[..new_token.len() - 1];
+        assert_eq!(
+            user.verify_email(prefix, expired_at),
+            Err(AccountError::TokenInvalid)
+        );
+        assert_eq!(
+            user.verify_email(&format!("{}!", prefix), expired_at),
+            Err(AccountError::TokenInvalid)
+        );
+        assert_eq!(user.verify_email(&new_token, expired_at), Ok(()));
+        assert_eq!(
+            user.verify_email(&new_token, expired_at),
+            Err(AccountError::IllegalTransition {
+                from: AccountStatus::Active,
+                to: AccountStatus::Active,
+            })
+        );
+    }
+
+    #[test]
+    fn test_import_users_csv() {
+        let data = "\
+email,username,password
+test@test.com,username,password
+other@test.com,user name,pass
+TEST@test.com,another_user,password
+\"quoted@test.com\",\"ｕｓｅｒｎａｍｅ\",password
+too_few_columns
+last@test.com,last_user,password
+";
+
+        let report = import_users_csv(data.as_bytes()).unwrap();
+
+        let imported: Vec<&str> = report.users.iter().map(|u| u.username.as_str()).collect();
+        assert_eq!(imported, vec!["username", "last_user"]);
+        assert!(!report.is_success());
+        assert_eq!(
+            report.failures,
+            vec![
+                ImportFailure::new(3, ImportField::Username, CreateUserError::InvalidUsername),
+                ImportFailure::new(3, ImportField::Password, CreateUserError::InvalidPassword),
+                ImportFailure::new(4, ImportField::Email, CreateUserError::DuplicateEmail),
+                ImportFailure::new(5, ImportField::Username, CreateUserError::DuplicateUsername),
+                ImportFailure::new(6, ImportField::Row, CreateUserError::InvalidUserData),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_users_csv_missing_column() {
+        let data = "username,password\nusername,password\n";
+        assert_eq!(
+            import_users_csv(data.as_bytes()).unwrap_err(),
+            ImportError::MissingColumn("email")
+        );
+    }
 }
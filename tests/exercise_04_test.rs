@@ -1,4 +1,7 @@
+use chrono::{Duration, TimeZone, Utc};
+use clean_code_notes_exercises::error::{Error, ErrorCode};
 use clean_code_notes_exercises::exercises::exercise_04::*;
+use clean_code_notes_exercises::retry::*;
 
 #[cfg(test)]
 mod exercise_04_test {
@@ -6,176 +9,2037 @@ mod exercise_04_test {
 
     #[test]
     fn test_payment_transaction_process() {
-        let open_payment_transaction = Transaction::new(
+        let mut open_payment_transaction = Transaction::new(
             String::from("t1"),
             TransactionType::Payment,
-            TransactionStatus::Open,
+            TransactionStatus::Pending,
             PaymentMethod::CreditCard,
-            23.99,
+            Money::new(2399, Currency::USD),
         );
 
         assert_eq!(
             open_payment_transaction.process(),
-            Ok("Processing payment credit card for amount: 23.99".to_string())
+            Ok("Processing payment credit card for amount: 23.99 USD".to_string())
         );
 
-        let closed_payment_transaction = Transaction::new(
+        let mut closed_payment_transaction = Transaction::new(
             String::from("t2"),
             TransactionType::Payment,
-            TransactionStatus::Closed,
+            TransactionStatus::Settled,
             PaymentMethod::PayPal,
-            100.43,
+            Money::new(10043, Currency::USD),
         );
 
         assert_eq!(
             closed_payment_transaction.process(),
-            Err(TransactionError::Closed(Some(
+            Err(TransactionError::Closed(Some(Box::new(
                 closed_payment_transaction.clone()
-            )))
+            ))))
         );
     }
 
     #[test]
     fn test_refund_transaction_process() {
-        let open_refund_transaction = Transaction::new(
+        let mut open_refund_transaction = Transaction::new(
             String::from("t1"),
             TransactionType::Refund,
-            TransactionStatus::Open,
+            TransactionStatus::Pending,
             PaymentMethod::CreditCard,
-            23.99,
+            Money::new(2399, Currency::USD),
         );
 
         assert_eq!(
             open_refund_transaction.process(),
-            Ok("Processing refund credit card for amount: 23.99".to_string())
+            Ok("Processing refund credit card for amount: 23.99 USD".to_string())
         );
 
-        let closed_refund_transaction = Transaction::new(
+        let mut closed_refund_transaction = Transaction::new(
             String::from("t2"),
             TransactionType::Refund,
-            TransactionStatus::Closed,
+            TransactionStatus::Settled,
             PaymentMethod::PayPal,
-            100.43,
+            Money::new(10043, Currency::USD),
         );
 
         assert_eq!(
             closed_refund_transaction.process(),
-            Err(TransactionError::Closed(Some(
+            Err(TransactionError::Closed(Some(Box::new(
                 closed_refund_transaction.clone()
-            )))
+            ))))
         );
     }
 
     #[test]
     fn test_process_transactions() {
-        let empty_transactions: Vec<Transaction> = vec![];
+        let mut empty_transactions: Vec<Transaction> = vec![];
         assert_eq!(
-            process_transactions(&empty_transactions),
+            process_transactions(&mut empty_transactions),
             Err(TransactionError::Empty)
         );
 
-        let all_transaction_types = vec![
+        let mut all_transaction_types = vec![
             Transaction::new(
                 String::from("t1"),
                 TransactionType::Payment,
-                TransactionStatus::Open,
+                TransactionStatus::Pending,
                 PaymentMethod::CreditCard,
-                23.99,
+                Money::new(2399, Currency::USD),
             ),
             Transaction::new(
                 String::from("t2"),
                 TransactionType::Payment,
-                TransactionStatus::Closed,
+                TransactionStatus::Settled,
                 PaymentMethod::CreditCard,
-                100.43,
+                Money::new(10043, Currency::USD),
             ),
             Transaction::new(
                 String::from("t3"),
                 TransactionType::Refund,
-                TransactionStatus::Open,
+                TransactionStatus::Pending,
                 PaymentMethod::CreditCard,
-                23.99,
+                Money::new(2399, Currency::USD),
             ),
             Transaction::new(
                 String::from("t4"),
                 TransactionType::Refund,
-                TransactionStatus::Closed,
+                TransactionStatus::Settled,
                 PaymentMethod::CreditCard,
-                100.43,
+                Money::new(10043, Currency::USD),
             ),
             Transaction::new(
                 String::from("t5"),
                 TransactionType::Payment,
-                TransactionStatus::Open,
+                TransactionStatus::Pending,
                 PaymentMethod::PayPal,
-                23.99,
+                Money::new(2399, Currency::USD),
             ),
             Transaction::new(
                 String::from("t6"),
                 TransactionType::Payment,
-                TransactionStatus::Closed,
+                TransactionStatus::Settled,
                 PaymentMethod::PayPal,
-                100.43,
+                Money::new(10043, Currency::USD),
             ),
             Transaction::new(
                 String::from("t7"),
                 TransactionType::Refund,
-                TransactionStatus::Open,
+                TransactionStatus::Pending,
                 PaymentMethod::PayPal,
-                23.99,
+                Money::new(2399, Currency::USD),
             ),
             Transaction::new(
                 String::from("t8"),
                 TransactionType::Refund,
-                TransactionStatus::Closed,
+                TransactionStatus::Settled,
                 PaymentMethod::PayPal,
-                100.43,
+                Money::new(10043, Currency::USD),
             ),
             Transaction::new(
                 String::from("t9"),
                 TransactionType::Payment,
-                TransactionStatus::Open,
+                TransactionStatus::Pending,
                 PaymentMethod::Plan,
-                23.99,
+                Money::new(2399, Currency::USD),
             ),
             Transaction::new(
                 String::from("t10"),
                 TransactionType::Payment,
-                TransactionStatus::Closed,
+                TransactionStatus::Settled,
                 PaymentMethod::Plan,
-                100.43,
+                Money::new(10043, Currency::USD),
             ),
             Transaction::new(
                 String::from("t11"),
                 TransactionType::Refund,
-                TransactionStatus::Open,
+                TransactionStatus::Pending,
                 PaymentMethod::Plan,
-                23.99,
+                Money::new(2399, Currency::USD),
             ),
             Transaction::new(
                 String::from("t12"),
                 TransactionType::Refund,
-                TransactionStatus::Closed,
+                TransactionStatus::Settled,
                 PaymentMethod::Plan,
-                100.43,
+                Money::new(10043, Currency::USD),
             ),
         ];
 
         let expected = vec![
-            "Processing payment credit card for amount: 23.99".to_string(),
-            "Your payment is already closed | Transaction: id: t2, type: payment, status: closed, method: credit card, amount: 100.43".to_string(),
-            "Processing refund credit card for amount: 23.99".to_string(),
-            "Your refund is already closed | Transaction: id: t4, type: refund, status: closed, method: credit card, amount: 100.43".to_string(),
-            "Processing payment PayPal for amount: 23.99".to_string(),
-            "Your payment is already closed | Transaction: id: t6, type: payment, status: closed, method: PayPal, amount: 100.43".to_string(),
-            "Processing refund PayPal for amount: 23.99".to_string(),
-            "Your refund is already closed | Transaction: id: t8, type: refund, status: closed, method: PayPal, amount: 100.43".to_string(),
-            "Processing payment plan for amount: 23.99".to_string(),
-            "Your payment is already closed | Transaction: id: t10, type: payment, status: closed, method: plan, amount: 100.43".to_string(),
-            "Processing refund plan for amount: 23.99".to_string(),
-            "Your refund is already closed | Transaction: id: t12, type: refund, status: closed, method: plan, amount: 100.43".to_string(),
+            "Processing payment credit card for amount: 23.99 USD".to_string(),
+            "Your payment is already closed | Transaction: id: t2, type: payment, status: settled, method: credit card, amount: 100.43 USD".to_string(),
+            "Processing refund credit card for amount: 23.99 USD".to_string(),
+            "Your refund is already closed | Transaction: id: t4, type: refund, status: settled, method: credit card, amount: 100.43 USD".to_string(),
+            "Processing payment PayPal for amount: 23.99 USD".to_string(),
+            "Your payment is already closed | Transaction: id: t6, type: payment, status: settled, method: PayPal, amount: 100.43 USD".to_string(),
+            "Processing refund PayPal for amount: 23.99 USD".to_string(),
+            "Your refund is already closed | Transaction: id: t8, type: refund, status: settled, method: PayPal, amount: 100.43 USD".to_string(),
+            "Processing payment plan for amount: 23.99 USD".to_string(),
+            "Your payment is already closed | Transaction: id: t10, type: payment, status: settled, method: plan, amount: 100.43 USD".to_string(),
+            "Processing refund plan for amount: 23.99 USD".to_string(),
+            "Your refund is already closed | Transaction: id: t12, type: refund, status: settled, method: plan, amount: 100.43 USD".to_string(),
+        ];
+
+        assert_eq!(
+            process_transactions(&mut all_transaction_types)
+                .map(|report| format_results(&report.results)),
+            Ok(expected)
+        );
+    }
+
+    #[test]
+    fn test_money_exact_arithmetic() {
+        let tenth = Money::parse("0.1", Currency::USD).unwrap();
+        let fifth = Money::parse("0.2", Currency::USD).unwrap();
+        assert_eq!(tenth.checked_add(&fifth), "0.30 USD".parse());
+        assert_eq!(
+            fifth.checked_sub(&tenth).unwrap().checked_mul(3),
+            Ok(Money::new(30, Currency::USD))
+        );
+        assert_eq!(
+            Money::new(i64::MAX, Currency::USD).checked_add(&tenth),
+            Err(MoneyError::Overflow)
+        );
+        assert_eq!(
+            tenth.checked_add(&Money::new(10, Currency::EUR)),
+            Err(MoneyError::CurrencyMismatch(Currency::USD, Currency::EUR))
+        );
+        assert_eq!(
+            tenth.mul_ratio(1, 0, RoundingMode::HalfUp),
+            Err(MoneyError::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn test_money_parsing_and_minor_units() {
+        assert_eq!(
+            Money::parse("1500", Currency::JPY),
+            Ok(Money::new(1500, Currency::JPY))
+        );
+        assert_eq!(
+            Money::parse("15.5", Currency::JPY),
+            Err(MoneyError::TooPrecise("15.5".to_string(), Currency::JPY))
+        );
+        assert_eq!(
+            Money::parse("-2.5", Currency::KWD),
+            Ok(Money::new(-2500, Currency::KWD))
+        );
+        assert_eq!(
+            Money::parse("12.3.4", Currency::USD),
+            Err(MoneyError::InvalidAmount("12.3.4".to_string()))
+        );
+        assert_eq!(
+            "10.00 XYZ".parse::<Money>(),
+            Err(MoneyError::UnknownCurrency("XYZ".to_string()))
+        );
+        assert_eq!(format!("{}", Money::new(-5, Currency::USD)), "-0.05 USD");
+        assert_eq!(format!("{}", Money::new(1250, Currency::KWD)), "1.250 KWD");
+    }
+
+    #[test]
+    fn test_money_rounding_modes() {
+        let cases = vec![
+            (RoundingMode::HalfUp, "2.345", 235),
+            (RoundingMode::HalfEven, "2.345", 234),
+            (RoundingMode::HalfEven, "2.355", 236),
+            (RoundingMode::Down, "2.349", 234),
+            (RoundingMode::Up, "2.341", 235),
+            (RoundingMode::Floor, "-2.341", -235),
+            (RoundingMode::Ceiling, "-2.349", -234),
+        ];
+
+        for (mode, amount, expected) in cases {
+            assert_eq!(
+                Money::parse_rounded(amount, Currency::USD, mode),
+                Ok(Money::new(expected, Currency::USD)),
+                "{:?} {}",
+                mode,
+                amount
+            );
+        }
+    }
+
+    #[test]
+    fn test_exchange_rate_conversion() {
+        let rates = StaticRateTable::new()
+            .with_rate(Currency::USD, Currency::JPY, "151.25")
+            .unwrap()
+            .with_rate(Currency::JPY, Currency::KWD, "0.00203")
+            .unwrap();
+
+        assert_eq!(
+            rates.convert(
+                &Money::new(1999, Currency::USD),
+                Currency::JPY,
+                RoundingMode::HalfUp
+            ),
+            Ok(Money::new(3023, Currency::JPY))
+        );
+        assert_eq!(
+            rates.convert(
+                &Money::new(10000, Currency::JPY),
+                Currency::KWD,
+                RoundingMode::HalfUp
+            ),
+            Ok(Money::new(20300, Currency::KWD))
+        );
+        assert_eq!(
+            rates.convert(
+                &Money::new(10, Currency::MXN),
+                Currency::MXN,
+                RoundingMode::HalfUp
+            ),
+            Ok(Money::new(10, Currency::MXN))
+        );
+        assert_eq!(
+            rates.convert(
+                &Money::new(10, Currency::JPY),
+                Currency::USD,
+                RoundingMode::HalfUp
+            ),
+            Err(MoneyError::MissingRate(Currency::JPY, Currency::USD))
+        );
+        assert_eq!(
+            ExchangeRate::parse(Currency::USD, Currency::EUR, "-1"),
+            Err(MoneyError::InvalidAmount("-1".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_process_transactions_normalized() {
+        let mut transactions = vec![
+            Transaction::new(
+                String::from("t1"),
+                TransactionType::Payment,
+                TransactionStatus::Pending,
+                PaymentMethod::CreditCard,
+                Money::new(2000, Currency::EUR),
+            ),
+            Transaction::new(
+                String::from("t2"),
+                TransactionType::Payment,
+                TransactionStatus::Settled,
+                PaymentMethod::CreditCard,
+                Money::new(9999, Currency::EUR),
+            ),
+            Transaction::new(
+                String::from("t3"),
+                TransactionType::Payment,
+                TransactionStatus::Pending,
+                PaymentMethod::PayPal,
+                Money::new(1500, Currency::JPY),
+            ),
+            Transaction::new(
+                String::from("t4"),
+                TransactionType::Refund,
+                TransactionStatus::Pending,
+                PaymentMethod::Plan,
+                Money::new(1000, Currency::MXN),
+            ),
+        ];
+        assert_eq!(transactions[2].currency(), Currency::JPY);
+
+        let rates = StaticRateTable::new()
+            .with_rate(Currency::EUR, Currency::MXN, "18.5")
+            .unwrap();
+        let report =
+            process_transactions_normalized(&mut transactions, Currency::MXN, &rates).unwrap();
+
+        assert_eq!(report.results.len(), 4);
+        assert_eq!(report.payments, Money::new(37000, Currency::MXN));
+        assert_eq!(report.refunds, Money::new(1000, Currency::MXN));
+        assert_eq!(report.net(), Ok(Money::new(36000, Currency::MXN)));
+        assert_eq!(report.missing_rates, vec!["t3".to_string()]);
+        assert_eq!(
+            process_transactions_normalized(&mut [], Currency::MXN, &rates),
+            Err(TransactionError::Empty)
+        );
+    }
+
+    fn payment_with_status(id: &str, status: TransactionStatus) -> Transaction {
+        Transaction::new(
+            String::from(id),
+            TransactionType::Payment,
+            status,
+            PaymentMethod::CreditCard,
+            Money::new(2399, Currency::USD),
+        )
+    }
+
+    fn pending_payment(id: &str) -> Transaction {
+        payment_with_status(id, TransactionStatus::Pending)
+    }
+
+    fn authorized_payment(id: &str) -> Transaction {
+        payment_with_status(id, TransactionStatus::Authorized)
+    }
+
+    #[test]
+    fn test_transaction_lifecycle() {
+        let mut transaction = pending_payment("t1");
+
+        for expected in [
+            TransactionStatus::Authorized,
+            TransactionStatus::Captured,
+            TransactionStatus::Settled,
+        ] {
+            assert!(transaction.process().is_ok());
+            assert_eq!(transaction.status(), &expected);
+        }
+        assert_eq!(
+            transaction.process(),
+            Err(TransactionError::Closed(Some(Box::new(
+                transaction.clone()
+            ))))
+        );
+
+        assert_eq!(transaction.dispute(), Ok(()));
+        assert_eq!(
+            transaction.process(),
+            Err(TransactionError::IllegalTransition {
+                id: String::from("t1"),
+                from: TransactionStatus::Disputed,
+                to: TransactionStatus::Settled,
+            })
+        );
+        assert_eq!(transaction.refund(), Ok(()));
+        assert!(transaction.status().is_closed());
+    }
+
+    #[test]
+    fn test_transaction_illegal_transitions() {
+        let mut authorized = pending_payment("t1");
+        authorized.authorize().unwrap();
+        assert_eq!(
+            authorized.refund(),
+            Err(TransactionError::IllegalTransition {
+                id: String::from("t1"),
+                from: TransactionStatus::Authorized,
+                to: TransactionStatus::Refunded,
+            })
+        );
+        assert_eq!(authorized.void(), Ok(()));
+        assert_eq!(
+            authorized.capture(),
+            Err(TransactionError::IllegalTransition {
+                id: String::from("t1"),
+                from: TransactionStatus::Voided,
+                to: TransactionStatus::Captured,
+            })
+        );
+
+        let mut failed = pending_payment("t2");
+        assert_eq!(failed.fail(), Ok(()));
+        assert_eq!(
+            failed.process(),
+            Err(TransactionError::Closed(Some(Box::new(failed.clone()))))
+        );
+        assert_eq!(failed.status(), &TransactionStatus::Failed);
+    }
+
+    #[test]
+    fn test_partial_and_multiple_refunds() {
+        let mut payment = pending_payment("t1");
+        assert_eq!(
+            payment.issue_refund(String::from("r0"), Money::new(100, Currency::USD)),
+            Err(TransactionError::IllegalTransition {
+                id: String::from("t1"),
+                from: TransactionStatus::Pending,
+                to: TransactionStatus::Refunded,
+            })
+        );
+        payment.authorize().unwrap();
+        payment.capture().unwrap();
+
+        let first = payment
+            .issue_refund(String::from("r1"), Money::new(999, Currency::USD))
+            .unwrap();
+        let second = payment
+            .issue_refund(String::from("r2"), Money::new(400, Currency::USD))
+            .unwrap();
+        assert_eq!(first.refund_of(), Some("t1"));
+        assert_eq!(second.method, PaymentMethod::CreditCard);
+        assert_eq!(second.status(), &TransactionStatus::Pending);
+        assert_eq!(payment.refunded(), Money::new(1399, Currency::USD));
+        assert_eq!(payment.refundable(), Money::new(1000, Currency::USD));
+        assert_eq!(payment.status(), &TransactionStatus::Captured);
+
+        assert_eq!(
+            payment.issue_refund(String::from("r3"), Money::new(1001, Currency::USD)),
+            Err(TransactionError::OverRefund {
+                id: String::from("t1"),
+                requested: Money::new(1001, Currency::USD),
+                refundable: Money::new(1000, Currency::USD),
+            })
+        );
+        assert_eq!(
+            payment.issue_refund(String::from("r3"), Money::new(10, Currency::EUR)),
+            Err(TransactionError::Amount(MoneyError::CurrencyMismatch(
+                Currency::USD,
+                Currency::EUR
+            )))
+        );
+        assert_eq!(
+            payment.issue_refund(String::from("r3"), Money::new(0, Currency::USD)),
+            Err(TransactionError::Amount(MoneyError::InvalidAmount(
+                "0.00 USD".to_string()
+            )))
+        );
+
+        payment
+            .issue_refund(String::from("r3"), Money::new(1000, Currency::USD))
+            .unwrap();
+        assert_eq!(payment.status(), &TransactionStatus::Refunded);
+        assert!(payment.refundable().is_zero());
+    }
+
+    #[test]
+    fn test_process_transactions_structured_results() {
+        let settled = payment_with_status("t2", TransactionStatus::Settled);
+        let mut transactions = vec![
+            pending_payment("t1"),
+            settled,
+            Transaction::new(
+                String::from("t3"),
+                TransactionType::Refund,
+                TransactionStatus::Pending,
+                PaymentMethod::PayPal,
+                Money::new(500, Currency::USD),
+            ),
+            Transaction::new(
+                String::from("t4"),
+                TransactionType::Payment,
+                TransactionStatus::Pending,
+                PaymentMethod::PayPal,
+                Money::new(700, Currency::EUR),
+            ),
+        ];
+
+        let report = process_transactions(&mut transactions).unwrap();
+
+        assert_eq!(report.results[0].id, "t1");
+        assert_eq!(
+            report.results[0].outcome,
+            TransactionOutcome::Processed {
+                message: "Processing payment credit card for amount: 23.99 USD".to_string(),
+                status: TransactionStatus::Authorized,
+            }
+        );
+        assert!(!report.results[1].is_processed());
+        assert!(matches!(
+            report.results[1].error(),
+            Some(TransactionError::Closed(Some(_)))
+        ));
+
+        assert_eq!(report.summary.processed, 3);
+        assert_eq!(report.summary.rejected, 1);
+        assert_eq!(report.summary.total(), 4);
+        assert_eq!(
+            report
+                .summary
+                .total_for_type(&TransactionType::Payment, Currency::USD),
+            Money::new(2399, Currency::USD)
+        );
+        assert_eq!(
+            report
+                .summary
+                .total_for_type(&TransactionType::Payment, Currency::EUR),
+            Money::new(700, Currency::EUR)
+        );
+        assert_eq!(
+            report
+                .summary
+                .total_for_method(&PaymentMethod::PayPal, Currency::USD),
+            Money::new(500, Currency::USD)
+        );
+        assert_eq!(
+            report
+                .summary
+                .total_for_method(&PaymentMethod::Plan, Currency::USD),
+            Money::zero(Currency::USD)
+        );
+    }
+
+    #[test]
+    fn test_processor_registry_dispatches_by_method() {
+        let mut registry = ProcessorRegistry::simulated();
+        let mut card = pending_payment("t1");
+
+        assert_eq!(
+            registry.process(&mut card),
+            Ok(String::from(
+                "Processing payment credit card for amount: 23.99 USD | reference: CARD-000001"
+            ))
+        );
+        assert_eq!(
+            registry.process(&mut card),
+            Ok(String::from(
+                "Processing payment credit card for amount: 23.99 USD | reference: CARD-000002"
+            ))
+        );
+        assert_eq!(card.status(), &TransactionStatus::Captured);
+        assert!(registry.process(&mut card).is_ok());
+        assert_eq!(card.status(), &TransactionStatus::Settled);
+
+        let refund = registry
+            .refund(
+                &mut card,
+                String::from("r1"),
+                Money::new(399, Currency::USD),
+            )
+            .unwrap();
+        assert_eq!(refund.refund_of(), Some("t1"));
+        assert_eq!(
+            refund.metadata.get("reference"),
+            Some(&String::from("CARD-R000001"))
+        );
+        assert_eq!(card.refundable(), Money::new(2000, Currency::USD));
+
+        let mut unknown = pending_payment("t2");
+        unknown.method = PaymentMethod::Other(String::from("crypto"));
+        assert_eq!(
+            registry.process(&mut unknown),
+            Err(TransactionError::Processor(ProcessorError::NotRegistered(
+                PaymentMethod::Other(String::from("crypto"))
+            )))
+        );
+        assert_eq!(unknown.status(), &TransactionStatus::Pending);
+    }
+
+    #[test]
+    fn test_process_transactions_with_registry_sends_refunds_to_the_gateway() {
+        let mut registry = ProcessorRegistry::simulated();
+        let mut store = MemoryStore::new();
+        let fees = FeeTable::default();
+        let mut payment = [pending_payment("t1")];
+        let submitted = payment.clone();
+        for _ in 0..2 {
+            process_transactions_with_registry(&mut payment, &mut store, &fees, &mut registry)
+                .unwrap();
+        }
+        assert_eq!(payment[0].status(), &TransactionStatus::Captured);
+
+        let first = payment[0]
+            .issue_refund(String::from("r1"), Money::new(399, Currency::USD))
+            .unwrap();
+        let mut refunds = [
+            first.clone(),
+            payment[0]
+                .issue_refund(String::from("r2"), Money::new(1000, Currency::USD))
+                .unwrap(),
         ];
+        let report =
+            process_transactions_with_registry(&mut refunds, &mut store, &fees, &mut registry)
+                .unwrap();
+        assert_eq!(
+            format_results(&report.results),
+            vec![
+                "Processing refund credit card for amount: 3.99 USD | reference: CARD-R000001",
+                "Processing refund credit card for amount: 10.00 USD | reference: CARD-R000002",
+            ]
+        );
+        assert_eq!(refunds[0].status(), &TransactionStatus::Authorized);
+
+        // Replays don't reach the gateway, the next step only changes the status
+        let replays = process_transactions_with_registry(
+            &mut [submitted[0].clone(), first],
+            &mut store,
+            &fees,
+            &mut registry,
+        )
+        .unwrap();
+        assert_eq!(replays.summary.replayed, 2);
+        let report =
+            process_transactions_with_registry(&mut refunds, &mut store, &fees, &mut registry)
+                .unwrap();
+        assert_eq!(report.summary.processed, 2);
+        assert_eq!(
+            format_results(&report.results)[0],
+            "Processing refund credit card for amount: 3.99 USD"
+        );
+
+        // Captured outside the gateway, so it has nothing to reverse
+        let mut orphan = [payment_with_status("t2", TransactionStatus::Captured)
+            .issue_refund(String::from("r3"), Money::new(100, Currency::USD))
+            .unwrap()];
+        let report =
+            process_transactions_with_registry(&mut orphan, &mut store, &fees, &mut registry)
+                .unwrap();
+        assert_eq!(
+            report.results[0].error(),
+            Some(&TransactionError::Processor(ProcessorError::Declined {
+                id: String::from("t2"),
+                reason: String::from("nothing was captured"),
+            }))
+        );
+        assert_eq!(orphan[0].status(), &TransactionStatus::Pending);
+    }
+
+    #[test]
+    fn test_processor_declines_leave_transaction_untouched() {
+        let mut registry = ProcessorRegistry::new();
+        registry.register(
+            PaymentMethod::CreditCard,
+            SimulatedGateway::new("CARD").with_credit_limit(Money::new(2000, Currency::USD)),
+        );
+        let mut gateway = SimulatedGateway::new("PAYPAL");
+        gateway.online = false;
+        registry.register(PaymentMethod::PayPal, gateway);
+
+        let mut over_limit = pending_payment("t1");
+        assert_eq!(
+            registry.process(&mut over_limit),
+            Err(TransactionError::Processor(ProcessorError::Declined {
+                id: String::from("t1"),
+                reason: String::from("over the credit limit of 20.00 USD"),
+            }))
+        );
+        assert_eq!(over_limit.status(), &TransactionStatus::Pending);
+
+        let mut offline = pending_payment("t2");
+        offline.method = PaymentMethod::PayPal;
+        assert_eq!(
+            registry.process(&mut offline),
+            Err(TransactionError::Processor(ProcessorError::Unavailable(
+                String::from("PAYPAL")
+            )))
+        );
+        assert_eq!(offline.status(), &TransactionStatus::Pending);
+    }
+
+    #[test]
+    fn test_simulated_gateway_refunds() {
+        let mut gateway = SimulatedGateway::new("PLAN");
+        assert_eq!(
+            gateway.capture("t1"),
+            Err(ProcessorError::UnknownAuthorization(String::from("t1")))
+        );
+
+        gateway.hold("t1", Money::new(1000, Currency::EUR)).unwrap();
+        assert_eq!(gateway.held("t1"), Some(Money::new(1000, Currency::EUR)));
+        gateway.capture("t1").unwrap();
+        assert_eq!(gateway.held("t1"), None);
+
+        assert_eq!(
+            gateway.reverse("t1", "r1", Money::new(600, Currency::EUR)),
+            Ok(String::from("PLAN-R000001"))
+        );
+        // The same refund sent again is not reversed twice
+        assert_eq!(
+            gateway.reverse("t1", "r1", Money::new(600, Currency::EUR)),
+            Ok(String::from("PLAN-R000001"))
+        );
+        assert!(gateway
+            .reverse("t1", "r2", Money::new(600, Currency::EUR))
+            .is_err());
+        assert_eq!(gateway.reversed("t1"), Some(Money::new(600, Currency::EUR)));
+    }
+
+    fn plan_payment(minor: i64) -> Transaction {
+        let mut payment = pending_payment("t1");
+        payment.method = PaymentMethod::Plan;
+        payment.amount = Money::new(minor, Currency::USD);
+        payment
+    }
+
+    #[test]
+    fn test_installment_plan_interest_fee_and_remainder() {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let mut terms = PlanTerms::new(4, Frequency::Biweekly);
+        terms.interest_basis_points = 500;
+        terms.fee = Some(Money::new(199, Currency::USD));
+        terms.remainder = Remainder::Last;
+
+        let plan = InstallmentPlan::new(&plan_payment(10000), &terms, start).unwrap();
+
+        assert_eq!(plan.total, Money::new(10699, Currency::USD));
+        let amounts: Vec<i64> = plan.installments.iter().map(|i| i.amount.minor).collect();
+        assert_eq!(amounts, vec![2674, 2674, 2674, 2677]);
+        assert_eq!(
+            plan.installments[3].due,
+            Utc.with_ymd_and_hms(2024, 4, 26, 0, 0, 0).unwrap()
+        );
+        assert_eq!(plan.remaining(), Ok(plan.total));
+
+        assert_eq!(
+            InstallmentPlan::new(&pending_payment("t2"), &terms, start),
+            Err(InstallmentError::NotAPlan(PaymentMethod::CreditCard))
+        );
+        assert_eq!(
+            InstallmentPlan::new(
+                &plan_payment(10000),
+                &PlanTerms::new(0, Frequency::Weekly),
+                start
+            ),
+            Err(InstallmentError::NoInstallments)
+        );
+    }
+
+    #[test]
+    fn test_installment_plan_overdue_and_default() {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let mut terms = PlanTerms::new(2, Frequency::Weekly);
+        terms.grace_period = Duration::days(2);
+        terms.default_after = Duration::days(10);
+        let mut plan = InstallmentPlan::new(&plan_payment(5000), &terms, start).unwrap();
+
+        plan.refresh(start + Duration::days(8));
+        assert_eq!(plan.installments[0].status, InstallmentStatus::Scheduled);
+
+        plan.refresh(start + Duration::days(10));
+        assert_eq!(plan.with_status(InstallmentStatus::Overdue).len(), 1);
+        assert_eq!(
+            plan.pay(
+                1,
+                Money::new(1000, Currency::USD),
+                start + Duration::days(10)
+            ),
+            Err(InstallmentError::WrongAmount {
+                number: 1,
+                expected: Money::new(2500, Currency::USD),
+                paid: Money::new(1000, Currency::USD),
+            })
+        );
+        plan.pay(
+            1,
+            Money::new(2500, Currency::USD),
+            start + Duration::days(10),
+        )
+        .unwrap();
+        assert_eq!(
+            plan.pay(
+                1,
+                Money::new(2500, Currency::USD),
+                start + Duration::days(10)
+            ),
+            Err(InstallmentError::AlreadyPaid(1))
+        );
+
+        assert_eq!(
+            plan.pay(
+                2,
+                Money::new(2500, Currency::USD),
+                start + Duration::days(30)
+            ),
+            Err(InstallmentError::Defaulted(2))
+        );
+        assert!(plan.is_defaulted());
+        assert!(!plan.is_paid_off());
+        assert_eq!(plan.remaining(), Ok(Money::new(2500, Currency::USD)));
+    }
+
+    #[test]
+    fn test_process_transactions_deduplicates_ids() {
+        let mut transactions = vec![pending_payment("t1"), pending_payment("t1")];
+
+        let report = process_transactions(&mut transactions).unwrap();
+
+        assert_eq!(report.summary.processed, 1);
+        assert_eq!(report.summary.replayed, 1);
+        assert_eq!(
+            report
+                .summary
+                .total_for_type(&TransactionType::Payment, Currency::USD),
+            Money::new(2399, Currency::USD)
+        );
+        assert_eq!(
+            format_results(&report.results),
+            vec![
+                "Processing payment credit card for amount: 23.99 USD".to_string(),
+                "Processing payment credit card for amount: 23.99 USD".to_string(),
+            ]
+        );
+        assert_eq!(transactions[0].status(), &TransactionStatus::Authorized);
+        assert_eq!(transactions[1].status(), &TransactionStatus::Pending);
+    }
+
+    #[test]
+    fn test_process_transactions_with_store_moves_a_payment_step_by_step() {
+        let mut store = MemoryStore::new();
+        let mut payment = [pending_payment("t1")];
+        let submitted_pending = payment[0].clone();
+
+        for expected in [
+            TransactionStatus::Authorized,
+            TransactionStatus::Captured,
+            TransactionStatus::Settled,
+        ] {
+            let report = process_transactions_with_store(&mut payment, &mut store).unwrap();
+            assert_eq!(report.summary.processed, 1);
+            assert_eq!(payment[0].status(), &expected);
+        }
+        // One record per step, and one for the first submission
+        assert_eq!(store.len(), 4);
+
+        let replay =
+            process_transactions_with_store(&mut [submitted_pending.clone()], &mut store).unwrap();
+        assert_eq!(replay.summary.replayed, 1);
+
+        let mut changed = submitted_pending;
+        changed.amount = Money::new(100, Currency::USD);
+        let conflict = process_transactions_with_store(&mut [changed], &mut store).unwrap();
+        assert_eq!(
+            conflict.results[0].error(),
+            Some(&TransactionError::IdempotencyConflict(String::from("t1")))
+        );
+
+        let settled = process_transactions_with_store(&mut payment, &mut store).unwrap();
+        assert!(matches!(
+            settled.results[0].error(),
+            Some(TransactionError::Closed(_))
+        ));
+    }
+
+    #[test]
+    fn test_process_transactions_with_store_rejects_changes_at_a_later_step() {
+        let mut store = MemoryStore::new();
+        let mut payment = [pending_payment("t1")];
+        process_transactions_with_store(&mut payment, &mut store).unwrap();
+        assert_eq!(payment[0].status(), &TransactionStatus::Authorized);
+
+        let mut changed = payment.clone();
+        changed[0].amount = Money::new(999_999, Currency::USD);
+        let conflict = process_transactions_with_store(&mut changed, &mut store).unwrap();
+        assert_eq!(
+            conflict.results[0].error(),
+            Some(&TransactionError::IdempotencyConflict(String::from("t1")))
+        );
+        assert_eq!(changed[0].status(), &TransactionStatus::Authorized);
+
+        changed[0].amount = payment[0].amount;
+        changed[0].method = PaymentMethod::PayPal;
+        let conflict = process_transactions_with_store(&mut changed, &mut store).unwrap();
+        assert_eq!(conflict.summary.rejected, 1);
+
+        let captured = process_transactions_with_store(&mut payment, &mut store).unwrap();
+        assert_eq!(captured.summary.processed, 1);
+        assert_eq!(payment[0].status(), &TransactionStatus::Captured);
+    }
+
+    /// Store that accepts a fixed number of writes and fails after that
+    struct FailingStore {
+        store: MemoryStore,
+        writes_left: usize,
+    }
+
+    impl IdempotencyStore for FailingStore {
+        fn get(&self, key: &str) -> Result<Option<IdempotencyRecord>, StoreError> {
+            self.store.get(key)
+        }
+
+        fn put(&mut self, key: &str, record: IdempotencyRecord) -> Result<(), StoreError> {
+            if self.writes_left == 0 {
+                return Err(StoreError::Io(std::sync::Arc::new(std::io::Error::other(
+                    "disk full",
+                ))));
+            }
+            self.writes_left -= 1;
+            self.store.put(key, record)
+        }
+    }
+
+    #[test]
+    fn test_process_transactions_with_store_reports_completed_on_store_failure() {
+        // Two records per new transaction, the second transaction's step fails
+        let mut store = FailingStore {
+            store: MemoryStore::new(),
+            writes_left: 3,
+        };
+        let mut batch = [
+            pending_payment("t1"),
+            pending_payment("t2"),
+            pending_payment("t3"),
+        ];
+
+        let error = process_transactions_with_store(&mut batch, &mut store).unwrap_err();
+        match &error {
+            TransactionError::Interrupted { completed, .. } => {
+                assert_eq!(completed.len(), 1);
+                assert_eq!(completed[0].id, "t1");
+            }
+            other => panic!("expected an interrupted batch, got {:?}", other),
+        }
+        assert_eq!(error.code(), "transaction.interrupted");
+        assert_eq!(
+            error.to_string(),
+            "Processing stopped after 1 transactions: Idempotency store I/O error: disk full"
+        );
+        assert_eq!(batch[0].status(), &TransactionStatus::Authorized);
+        assert_eq!(batch[1].status(), &TransactionStatus::Pending);
+        assert_eq!(batch[2].status(), &TransactionStatus::Pending);
+
+        // Retrying once the store recovers picks up where the batch stopped
+        store.writes_left = usize::MAX;
+        let report = process_transactions_with_store(&mut batch, &mut store).unwrap();
+        assert_eq!(report.summary.processed, 3);
+        assert_eq!(batch[0].status(), &TransactionStatus::Captured);
+        assert_eq!(batch[1].status(), &TransactionStatus::Authorized);
+    }
+
+    #[test]
+    fn test_file_store_survives_reopening() {
+        let path = std::env::temp_dir().join("exercise_04_file_store_test.log");
+        let _ = std::fs::remove_file(&path);
+
+        let mut store = FileStore::open(&path).unwrap();
+        let closed = payment_with_status("t2", TransactionStatus::Settled);
+        let first = process_transactions_with_store(
+            &mut [pending_payment("t1"), closed.clone()],
+            &mut store,
+        )
+        .unwrap();
+        assert_eq!(first.summary.processed, 1);
+        assert_eq!(first.summary.rejected, 1);
+
+        let mut reopened = FileStore::open(&path).unwrap();
+        let mut changed = pending_payment("t1");
+        changed.method = PaymentMethod::PayPal;
+        let replay =
+            process_transactions_with_store(&mut [closed, changed], &mut reopened).unwrap();
+
+        assert_eq!(replay.summary.replayed, 1);
+        assert_eq!(replay.results[0].to_string(), first.results[1].to_string());
+        assert_eq!(
+            replay.results[1].error(),
+            Some(&TransactionError::IdempotencyConflict(String::from("t1")))
+        );
+
+        std::fs::write(&path, "t1\tbroken\n").unwrap();
+        assert_eq!(
+            FileStore::open(&path).unwrap_err(),
+            StoreError::Corrupt { line: 1 }
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_ledger_posts_transaction_lifecycle() {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let mut ledger = Ledger::new();
+        let mut payment = pending_payment("t1");
+
+        payment.process().unwrap();
+        assert_eq!(ledger.post_transaction(&payment, start), Ok(false));
+        payment.process().unwrap();
+        assert_eq!(ledger.post_transaction(&payment, start), Ok(true));
+        assert_eq!(
+            ledger.post_transaction(&payment, start),
+            Err(LedgerError::DuplicatePosting(String::from("t1:captured")))
+        );
+
+        let mut refund = payment
+            .issue_refund(String::from("r1"), Money::new(399, Currency::USD))
+            .unwrap();
+        refund.authorize().unwrap();
+        refund.capture().unwrap();
+        ledger
+            .post_transaction(&refund, start + Duration::days(1))
+            .unwrap();
+        payment.settle().unwrap();
+        ledger
+            .post_transaction(&payment, start + Duration::days(2))
+            .unwrap();
+        refund.settle().unwrap();
+        ledger
+            .post_transaction(&refund, start + Duration::days(2))
+            .unwrap();
+
+        let end = start + Duration::days(2);
+        assert_eq!(
+            ledger.balance(CASH_ACCOUNT, Currency::USD, end),
+            Ok(Money::new(2000, Currency::USD))
+        );
+        assert_eq!(
+            ledger.balance(
+                "clearing:credit card",
+                Currency::USD,
+                start + Duration::days(1)
+            ),
+            Ok(Money::new(2000, Currency::USD))
+        );
+        assert_eq!(
+            ledger.balance("clearing:credit card", Currency::USD, end),
+            Ok(Money::zero(Currency::USD))
+        );
+
+        let trial_balance = ledger.trial_balance(end).unwrap();
+        assert!(trial_balance.is_balanced());
+        assert_eq!(
+            trial_balance
+                .lines
+                .iter()
+                .map(|line| line.account.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["cash", "clearing:credit card", "refunds", "revenue"]
+        );
+    }
+
+    #[test]
+    fn test_ledger_rejects_unbalanced_postings() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let mut ledger = Ledger::new();
+
+        assert_eq!(
+            ledger.post(Posting::new("p1", now)),
+            Err(LedgerError::NoEntries(String::from("p1")))
+        );
+        assert_eq!(
+            ledger.post(
+                Posting::new("p2", now)
+                    .debit(CASH_ACCOUNT, Money::new(100, Currency::USD))
+                    .credit(REVENUE_ACCOUNT, Money::new(100, Currency::EUR))
+            ),
+            Err(LedgerError::Unbalanced {
+                posting: String::from("p2"),
+                currency: Currency::USD,
+            })
+        );
+        assert_eq!(
+            ledger.post(
+                Posting::new("p3", now)
+                    .debit(CASH_ACCOUNT, Money::new(-100, Currency::USD))
+                    .credit(REVENUE_ACCOUNT, Money::new(-100, Currency::USD))
+            ),
+            Err(LedgerError::NotPositive {
+                posting: String::from("p3"),
+                amount: Money::new(-100, Currency::USD),
+            })
+        );
+        assert!(ledger.postings().is_empty());
+    }
+
+    #[test]
+    fn test_process_stream_matches_process_transactions() {
+        let closed = payment_with_status("t2", TransactionStatus::Settled);
+        let batch = vec![pending_payment("t1"), closed, pending_payment("t1")];
+
+        let mut store = MemoryStore::new();
+        let streamed: Vec<ProcessedTransaction> =
+            process_stream(batch.clone(), &mut store, &FeeTable::default())
+                .collect::<Result<Vec<ProcessedTransaction>, StoreError>>()
+                .unwrap();
+        let report = process_transactions(&mut batch.clone()).unwrap();
+
+        assert_eq!(
+            streamed
+                .iter()
+                .map(|processed| processed.result.clone())
+                .collect::<Vec<TransactionResult>>(),
+            report.results
+        );
+        assert_eq!(
+            streamed[0].transaction.status(),
+            &TransactionStatus::Authorized
+        );
+        // A submission and a step record for each of the two ids
+        assert_eq!(store.len(), 4);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_process_transactions_parallel_matches_sequential() {
+        let mut batch: Vec<Transaction> = (0..1_000)
+            .map(|n| {
+                let status = match n % 11 {
+                    0 => TransactionStatus::Settled,
+                    _ => TransactionStatus::Pending,
+                };
+                let mut transaction = payment_with_status(&format!("t{}", n % 900), status);
+                transaction.amount = Money::new(n % 7 + 1, Currency::USD);
+                transaction
+            })
+            .collect();
+        let submitted = batch.clone();
+        let mut sequential = batch.clone();
+        let fees = FeeTable::new()
+            .with_schedule(
+                PaymentMethod::CreditCard,
+                FeeSchedule::new(Money::new(30, Currency::USD), 290),
+            )
+            .with_tax(TaxRule::new("Sales tax", 500, TaxBase::Amount));
+        let mut parallel_store = MemoryStore::new();
+        let mut sequential_store = MemoryStore::new();
+
+        // Authorizes, then captures with fees, then replays the first submissions
+        for _ in 0..2 {
+            let parallel_report =
+                process_transactions_parallel(&mut batch, &mut parallel_store, &fees).unwrap();
+            let sequential_report =
+                process_transactions_with_fees(&mut sequential, &mut sequential_store, &fees)
+                    .unwrap();
+            assert_eq!(parallel_report, sequential_report);
+        }
+        let parallel_replays =
+            process_transactions_parallel(&mut submitted.clone(), &mut parallel_store, &fees)
+                .unwrap();
+        let sequential_replays =
+            process_transactions_with_fees(&mut submitted.clone(), &mut sequential_store, &fees)
+                .unwrap();
+        assert_eq!(parallel_replays, sequential_replays);
+        assert!(parallel_replays.summary.replayed > 0);
+        assert_eq!(parallel_store.len(), sequential_store.len());
+        // Processing stamps each status change with the time it happened
+        let without_times = |transactions: &[Transaction]| {
+            transactions
+                .iter()
+                .map(|t| {
+                    let changes: Vec<_> = t
+                        .audit_log()
+                        .iter()
+                        .map(|change| (change.from.clone(), change.to.clone()))
+                        .collect();
+                    (t.id.clone(), t.status().clone(), t.refunded(), changes)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(without_times(&batch), without_times(&sequential));
+        assert_eq!(
+            process_transactions_parallel(&mut [], &mut MemoryStore::new(), &fees),
+            Err(TransactionError::Empty)
+        );
+    }
+
+    fn mixed_transactions() -> Vec<Transaction> {
+        let mut payment = pending_payment("t1")
+            .with_customer("cust42")
+            .with_metadata("order", "1001, \"gift\"");
+        payment.authorize().unwrap();
+        payment
+            .change_status(
+                TransactionStatus::Captured,
+                "ops@shop.com",
+                "Shipped",
+                Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap(),
+            )
+            .unwrap();
+        let refund = payment
+            .issue_refund(String::from("r1"), Money::new(399, Currency::USD))
+            .unwrap();
+        let gift_card = Transaction::new(
+            String::from("t2"),
+            TransactionType::Payment,
+            TransactionStatus::Pending,
+            PaymentMethod::Other(String::from("Gift Card")),
+            Money::new(1500, Currency::JPY),
+        );
+        vec![payment, refund, gift_card]
+    }
+
+    #[test]
+    fn test_transactions_json_round_trip() {
+        let transactions = mixed_transactions();
+
+        let json = transactions_to_json(&transactions).unwrap();
+
+        assert!(json.contains(r#""status":"captured""#));
+        assert!(json.contains(r#""method":"credit card""#));
+        assert!(json.contains(r#""method":"Gift Card""#));
+        assert_eq!(transactions_from_json(&json), Ok(transactions));
+        let error = transactions_from_json(r#"[{"id":"t1"}]"#).unwrap_err();
+        assert!(matches!(error, FormatError::Json(_)));
+        assert!(std::error::Error::source(&error).is_some());
+        // Dropping the history is not the same as having none
+        let without_history = json.replace(r#","audit_log":[]"#, "");
+        assert_ne!(without_history, json);
+        assert!(matches!(
+            transactions_from_json(&without_history),
+            Err(FormatError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn test_transactions_csv_round_trip() {
+        let transactions = mixed_transactions();
+        let mut csv = vec![];
+
+        export_transactions_csv(&transactions, &mut csv).unwrap();
+
+        let text = String::from_utf8(csv.clone()).unwrap();
+        assert!(text.starts_with(
+            "id,transaction_type,status,method,amount,refund_of,refunded,\
+             created_at,updated_at,customer,description,metadata,audit_log\n"
+        ));
+        assert!(text.contains("r1,refund,pending,credit card,3.99 USD,t1,0.00 USD"));
+        assert_eq!(import_transactions_csv(csv.as_slice()), Ok(transactions));
+        assert!(matches!(
+            import_transactions_csv("id,transaction_type\nt1,transfer\n".as_bytes()),
+            Err(FormatError::Csv(_))
+        ));
+        // The history can't be dropped, nor disagree with the status
+        let without_history = "\
+            id,transaction_type,status,method,amount,refund_of,refunded\n\
+            t1,payment,settled,credit card,23.99 USD,,0.00 USD\n";
+        assert!(matches!(
+            import_transactions_csv(without_history.as_bytes()),
+            Err(FormatError::Csv(_))
+        ));
+        let error = import_transactions_csv(
+            text.replace("t1,payment,captured,", "t1,payment,settled,")
+                .as_bytes(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error,
+            FormatError::InconsistentStatus {
+                id: String::from("t1"),
+                status: TransactionStatus::Settled,
+                logged: TransactionStatus::Captured,
+            }
+        );
+        assert_eq!(error.code(), "format.inconsistent_status");
+    }
+
+    #[test]
+    fn test_pain001_round_trip() {
+        let header = Pain001Header {
+            message_id: String::from("MSG-<1>"),
+            created_at: Utc.with_ymd_and_hms(2024, 3, 1, 9, 30, 0).unwrap(),
+            initiating_party: String::from("Clean & Code"),
+            debtor: String::from("Clean & Code"),
+            debtor_iban: String::from("ES9121000418450200051332"),
+        };
+        let payments: Vec<Transaction> = mixed_transactions()
+            .into_iter()
+            .filter(|transaction| transaction.transaction_type == TransactionType::Payment)
+            .map(|payment| {
+                Transaction::new(
+                    payment.id,
+                    payment.transaction_type,
+                    TransactionStatus::Pending,
+                    payment.method,
+                    payment.amount,
+                )
+                .with_created_at(header.created_at)
+            })
+            .collect();
+
+        let xml = export_pain001(&header, &payments).unwrap();
+
+        assert!(xml.contains("<MsgId>MSG-&lt;1&gt;</MsgId>"));
+        assert!(xml.contains("<NbOfTxs>2</NbOfTxs>"));
+        assert!(xml.contains("<CtrlSum>1523.99</CtrlSum>"));
+        assert!(xml.contains(r#"<InstdAmt Ccy="JPY">1500</InstdAmt>"#));
+        assert_eq!(import_pain001(&xml), Ok(payments));
+
+        let reversal = [Transaction::new(
+            String::from("t3"),
+            TransactionType::Payment,
+            TransactionStatus::Pending,
+            PaymentMethod::PayPal,
+            Money::new(-50, Currency::EUR),
+        )
+        .with_created_at(header.created_at)];
+        let reversal_xml = export_pain001(&header, &reversal).unwrap();
+        assert!(reversal_xml.contains("<CtrlSum>-0.5</CtrlSum>"));
+        assert_eq!(import_pain001(&reversal_xml), Ok(reversal.to_vec()));
+
+        assert_eq!(
+            export_pain001(&header, &mixed_transactions()),
+            Err(FormatError::NotAPayment(String::from("r1")))
+        );
+        assert_eq!(export_pain001(&header, &[]), Err(FormatError::NoPayments));
+        let error = import_pain001("<Document>").unwrap_err();
+        assert!(matches!(error, FormatError::Xml(_)));
+        assert!(std::error::Error::source(&error).is_some());
+        assert_eq!(
+            import_pain001(&xml.replace(r#"Ccy="JPY""#, r#"Ccy="XYZ""#)),
+            Err(FormatError::InvalidElement {
+                name: "Ccy",
+                value: String::from("XYZ")
+            })
+        );
+    }
+
+    #[test]
+    fn test_risk_engine_amount_thresholds_and_bursts() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let mut engine = RiskEngine::new(vec![
+            RiskRule::AmountThreshold {
+                method: PaymentMethod::PayPal,
+                limit: Money::new(10000, Currency::USD),
+                decision: RiskDecision::Review,
+            },
+            RiskRule::DuplicateAmountBurst {
+                scope: RiskScope::IdPrefix('-'),
+                max: 2,
+                window: Duration::minutes(5),
+                decision: RiskDecision::Deny,
+            },
+        ]);
+
+        let mut large = pending_payment("bob-1");
+        large.method = PaymentMethod::PayPal;
+        large.amount = Money::new(25000, Currency::USD);
+        let (assessment, _) = engine.process(&mut large, now).unwrap();
+        let assessment = assessment.unwrap();
+        assert_eq!(assessment.decision, RiskDecision::Review);
+        assert_eq!(
+            assessment.triggered[0].reason,
+            "250.00 USD is over the PayPal limit of 100.00 USD"
+        );
+        assert_eq!(large.status(), &TransactionStatus::Authorized);
+
+        let card = pending_payment("bob-2");
+        assert_eq!(engine.assess(&card, now).decision, RiskDecision::Allow);
+        assert_eq!(engine.assess(&card, now).decision, RiskDecision::Allow);
+        assert_eq!(
+            engine.screen(&pending_payment("bob-3"), now),
+            Err(TransactionError::RiskDenied {
+                id: String::from("bob-3"),
+                rules: vec![String::from("duplicate amount burst")],
+            })
+        );
+        assert_eq!(
+            engine.assess(&pending_payment("alice-1"), now).decision,
+            RiskDecision::Allow
+        );
+        assert_eq!(
+            engine
+                .assess(&pending_payment("bob-4"), now + Duration::minutes(6))
+                .decision,
+            RiskDecision::Allow
+        );
+    }
+
+    #[test]
+    fn test_risk_engine_reports_every_triggered_rule() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let mut engine = RiskEngine::default();
+        engine.add_rule(RiskRule::Velocity {
+            scope: RiskScope::Method,
+            max: 1,
+            window: Duration::hours(1),
+            decision: RiskDecision::Review,
+        });
+        engine.add_rule(RiskRule::Blocklist {
+            scope: RiskScope::Method,
+            entries: vec![String::from("plan")],
+        });
+
+        engine.assess(&pending_payment("t1"), now);
+        let assessment = engine.assess(&pending_payment("t2"), now);
+        assert_eq!(assessment.decision, RiskDecision::Review);
+        assert_eq!(
+            assessment.triggered[0].reason,
+            "2 transactions for credit card within 60 minutes"
+        );
+
+        let mut plan = pending_payment("t3");
+        plan.method = PaymentMethod::Plan;
+        engine.assess(&plan, now);
+        let assessment = engine.assess(&plan, now);
+        assert_eq!(assessment.decision, RiskDecision::Deny);
+        assert_eq!(assessment.rule_names(), vec!["velocity", "blocklist"]);
+        assert_eq!(
+            TransactionError::RiskDenied {
+                id: String::from("t3"),
+                rules: assessment.rule_names(),
+            }
+            .to_string(),
+            "Transaction t3 was denied by risk rules: velocity, blocklist"
+        );
+    }
+
+    #[test]
+    fn test_risk_engine_screens_a_payment_once_across_its_steps() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let mut engine = RiskEngine::new(vec![
+            RiskRule::Velocity {
+                scope: RiskScope::IdPrefix('-'),
+                max: 1,
+                window: Duration::minutes(10),
+                decision: RiskDecision::Deny,
+            },
+            RiskRule::DuplicateAmountBurst {
+                scope: RiskScope::IdPrefix('-'),
+                max: 1,
+                window: Duration::minutes(10),
+                decision: RiskDecision::Deny,
+            },
+        ]);
+        let mut payment = pending_payment("alice-1");
+
+        let (assessment, _) = engine.process(&mut payment, now).unwrap();
+        assert_eq!(assessment.map(|a| a.decision), Some(RiskDecision::Allow));
+        for _ in 0..2 {
+            let (assessment, _) = engine.process(&mut payment, now).unwrap();
+            assert_eq!(assessment, None);
+        }
+
+        assert_eq!(payment.status(), &TransactionStatus::Settled);
+        assert_eq!(engine.remembered(), 1);
+    }
+
+    #[test]
+    fn test_risk_engine_forgets_attempts_out_of_every_window() {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let mut engine = RiskEngine::new(vec![
+            RiskRule::Velocity {
+                scope: RiskScope::IdPrefix('-'),
+                max: 3,
+                window: Duration::minutes(10),
+                decision: RiskDecision::Review,
+            },
+            RiskRule::DuplicateAmountBurst {
+                scope: RiskScope::IdPrefix('-'),
+                max: 5,
+                window: Duration::minutes(30),
+                decision: RiskDecision::Deny,
+            },
+        ]);
+
+        for minute in 0..1_000 {
+            let now = start + Duration::minutes(minute);
+            let payment = pending_payment(&format!("cust{}-t{}", minute % 100, minute));
+            assert_eq!(engine.assess(&payment, now).decision, RiskDecision::Allow);
+        }
+        // Every customer pays once every 100 minutes, the longest window is 30,
+        // so most of the 100 customers are forgotten
+        assert!(engine.remembered() <= 64);
+
+        let now = start + Duration::minutes(1_000);
+        for n in 0..3 {
+            engine.assess(&pending_payment(&format!("eve-{}", n)), now);
+        }
+        assert_eq!(
+            engine.assess(&pending_payment("eve-3"), now).decision,
+            RiskDecision::Review
+        );
+        let later = now + Duration::minutes(10);
+        assert_eq!(
+            engine.assess(&pending_payment("eve-4"), later).decision,
+            RiskDecision::Allow
+        );
+    }
+
+    #[test]
+    fn test_reconcile_with_tolerance_and_mismatches() {
+        let day = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let mut payment = pending_payment("t1")
+            .with_customer("cust42")
+            .with_metadata("order", "1001, \"gift\"");
+        payment.authorize().unwrap();
+        payment
+            .change_status(
+                TransactionStatus::Captured,
+                "ops@shop.com",
+                "Shipped",
+                Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap(),
+            )
+            .unwrap();
+        let refund = payment
+            .issue_refund(String::from("r1"), Money::new(399, Currency::USD))
+            .unwrap();
+        let ours = vec![payment, refund, pending_payment("t2")];
+        let statement = vec![
+            StatementLine {
+                reference: Some(String::from("r1")),
+                amount: Money::new(-399, Currency::USD),
+                settled_at: day,
+            },
+            StatementLine {
+                reference: Some(String::from("t1")),
+                amount: Money::new(2398, Currency::USD),
+                settled_at: day,
+            },
+            StatementLine {
+                reference: Some(String::from("t2")),
+                amount: Money::new(2399, Currency::EUR),
+                settled_at: day,
+            },
+        ];
+        let config = ReconciliationConfig {
+            amount_tolerance: 1,
+            ..ReconciliationConfig::default()
+        };
+
+        let report = reconcile(&ours, &statement, &config, |_| day).unwrap();
+
+        assert_eq!(
+            report.matched,
+            vec![
+                Match {
+                    transaction_id: String::from("r1"),
+                    line: 0,
+                    difference: 0,
+                    fuzzy: false,
+                },
+                Match {
+                    transaction_id: String::from("t1"),
+                    line: 1,
+                    difference: -1,
+                    fuzzy: false,
+                },
+            ]
+        );
+        assert_eq!(
+            report.mismatched,
+            vec![Mismatch {
+                transaction_id: String::from("t2"),
+                line: 2,
+                expected: Money::new(2399, Currency::USD),
+                found: Money::new(2399, Currency::EUR),
+            }]
+        );
+        assert!(report.missing.is_empty());
+        assert!(!report.is_clean());
+
+        let strict = reconcile(
+            &ours[..2],
+            &statement[..2],
+            &ReconciliationConfig::default(),
+            |_| day,
+        )
+        .unwrap();
+        assert_eq!(strict.mismatched.len(), 1);
+
+        let unsettleable = Transaction::new(
+            String::from("r2"),
+            TransactionType::Refund,
+            TransactionStatus::Settled,
+            PaymentMethod::CreditCard,
+            Money::new(i64::MIN, Currency::USD),
+        );
+        assert_eq!(settled_amount(&unsettleable), Err(MoneyError::Overflow));
+        assert_eq!(
+            reconcile(&[unsettleable], &statement, &config, |_| day),
+            Err(MoneyError::Overflow)
+        );
+
+        // -1 - i64::MAX is i64::MIN, whose absolute value doesn't fit an i64
+        let mut largest = pending_payment("t3");
+        largest.amount = Money::new(i64::MAX, Currency::USD);
+        let overdrawn = StatementLine {
+            reference: None,
+            amount: Money::new(-1, Currency::USD),
+            settled_at: day,
+        };
+        let lenient = ReconciliationConfig {
+            amount_tolerance: i64::MAX,
+            ..ReconciliationConfig::default()
+        };
+        let report = reconcile(
+            &[largest],
+            std::slice::from_ref(&overdrawn),
+            &lenient,
+            |_| day,
+        )
+        .unwrap();
+        assert!(report.matched.is_empty());
+        assert_eq!(report.extra, vec![overdrawn]);
+    }
+
+    #[test]
+    fn test_reconcile_fuzzy_matches_closest_date() {
+        let day = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let ours = vec![
+            pending_payment("t1"),
+            pending_payment("t2"),
+            pending_payment("t3"),
+        ];
+        let booked_at = |transaction: &Transaction| match transaction.id.as_str() {
+            "t1" => day,
+            "t2" => day + Duration::hours(20),
+            _ => day - Duration::days(5),
+        };
+        let csv = "\
+reference,amount,settled_at
+,23.99 USD,2024-03-01T21:00:00Z
+,23.99 USD,2024-03-01T01:00:00Z
+,23.99 USD,2024-03-01T02:00:00Z
+";
+        let statement = import_statement_csv(csv.as_bytes()).unwrap();
+
+        let report = reconcile(
+            &ours,
+            &statement,
+            &ReconciliationConfig::default(),
+            booked_at,
+        )
+        .unwrap();
+
+        let fuzzy: Vec<(&str, usize)> = report
+            .matched
+            .iter()
+            .map(|matched| (matched.transaction_id.as_str(), matched.line))
+            .collect();
+        assert_eq!(fuzzy, vec![("t2", 0), ("t1", 1)]);
+        assert_eq!(report.missing, vec![String::from("t3")]);
+        assert_eq!(report.extra, vec![statement[2].clone()]);
+    }
+
+    #[test]
+    fn test_audit_log_records_every_status_change() {
+        let created_at = Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap();
+        let shipped_at = created_at + Duration::hours(2);
+        let mut payment = pending_payment("t1")
+            .with_created_at(created_at)
+            .with_customer("cust42")
+            .with_description("Order 1001")
+            .with_metadata("channel", "web");
+
+        assert_eq!(payment.updated_at(), created_at);
+        assert!(payment.audit_log().is_empty());
+
+        payment.process().unwrap();
+        let captured = payment
+            .change_status(
+                TransactionStatus::Captured,
+                "ops@shop.com",
+                "Shipped",
+                shipped_at,
+            )
+            .unwrap()
+            .clone();
+        let illegal = payment.change_status(
+            TransactionStatus::Pending,
+            "ops@shop.com",
+            "Oops",
+            shipped_at,
+        );
+
+        assert_eq!(captured.from, TransactionStatus::Authorized);
+        assert_eq!(captured.reason, "Shipped");
+        assert!(matches!(
+            illegal,
+            Err(TransactionError::IllegalTransition { .. })
+        ));
+        assert_eq!(payment.audit_log().len(), 2);
+        assert_eq!(payment.audit_log()[0].actor, SYSTEM_ACTOR);
+        assert_eq!(payment.audit_log()[1], captured);
+        assert_eq!(payment.changes_by("ops@shop.com"), vec![&captured]);
+        assert_eq!(
+            payment.changed_to(&TransactionStatus::Captured),
+            Some(shipped_at)
+        );
+        assert_eq!(payment.changed_to(&TransactionStatus::Settled), None);
+        assert_eq!(payment.created_at, created_at);
+        assert_eq!(payment.updated_at(), shipped_at);
+        assert_eq!(payment.customer.as_deref(), Some("cust42"));
+        assert_eq!(
+            payment.metadata.get("channel").map(String::as_str),
+            Some("web")
+        );
+    }
+
+    #[test]
+    fn test_fees_and_taxes_per_payment_method() {
+        let fees = FeeTable::new()
+            .with_schedule(
+                PaymentMethod::CreditCard,
+                FeeSchedule::new(Money::new(30, Currency::USD), 290)
+                    .with_min(Money::new(50, Currency::USD))
+                    .with_max(Money::new(500, Currency::USD)),
+            )
+            .with_schedule(
+                PaymentMethod::CreditCard,
+                FeeSchedule::new(Money::new(25, Currency::EUR), 140),
+            )
+            .with_tax(TaxRule::new("VAT on fees", 2100, TaxBase::Fee).only_in(Currency::EUR));
+        let mut transactions = vec![
+            authorized_payment("small"),
+            authorized_payment("large"),
+            Transaction::new(
+                String::from("euro"),
+                TransactionType::Payment,
+                TransactionStatus::Authorized,
+                PaymentMethod::CreditCard,
+                Money::new(10000, Currency::EUR),
+            ),
+            authorized_payment("paypal"),
+            payment_with_status("closed", TransactionStatus::Settled),
+        ];
+        transactions[0].amount = Money::new(500, Currency::USD);
+        transactions[1].amount = Money::new(50000, Currency::USD);
+        transactions[3].method = PaymentMethod::PayPal;
+
+        let report =
+            process_transactions_with_fees(&mut transactions, &mut MemoryStore::new(), &fees)
+                .unwrap();
+        let charges: Vec<Option<Charges>> =
+            report.results.iter().map(|result| result.charges).collect();
+
+        // 30 + 2.9% of 5.00 is under the minimum
+        assert_eq!(charges[0].unwrap().fee, Money::new(50, Currency::USD));
+        // 30 + 2.9% of 500.00 is over the maximum
+        assert_eq!(charges[1].unwrap().fee, Money::new(500, Currency::USD));
+        assert_eq!(
+            charges[2],
+            Some(Charges {
+                gross: Money::new(10000, Currency::EUR),
+                fee: Money::new(165, Currency::EUR),
+                tax: Money::new(35, Currency::EUR),
+                net: Money::new(9800, Currency::EUR),
+            })
+        );
+        assert_eq!(charges[3].unwrap().fee, Money::zero(Currency::USD));
+        assert_eq!(charges[4], None);
+
+        let summary = &report.summary;
+        assert_eq!(
+            summary.total_fees(Currency::USD),
+            Money::new(550, Currency::USD)
+        );
+        assert_eq!(
+            summary.total_fees(Currency::EUR),
+            Money::new(165, Currency::EUR)
+        );
+        assert_eq!(
+            summary.total_taxes(Currency::USD),
+            Money::zero(Currency::USD)
+        );
+        assert_eq!(
+            summary.total_taxes(Currency::EUR),
+            Money::new(35, Currency::EUR)
+        );
+        assert_eq!(
+            summary.total_net(Currency::USD),
+            Money::new(500 + 50000 + 2399 - 550, Currency::USD)
+        );
+
+        let plain = process_transactions(&mut [authorized_payment("t1")]).unwrap();
+        assert_eq!(
+            plain.summary.total_net(Currency::USD),
+            Money::new(2399, Currency::USD)
+        );
+        assert_eq!(
+            plain.summary.total_fees(Currency::USD),
+            Money::zero(Currency::USD)
+        );
+    }
+
+    #[test]
+    fn test_fees_are_charged_once_per_payment() {
+        let fees = FeeTable::new()
+            .with_schedule(
+                PaymentMethod::CreditCard,
+                FeeSchedule::new(Money::new(30, Currency::USD), 290),
+            )
+            .with_tax(TaxRule::new("Sales tax", 500, TaxBase::Amount));
+        let mut store = MemoryStore::new();
+        let mut refund = pending_payment("r1");
+        refund.transaction_type = TransactionType::Refund;
+        let mut batch = vec![pending_payment("t1"), refund];
+
+        let mut total_fees = Money::zero(Currency::USD);
+        let mut total_taxes = Money::zero(Currency::USD);
+        for _ in 0..3 {
+            let report = process_transactions_with_fees(&mut batch, &mut store, &fees).unwrap();
+            assert_eq!(report.results[1].charges, None);
+            total_fees = total_fees
+                .checked_add(&report.summary.total_fees(Currency::USD))
+                .unwrap();
+            total_taxes = total_taxes
+                .checked_add(&report.summary.total_taxes(Currency::USD))
+                .unwrap();
+        }
+
+        assert_eq!(batch[0].status(), &TransactionStatus::Settled);
+        assert_eq!(batch[1].status(), &TransactionStatus::Settled);
+        assert_eq!(total_fees, Money::new(100, Currency::USD));
+        assert_eq!(total_taxes, Money::new(120, Currency::USD));
+
+        let streamed: Vec<ProcessedTransaction> =
+            process_stream(vec![authorized_payment("t2")], &mut store, &fees)
+                .collect::<Result<Vec<ProcessedTransaction>, StoreError>>()
+                .unwrap();
+        assert_eq!(
+            streamed[0].result.charges.unwrap().fee,
+            Money::new(100, Currency::USD)
+        );
+    }
+
+    #[test]
+    fn test_errors_chain_their_sources_and_codes() {
+        let mut payment = payment_with_status("t1", TransactionStatus::Captured);
+
+        let error = payment
+            .issue_refund(String::from("r1"), Money::new(100, Currency::EUR))
+            .unwrap_err();
+
+        assert_eq!(error.code(), "transaction.amount");
+        let source = std::error::Error::source(&error).unwrap();
+        assert_eq!(source.to_string(), "Currency mismatch: USD and EUR");
+
+        fn refund_everything(payment: &mut Transaction) -> Result<String, Error> {
+            let amount = payment.refundable();
+            let refund = payment.issue_refund(format!("{}-refund", payment.id), amount)?;
+            let json = transactions_to_json(&[refund])?;
+            Ok(json)
+        }
+        let error = refund_everything(&mut pending_payment("t2")).unwrap_err();
+        assert_eq!(error.code(), "transaction.illegal_transition");
+        assert!(matches!(
+            error,
+            Error::Transaction(TransactionError::IllegalTransition { .. })
+        ));
+        assert!(std::error::Error::source(&error).is_none());
+
+        let error = Error::from(TransactionError::Processor(ProcessorError::Unavailable(
+            String::from("CARD"),
+        )));
+        assert_eq!(error.code(), "transaction.processor");
+        assert_eq!(
+            std::error::Error::source(&error).map(|source| source.to_string()),
+            Some(ProcessorError::Unavailable(String::from("CARD")).to_string())
+        );
+
+        let io_error = std::io::Error::new(std::io::ErrorKind::TimedOut, "disk is slow");
+        let error = StoreError::from(io_error);
+        assert_eq!(error.code(), "store.io");
+        let source = std::error::Error::source(&error).unwrap();
+        assert_eq!(
+            source.downcast_ref::<std::io::Error>().map(|e| e.kind()),
+            Some(std::io::ErrorKind::TimedOut)
+        );
+    }
+
+    /// Unavailable until it has been called `outages` times
+    struct FlakyProcessor {
+        outages: u32,
+        calls: u32,
+    }
+
+    impl FlakyProcessor {
+        fn respond(&mut self) -> Result<String, ProcessorError> {
+            self.calls += 1;
+            match self.calls <= self.outages {
+                true => Err(ProcessorError::Unavailable(String::from("FLAKY"))),
+                false => Ok(format!("FLAKY-{}", self.calls)),
+            }
+        }
+    }
+
+    impl PaymentProcessor for FlakyProcessor {
+        fn authorize(&mut self, _: &Transaction) -> Result<String, ProcessorError> {
+            self.respond()
+        }
+
+        fn capture(&mut self, _: &Transaction) -> Result<String, ProcessorError> {
+            self.respond()
+        }
+
+        fn refund(&mut self, _: &Transaction) -> Result<String, ProcessorError> {
+            self.respond()
+        }
+    }
+
+    #[test]
+    fn test_process_with_retry_backs_off_while_the_gateway_is_down() {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+        let policy = RetryPolicy::new(4)
+            .with_backoff(Duration::milliseconds(100), 2)
+            .with_jitter(Jitter::None);
+        let mut registry = ProcessorRegistry::new();
+        registry.register(
+            PaymentMethod::CreditCard,
+            FlakyProcessor {
+                outages: 2,
+                calls: 0,
+            },
+        );
+
+        let clock = ManualClock::new(start);
+        let mut retry = Retry::new(policy.clone(), &clock);
+        let mut payment = pending_payment("t1");
+
+        let message = registry.process_with_retry(&mut payment, &mut retry);
+
+        assert_eq!(
+            message,
+            Ok(String::from(
+                "Processing payment credit card for amount: 23.99 USD | reference: FLAKY-3"
+            ))
+        );
+        assert_eq!(payment.status(), &TransactionStatus::Authorized);
+        assert_eq!(
+            clock.sleeps(),
+            vec![Duration::milliseconds(100), Duration::milliseconds(200)]
+        );
+
+        registry.register(
+            PaymentMethod::CreditCard,
+            FlakyProcessor {
+                outages: 10,
+                calls: 0,
+            },
+        );
+        let mut retry = Retry::new(policy.clone(), &clock);
+        let exhausted = registry
+            .process_with_retry(&mut payment, &mut retry)
+            .unwrap_err();
+        assert_eq!(exhausted.code(), "retry.exhausted");
+        assert_eq!(exhausted.attempts(), 4);
+        assert_eq!(payment.status(), &TransactionStatus::Authorized);
+
+        let clock = ManualClock::new(start);
+        let mut retry = Retry::new(policy.with_deadline(Duration::milliseconds(250)), &clock);
+        let late = registry
+            .process_with_retry(&mut payment, &mut retry)
+            .unwrap_err();
+        assert!(matches!(
+            late,
+            RetryError::DeadlineExceeded { attempts: 2, .. }
+        ));
+        assert_eq!(clock.now(), start + Duration::milliseconds(100));
+
+        let clock = ManualClock::new(start);
+        let mut retry = Retry::new(RetryPolicy::new(4), &clock);
+        let mut settled = payment_with_status("t3", TransactionStatus::Settled);
+        let permanent = registry
+            .process_with_retry(&mut settled, &mut retry)
+            .unwrap_err();
+        assert_eq!(permanent.attempts(), 1);
+        assert!(matches!(
+            permanent.into_inner(),
+            TransactionError::Closed(Some(_))
+        ));
+    }
+
+    #[test]
+    fn test_retry_jitter_stays_within_the_backoff_and_repeats_with_a_seed() {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+        let sleeps = |jitter: Jitter, seed: u64| {
+            let clock = ManualClock::new(start);
+            let policy = RetryPolicy::new(6)
+                .with_backoff(Duration::milliseconds(100), 2)
+                .with_max_delay(Duration::milliseconds(1000))
+                .with_jitter(jitter);
+            let mut retry = Retry::new(policy, &clock).with_seed(seed);
+            let result: Result<(), RetryError<&str>> = retry.run(|_| true, |_| Err("busy"));
+            assert_eq!(result.unwrap_err().attempts(), 6);
+            clock.sleeps()
+        };
+
+        let backoff = [100, 200, 400, 800, 1000].map(Duration::milliseconds);
+        let full = sleeps(Jitter::Full, 7);
+        let equal = sleeps(Jitter::Equal, 7);
+
+        assert_eq!(full, sleeps(Jitter::Full, 7));
+        assert_eq!(sleeps(Jitter::None, 7), backoff.to_vec());
+        for (retry, delay) in backoff.iter().enumerate() {
+            assert!(full[retry] >= Duration::zero() && full[retry] <= *delay);
+            assert!(equal[retry] >= *delay / 2 && equal[retry] <= *delay);
+        }
+
+        let negative = RetryPolicy::new(3).with_backoff(Duration::milliseconds(-100), 2);
+        assert_eq!(negative.backoff(1), Duration::zero());
+        assert_eq!(negative.backoff(2), Duration::zero());
+    }
+
+    #[test]
+    fn test_retry_backoff_saturates_instead_of_overflowing() {
+        let max_delay = Duration::seconds(30);
+        let negative = RetryPolicy::new(10)
+            .with_backoff(Duration::milliseconds(-1_000_000), u32::MAX)
+            .with_max_delay(max_delay);
+        let huge = RetryPolicy::new(10)
+            .with_backoff(Duration::milliseconds(1_000_000), u32::MAX)
+            .with_max_delay(max_delay);
 
-        assert_eq!(process_transactions(&all_transaction_types), Ok(expected));
+        for retry in [1, 2, 5, u32::MAX] {
+            assert_eq!(negative.backoff(retry), Duration::zero());
+        }
+        assert_eq!(huge.backoff(1), max_delay);
+        assert_eq!(huge.backoff(u32::MAX), max_delay);
     }
 }
@@ -1,3 +1,6 @@
+use chrono::{NaiveDate, TimeZone, Utc};
+use clean_code_notes_exercises::exercises::exercise_01::{Point, Rectangle};
+use clean_code_notes_exercises::exercises::exercise_04::{Currency, Money, MoneyError};
 use clean_code_notes_exercises::exercises::exercise_05::*;
 
 #[cfg(test)]
@@ -42,4 +45,330 @@ mod exercise_05_test {
         let track = delivery.track_product();
         assert_eq!(track, "Tracking standard delivery for A product");
     }
+
+    fn package(width: f64, depth: f64, height: f64, weight_kg: f64) -> Package {
+        Package::new(
+            Rectangle::new(Point::new(0.0, 0.0), width, depth),
+            height,
+            weight_kg,
+        )
+    }
+
+    #[test]
+    fn test_pricing_engine_per_delivery_type_and_zone() {
+        let euros = |minor| Money::new(minor, Currency::EUR);
+        let rates = RateTable::new(Currency::EUR)
+            .with_rate(DeliveryType::Standard, Zone::Local, euros(300), euros(50))
+            .unwrap()
+            .with_rate(
+                DeliveryType::Standard,
+                Zone::National,
+                euros(500),
+                euros(100),
+            )
+            .unwrap()
+            .with_rate(
+                DeliveryType::Express,
+                Zone::National,
+                euros(1200),
+                euros(150),
+            )
+            .unwrap()
+            .with_rate(
+                DeliveryType::Insured,
+                Zone::National,
+                euros(800),
+                euros(100),
+            )
+            .unwrap()
+            .with_insurance(200, euros(500))
+            .with_max_weight(20.0);
+        let engine = PricingEngine::new(rates);
+        let small = package(20.0, 15.0, 10.0, 1.25);
+
+        assert_eq!(Zone::for_distance(12.0), Zone::Local);
+        assert_eq!(Zone::for_distance(640.0), Zone::National);
+        assert_eq!(Zone::for_distance(2500.0), Zone::International);
+
+        let local = engine
+            .quote(&small, Zone::Local, &DeliveryType::Standard)
+            .unwrap();
+        assert_eq!(local.billable_grams, 1250);
+        assert_eq!(local.weight_charge, Money::new(63, Currency::EUR));
+        assert_eq!(local.total, Money::new(363, Currency::EUR));
+
+        let standard = engine
+            .quote(&small, Zone::National, &DeliveryType::Standard)
+            .unwrap();
+        let express = engine
+            .quote(&small, Zone::National, &DeliveryType::Express)
+            .unwrap();
+        assert_eq!(standard.total, Money::new(625, Currency::EUR));
+        assert_eq!(express.total, Money::new(1388, Currency::EUR));
+        assert_eq!(express.insurance, Money::zero(Currency::EUR));
+
+        let cheap = small
+            .clone()
+            .with_declared_value(Money::new(10000, Currency::EUR));
+        let valuable = small
+            .clone()
+            .with_declared_value(Money::new(100000, Currency::EUR));
+        let insured = |package: &Package| {
+            engine
+                .quote(package, Zone::National, &DeliveryType::Insured)
+                .map(|quote| quote.insurance)
+        };
+        assert_eq!(insured(&cheap), Ok(Money::new(500, Currency::EUR)));
+        assert_eq!(insured(&valuable), Ok(Money::new(2000, Currency::EUR)));
+        assert_eq!(insured(&small), Err(PricingError::MissingDeclaredValue));
+        assert_eq!(
+            insured(
+                &small
+                    .clone()
+                    .with_declared_value(Money::new(100, Currency::USD))
+            ),
+            Err(PricingError::Amount(MoneyError::CurrencyMismatch(
+                Currency::EUR,
+                Currency::USD
+            )))
+        );
+
+        assert_eq!(
+            engine.quote(&small, Zone::Regional, &DeliveryType::Standard),
+            Err(PricingError::NoRate {
+                delivery_type: DeliveryType::Standard,
+                zone: Zone::Regional,
+            })
+        );
+        assert_eq!(
+            RateTable::new(Currency::EUR)
+                .with_rate(
+                    DeliveryType::Express,
+                    Zone::Local,
+                    euros(900),
+                    Money::new(100, Currency::USD),
+                )
+                .unwrap_err(),
+            PricingError::Amount(MoneyError::CurrencyMismatch(Currency::EUR, Currency::USD))
+        );
+        assert_eq!(
+            engine.quote(
+                &package(0.0, 15.0, 10.0, 1.0),
+                Zone::Local,
+                &DeliveryType::Standard
+            ),
+            Err(PricingError::InvalidDimensions)
+        );
+        assert_eq!(
+            engine.quote(
+                &package(20.0, 15.0, 10.0, 25.0),
+                Zone::Local,
+                &DeliveryType::Standard
+            ),
+            Err(PricingError::Overweight {
+                weight_kg: 25.0,
+                max_kg: 20.0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_pricing_bills_bulky_packages_by_volume() {
+        let engine = PricingEngine::new(
+            RateTable::new(Currency::EUR)
+                .with_rate(
+                    DeliveryType::Standard,
+                    Zone::Local,
+                    Money::new(300, Currency::EUR),
+                    Money::new(100, Currency::EUR),
+                )
+                .unwrap(),
+        );
+        let pillow = package(60.0, 50.0, 40.0, 1.0);
+
+        let quote = engine
+            .quote(&pillow, Zone::Local, &DeliveryType::Standard)
+            .unwrap();
+
+        assert_eq!(quote.billable_grams, 24000);
+        assert_eq!(quote.total, Money::new(300 + 2400, Currency::EUR));
+
+        let lenient = PricingEngine::new(engine.rates.clone().with_volumetric_divisor(8000.0));
+        let quote = lenient
+            .quote(&pillow, Zone::Local, &DeliveryType::Standard)
+            .unwrap();
+        assert_eq!(quote.billable_grams, 15000);
+    }
+
+    #[test]
+    fn test_shipment_timeline() {
+        let at = |day, hour| Utc.with_ymd_and_hms(2024, 3, day, hour, 0, 0).unwrap();
+        let delivery = DeliveryInsured {
+            base: Delivery::new("A product".to_string()),
+        };
+        let mut shipment = delivery.ship_product("TRK1", at(1, 9));
+
+        shipment
+            .record(TrackingEvent::new(TrackingStatus::PickedUp, at(1, 16)).with_location("Lyon"))
+            .unwrap();
+        shipment
+            .record(
+                TrackingEvent::new(TrackingStatus::Exception, at(5, 11))
+                    .with_location("Paris")
+                    .with_note("Nobody home"),
+            )
+            .unwrap();
+        // Reported late by the carrier
+        shipment
+            .record(TrackingEvent::new(TrackingStatus::InTransit, at(4, 7)).with_location("Dijon"))
+            .unwrap();
+
+        let statuses: Vec<TrackingStatus> =
+            shipment.events().iter().map(|event| event.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                TrackingStatus::LabelCreated,
+                TrackingStatus::PickedUp,
+                TrackingStatus::InTransit,
+                TrackingStatus::Exception,
+            ]
+        );
+        assert_eq!(shipment.status(), TrackingStatus::Exception);
+        assert_eq!(
+            shipment.estimated_delivery(),
+            NaiveDate::from_ymd_opt(2024, 3, 6).unwrap()
+        );
+        assert_eq!(
+            delivery.track_shipment(&shipment),
+            "Tracking insured delivery for A product: TRK1: exception in Paris, expected on 2024-03-06"
+        );
+
+        shipment
+            .record(TrackingEvent::new(TrackingStatus::OutForDelivery, at(6, 8)))
+            .unwrap();
+        shipment
+            .record(TrackingEvent::new(TrackingStatus::Delivered, at(6, 14)).with_location("Paris"))
+            .unwrap();
+
+        assert!(shipment.is_delivered());
+        assert_eq!(shipment.status(), TrackingStatus::Delivered);
+        assert_eq!(shipment.summary(), "TRK1: delivered on 2024-03-06 in Paris");
+        // Once the delivery has its shipment, tracking goes through it
+        let delivery = DeliveryInsured {
+            base: delivery.base.with_shipment(shipment.clone()),
+        };
+        assert_eq!(
+            delivery.track_product(),
+            "Tracking insured delivery for A product: TRK1: delivered on 2024-03-06 in Paris"
+        );
+        assert_eq!(
+            shipment.record(TrackingEvent::new(TrackingStatus::InTransit, at(7, 9))),
+            Err(TrackingError::AlreadyDelivered {
+                delivered_at: at(6, 14)
+            })
+        );
+        assert_eq!(
+            shipment.record(TrackingEvent::new(TrackingStatus::Delivered, at(5, 9))),
+            Err(TrackingError::AlreadyDelivered {
+                delivered_at: at(6, 14)
+            })
+        );
+        assert_eq!(
+            shipment.record(TrackingEvent::new(TrackingStatus::PickedUp, at(1, 8))),
+            Err(TrackingError::BeforeLabelCreated {
+                at: at(1, 8),
+                created_at: at(1, 9)
+            })
+        );
+        assert_eq!(
+            shipment.record(TrackingEvent::new(TrackingStatus::LabelCreated, at(2, 9))),
+            Err(TrackingError::DuplicateLabel)
+        );
+        assert_eq!(shipment.events().len(), 6);
+    }
+
+    #[test]
+    fn test_estimated_delivery_per_delivery_type() {
+        // Thursday
+        let created_at = Utc.with_ymd_and_hms(2024, 3, 7, 9, 0, 0).unwrap();
+        let estimate = |delivery_type| {
+            Shipment::new("TRK1", "A product", delivery_type, created_at).estimated_delivery()
+        };
+
+        assert_eq!(
+            estimate(DeliveryType::Express),
+            NaiveDate::from_ymd_opt(2024, 3, 8).unwrap()
+        );
+        assert_eq!(
+            estimate(DeliveryType::Insured),
+            NaiveDate::from_ymd_opt(2024, 3, 12).unwrap()
+        );
+        assert_eq!(
+            estimate(DeliveryType::Standard),
+            NaiveDate::from_ymd_opt(2024, 3, 14).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_tracking_numbers_round_trip_and_validate() {
+        let mut generator = TrackingNumberGenerator::new(12345678);
+        let delivery = DeliveryExpress {
+            base: Delivery::new("A product".to_string()),
+        };
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap();
+
+        let shipment = delivery.ship_with(&mut generator, now).unwrap();
+        assert_eq!(shipment.tracking_number, "EX123456785");
+        let number: TrackingNumber = shipment.tracking_number.parse().unwrap();
+        assert_eq!(number.delivery_type, DeliveryType::Express);
+        assert_eq!(number.serial, 12345678);
+
+        for delivery_type in [
+            DeliveryType::Express,
+            DeliveryType::Insured,
+            DeliveryType::Standard,
+        ] {
+            let number = generator.next_for(&delivery_type).unwrap();
+            assert_eq!(number.to_string().parse::<TrackingNumber>(), Ok(number));
+        }
+        assert_eq!(
+            "st-1234-5678-5".parse::<TrackingNumber>().unwrap().serial,
+            12345678
+        );
+
+        assert_eq!(
+            "EX12345678".parse::<TrackingNumber>(),
+            Err(TrackingNumberError::InvalidLength(10))
+        );
+        assert_eq!(
+            "ZZ123456785".parse::<TrackingNumber>(),
+            Err(TrackingNumberError::UnknownPrefix("ZZ".to_string()))
+        );
+        assert_eq!(
+            "EX12345A785".parse::<TrackingNumber>(),
+            Err(TrackingNumberError::InvalidSerial("12345A785".to_string()))
+        );
+        assert_eq!(
+            "EXé23456785".parse::<TrackingNumber>(),
+            Err(TrackingNumberError::InvalidSerial(
+                "EXÉ23456785".to_string()
+            ))
+        );
+        // Swapped digits are caught
+        assert_eq!(
+            "EX213456785".parse::<TrackingNumber>(),
+            Err(TrackingNumberError::InvalidCheckDigit {
+                expected: 3,
+                found: 5
+            })
+        );
+
+        let mut full = TrackingNumberGenerator::new(99_999_999);
+        assert!(full.next_for(&DeliveryType::Standard).is_ok());
+        assert_eq!(
+            full.next_for(&DeliveryType::Standard),
+            Err(TrackingNumberError::SerialTooLarge(100_000_000))
+        );
+    }
 }
@@ -1,3 +1,4 @@
+use chrono::{Duration, Utc};
 use clean_code_notes_exercises::exercises::exercise_06::*;
 
 #[cfg(test)]
@@ -13,11 +14,15 @@ mod exercise_06_test {
             member.signup("test@test.com", "password"),
             "Member test@test.com signed up"
         );
-        assert_eq!(
-            member.assign_role("admin"),
-            "Member test@test.com assigned role admin"
-        );
+        assert_eq!(member.role(), &Role::User);
         assert_eq!(member.login("test@test.com", "password"), true);
+
+        // Roles are assigned through the access control, which records who did it
+        let mut access = AccessControl::default();
+        let assignment = access.bootstrap_admin(&mut member, Utc::now()).unwrap();
+        assert_eq!(assignment.member, "test@test.com");
+        assert_eq!(assignment.role, Role::Admin);
+        assert_eq!(member.role(), &Role::Admin);
     }
 
     #[test]
@@ -27,21 +32,21 @@ mod exercise_06_test {
             web_printer.print("Some data"),
             Ok("Web Printer Printing: Some data".to_string())
         );
-        assert_eq!(web_printer.print(""), Err("Data is empty"));
+        assert_eq!(web_printer.print(""), Err(PrintError::EmptyData));
 
         let pdf_printer = PDFPrinter;
         assert_eq!(
             pdf_printer.print("Some data"),
             Ok("PDF Printer Printing: Some data".to_string())
         );
-        assert_eq!(pdf_printer.print(""), Err("Data is empty"));
+        assert_eq!(pdf_printer.print(""), Err(PrintError::EmptyData));
 
         let page_printer = PagePrinter;
         assert_eq!(
             page_printer.print("Some data"),
             Ok("Page Printer Printing: Some data".to_string())
         );
-        assert_eq!(page_printer.print(""), Err("Data is empty"));
+        assert_eq!(page_printer.print(""), Err(PrintError::EmptyData));
     }
 
     #[test]
@@ -79,4 +84,185 @@ mod exercise_06_test {
         let in_memory_database = InMemoryDatabase;
         assert_eq!(in_memory_database.store_data("Some data"), ());
     }
+
+    fn auth_service() -> AuthService {
+        AuthService::new(AuthConfig {
+            hash_cost: 4,
+            max_failed_logins: 3,
+            max_attempts_per_window: 5,
+            ..AuthConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_auth_service_sessions() {
+        let mut auth = auth_service();
+        let now = Utc::now();
+        let member = Member::new("test@test.com", "password");
+
+        assert_eq!(
+            auth.import_member(&member),
+            Ok(&MemberProfile {
+                email: "test@test.com".to_string(),
+                role: Role::User,
+            })
+        );
+        assert_eq!(
+            auth.signup("test@test.com", "other_password"),
+            Err(AuthError::MemberExists("test@test.com".to_string()))
+        );
+
+        let session = auth.login("test@test.com", "password", now).unwrap();
+        assert_eq!(auth.validate_session(&session.token, now), Ok(&session));
+        assert_eq!(
+            auth.validate_session(&session.token, session.expires_at),
+            Err(AuthError::SessionExpired)
+        );
+        assert_eq!(auth.purge_expired_sessions(session.expires_at), 1);
+        assert_eq!(
+            auth.validate_session(&session.token, now),
+            Err(AuthError::SessionNotFound)
+        );
+
+        auth.login("test@test.com", "password", now).unwrap();
+        auth.login("test@test.com", "password", now).unwrap();
+        assert_eq!(auth.revoke_member_sessions("test@test.com"), 2);
+        assert_eq!(
+            auth.revoke_session("unknown"),
+            Err(AuthError::SessionNotFound)
+        );
+    }
+
+    #[test]
+    fn test_auth_service_lockout() {
+        let mut auth = auth_service();
+        let now = Utc::now();
+        auth.signup("test@test.com", "password").unwrap();
+
+        for _ in 0..3 {
+            assert_eq!(
+                auth.login("test@test.com", "wrong", now),
+                Err(AuthError::InvalidCredentials)
+            );
+        }
+
+        let until = now + auth.config.lockout_duration;
+        assert_eq!(
+            auth.login("test@test.com", "password", now),
+            Err(AuthError::LockedOut { until })
+        );
+        assert!(auth.login("test@test.com", "password", until).is_ok());
+    }
+
+    #[test]
+    fn test_auth_service_rate_limit() {
+        let mut auth = auth_service();
+        let now = Utc::now();
+        auth.signup("test@test.com", "password").unwrap();
+
+        for second in 0..5 {
+            let attempt_at = now + Duration::seconds(second);
+            assert!(auth.login("test@test.com", "password", attempt_at).is_ok());
+        }
+
+        let retry_at = now + auth.config.attempts_window;
+        assert_eq!(
+            auth.login("test@test.com", "password", now + Duration::seconds(5)),
+            Err(AuthError::RateLimited { retry_at })
+        );
+        assert!(auth.login("test@test.com", "password", retry_at).is_ok());
+    }
+
+    #[test]
+    fn test_auth_service_unknown_emails() {
+        let mut auth = auth_service();
+        let now = Utc::now();
+        auth.signup("test@test.com", "password").unwrap();
+
+        assert_eq!(
+            auth.login("nobody@test.com", "password", now),
+            Err(AuthError::InvalidCredentials)
+        );
+        for n in 0..100 {
+            let email = format!("guess{}@test.com", n);
+            let _ = auth.login(&email, "password", now);
+        }
+
+        let later = now + auth.config.lockout_duration;
+        assert_eq!(auth.purge_expired_attempts(now), 0);
+        assert!(auth.purge_expired_attempts(later) > 0);
+        assert_eq!(auth.purge_expired_attempts(later), 0);
+        assert!(auth.login("test@test.com", "password", later).is_ok());
+    }
+
+    #[test]
+    fn test_role_based_access_control() {
+        let mut access = AccessControl::default();
+        let mut member = Member::new("test@test.com", "password");
+
+        assert!(access.can(&member, Action::Create, Resource::Content));
+        assert!(access.can(&member, Action::Read, Resource::Content));
+        assert!(!access.can(&member, Action::Update, Resource::Settings));
+        assert_eq!(
+            access.authorize(&member, Action::Delete, Resource::Member),
+            Err(AccessError::Forbidden {
+                role: Role::User,
+                action: Action::Delete,
+                resource: Resource::Member,
+            })
+        );
+
+        access.bootstrap_admin(&mut member, Utc::now()).unwrap();
+        assert!(access.can(&member, Action::Update, Resource::Settings));
+        assert!(access.can(&member, Action::Delete, Resource::Content));
+        assert!(access.can(&member, Action::Read, Resource::Content));
+        assert_eq!(
+            "owner".parse::<Role>(),
+            Err(AccessError::UnknownRole("owner".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_role_assignment_audit() {
+        let mut access = AccessControl::default();
+        let now = Utc::now();
+        let mut admin = Member::new("admin@test.com", "password");
+        access.bootstrap_admin(&mut admin, now).unwrap();
+        let mut moderator = Member::new("moderator@test.com", "password");
+        let mut member = Member::new("test@test.com", "password");
+
+        access
+            .assign_role(&admin, &mut moderator, Role::Moderator, now)
+            .unwrap();
+        assert_eq!(
+            access.assign_role(&moderator, &mut member, Role::Moderator, now),
+            Err(AccessError::Forbidden {
+                role: Role::Moderator,
+                action: Action::AssignRole,
+                resource: Resource::Member,
+            })
+        );
+
+        let later = now + Duration::minutes(5);
+        access
+            .assign_role(&admin, &mut member, Role::Admin, later)
+            .unwrap();
+        assert_eq!(member.role(), &Role::Admin);
+        assert_eq!(access.audit_log().len(), 3);
+        assert_eq!(
+            access.bootstrap_admin(&mut moderator, later),
+            Err(AccessError::AdminExists)
+        );
+        assert_eq!(moderator.role(), &Role::Moderator);
+        assert_eq!(
+            access.assignments_for("test@test.com"),
+            vec![&RoleAssignment {
+                member: "test@test.com".to_string(),
+                previous_role: Role::User,
+                role: Role::Admin,
+                assigned_by: "admin@test.com".to_string(),
+                assigned_at: later,
+            }]
+        );
+    }
 }